@@ -1,6 +1,12 @@
-use core::iter::ExactSizeIterator;
+use core::convert::TryFrom;
+use core::iter::{Cloned, ExactSizeIterator};
+use core::slice;
 
-use super::Addr;
+use arrayvec::ArrayVec;
+
+use crate::params::{self, AtParam};
+
+use super::{Addr, BROADCAST_ADDR};
 
 pub const START: u8 = 0x7E;
 pub const ESCAPE: u8 = 0x7D;
@@ -11,6 +17,10 @@ pub const XOFF: u8 = 0x13;
 pub enum ApiPackError {
     TooShort,
     TooLong,
+    /// The frame is one only the radio sends, or sending it is not supported yet.
+    NotSendable,
+    /// The buffer to pack into cannot hold the whole frame.
+    BufferTooSmall,
 }
 
 enum FramePackingState {
@@ -22,14 +32,54 @@ enum FramePackingState {
     Done,
 }
 
+/// How frames are encoded on the wire, selected on the radio with the `AP` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameFormat {
+    /// API mode without escaping (AP=1).
+    Unescaped,
+    /// API mode with escaping (AP=2). Every byte after the start delimiter that `needs_escape` is
+    /// sent as `ESCAPE` followed by the byte XORed with 0x20.
+    Escaped,
+}
+
+impl FrameFormat {
+    pub fn is_escaped(self) -> bool {
+        self == FrameFormat::Escaped
+    }
+
+    /// The value of `AP` selecting this format.
+    pub fn ap_value(self) -> u8 {
+        match self {
+            FrameFormat::Unescaped => 1,
+            FrameFormat::Escaped => 2,
+        }
+    }
+}
+
+/// Fails with the value itself if it is not an API mode, such as 0 for transparent mode.
+impl TryFrom<u8> for FrameFormat {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<FrameFormat, u8> {
+        match val {
+            1 => Ok(FrameFormat::Unescaped),
+            2 => Ok(FrameFormat::Escaped),
+            _ => Err(val),
+        }
+    }
+}
+
 // TODO: encryption
-// TODO: escaped mode
 pub struct FramePacker<I> {
     state: FramePackingState,
     escaped: bool,
     encrypted: bool,
     data: I,
+    /// Length of `data` as checked by `new`, which the header is written from.
+    len: u16,
     checksum: u8,
+    /// Second half of an escaped byte, sent on the next call to `next`.
+    escape_pending: Option<u8>,
 }
 
 impl<I> FramePacker<I>
@@ -37,11 +87,13 @@ where
     I: ExactSizeIterator<Item = u8>,
 {
     pub fn new(data: I, escaped: bool, encrypted: bool) -> Result<FramePacker<I>, ApiPackError> {
-        if data.len() == 0 {
+        // Only asked for once so the header always matches what was checked
+        let len = data.len();
+        if len == 0 {
             return Err(ApiPackError::TooShort);
         }
 
-        if data.len() > u16::max_value() as usize {
+        if len > u16::max_value() as usize {
             return Err(ApiPackError::TooLong);
         }
 
@@ -50,19 +102,36 @@ where
             escaped,
             encrypted,
             data,
+            len: len as u16,
             checksum: 0,
+            escape_pending: None,
         })
     }
-}
 
-impl<I> Iterator for FramePacker<I>
-where
-    I: ExactSizeIterator<Item = u8>,
-{
-    // TODO: get working with references
-    type Item = u8;
+    /// Number of bytes left to produce, if it is known up front.
+    ///
+    /// Only unescaped frames have a known length, as how many bytes escaping adds depends on data
+    /// that has not been seen yet. `size_hint` still bounds the length of escaped frames.
+    pub fn exact_len(&self) -> Option<usize> {
+        if self.escaped {
+            None
+        } else {
+            Some(self.unescaped_len())
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Bytes left before escaping, not counting a pending escaped byte.
+    fn unescaped_len(&self) -> usize {
+        match self.state {
+            FramePackingState::Start => 3 + self.len as usize + 1,
+            FramePackingState::LenH => 2 + self.len as usize + 1,
+            FramePackingState::LenL => 1 + self.len as usize + 1,
+            FramePackingState::Data => self.data.len() + 1,
+            FramePackingState::Done => 0,
+        }
+    }
+
+    fn next_unescaped(&mut self) -> Option<u8> {
         match self.state {
             FramePackingState::Start => {
                 self.state = FramePackingState::LenH;
@@ -70,11 +139,11 @@ where
             }
             FramePackingState::LenH => {
                 self.state = FramePackingState::LenL;
-                Some((self.data.len() >> 8) as u8)
+                Some((self.len >> 8) as u8)
             }
             FramePackingState::LenL => {
                 self.state = FramePackingState::Data;
-                Some(self.data.len() as u8)
+                Some(self.len as u8)
             }
             FramePackingState::Data => {
                 if let Some(val) = self.data.next() {
@@ -90,21 +159,73 @@ where
     }
 }
 
+impl<I> Iterator for FramePacker<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    // TODO: get working with references
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(val) = self.escape_pending.take() {
+            return Some(val);
+        }
+
+        let is_start = matches!(self.state, FramePackingState::Start);
+        let val = self.next_unescaped()?;
+        // The start delimiter is the only byte that is never escaped
+        if self.escaped && !is_start && needs_escape(val) {
+            self.escape_pending = Some(val ^ 0x20);
+            Some(ESCAPE)
+        } else {
+            Some(val)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.escape_pending.is_some() as usize;
+        let len = self.unescaped_len();
+        if !self.escaped {
+            return (pending + len, Some(pending + len));
+        }
+
+        // Every byte but the start delimiter might need escaping
+        let start = matches!(self.state, FramePackingState::Start) as usize;
+        (pending + len, Some(pending + start + 2 * (len - start)))
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum ApiUnpackError {
     NoStart,
     BadLength(usize),
     BadChecksum(u8),
+    /// The frame was said to be escaped. Unescape it with `unescape_into` first.
+    Escaped,
+}
+
+/// Why `ApiData::parse` failed, with the type of the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApiParseError {
+    /// A frame type this crate does not know.
+    UnknownType(u8),
+    /// A frame of a known type that is too short, too long or holds a bad IO sample.
+    Malformed(u8),
 }
 
 /// Returns the data portion of the frame and any remaining part of the buffer on success.
 ///
-/// Currently escaped and encrypted modes are not supported.
+/// Only unescaped frames can be unpacked in place, so `escaped` frames are refused with
+/// `ApiUnpackError::Escaped`. Encrypted mode is not supported.
 pub fn unpack_frame(
     buf: &[u8],
     escaped: bool,
     _encryption: bool,
 ) -> Result<(&[u8], &[u8]), ApiUnpackError> {
+    if escaped {
+        return Err(ApiUnpackError::Escaped);
+    }
+
     if buf.is_empty() {
         return Err(ApiUnpackError::NoStart);
     }
@@ -113,12 +234,8 @@ pub fn unpack_frame(
         return Err(ApiUnpackError::BadLength(4));
     }
 
-    if !escaped {
-        if buf[0] != START {
-            return Err(ApiUnpackError::NoStart);
-        }
-    } else {
-        unimplemented!()
+    if buf[0] != START {
+        return Err(ApiUnpackError::NoStart);
     }
 
     let buf = &buf[1..];
@@ -140,6 +257,175 @@ pub fn unpack_frame(
     }
 }
 
+/// Returns the total length of the frame at the start of `buf`, including the start delimiter,
+/// length and checksum, from its header alone.
+///
+/// Returns `None` until the 3 byte header is available, or if `buf` does not start with a start
+/// delimiter. Only unescaped frames are supported.
+pub fn frame_len_from_header(buf: &[u8]) -> Option<usize> {
+    match *buf {
+        [START, len_h, len_l, ..] => Some(3 + ((len_h as usize) << 8 | len_l as usize) + 1),
+        _ => None,
+    }
+}
+
+/// Packs a TX request into `out`, returning the length of the frame, so it can be handed to DMA
+/// without going through a queue.
+///
+/// If `out` cannot hold the whole frame, `ApiPackError::BufferTooSmall` is returned and `out` is
+/// left partly written.
+pub fn pack_into<I>(
+    req: TxRequestIter<I>,
+    escaped: bool,
+    out: &mut [u8],
+) -> Result<usize, ApiPackError>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    let packer = FramePacker::new(req, escaped, false)?;
+    if packer.size_hint().0 > out.len() {
+        return Err(ApiPackError::BufferTooSmall);
+    }
+
+    let mut len = 0;
+    for byte in packer {
+        *out.get_mut(len).ok_or(ApiPackError::BufferTooSmall)? = byte;
+        len += 1;
+    }
+    Ok(len)
+}
+
+/// Whether a byte must be escaped when sent in escaped API mode (AP=2).
+pub fn needs_escape(byte: u8) -> bool {
+    byte == START || byte == ESCAPE || byte == XON || byte == XOFF
+}
+
+/// Escapes `src` into `dst`, returning the number of bytes written.
+///
+/// Each of the special bytes (start delimiter, escape, XON and XOFF) is replaced by `ESCAPE`
+/// followed by the byte XORed with 0x20. Fails if `dst` is too small to hold the result.
+pub fn escape_into(src: &[u8], dst: &mut [u8]) -> Result<usize, ()> {
+    let mut len = 0;
+    for &byte in src {
+        if needs_escape(byte) {
+            if len + 2 > dst.len() {
+                return Err(());
+            }
+            dst[len] = ESCAPE;
+            dst[len + 1] = byte ^ 0x20;
+            len += 2;
+        } else {
+            if len + 1 > dst.len() {
+                return Err(());
+            }
+            dst[len] = byte;
+            len += 1;
+        }
+    }
+    Ok(len)
+}
+
+/// Reverses `escape_into`, returning the number of bytes written to `dst`.
+///
+/// Fails if `dst` is too small or if `src` ends with a dangling `ESCAPE`.
+pub fn unescape_into(src: &[u8], dst: &mut [u8]) -> Result<usize, ()> {
+    let mut len = 0;
+    let mut iter = src.iter();
+    while let Some(&byte) = iter.next() {
+        let byte = if byte == ESCAPE {
+            match iter.next() {
+                Some(&escaped) => escaped ^ 0x20,
+                None => return Err(()),
+            }
+        } else {
+            byte
+        };
+
+        if len >= dst.len() {
+            return Err(());
+        }
+        dst[len] = byte;
+        len += 1;
+    }
+    Ok(len)
+}
+
+/// Encodes a `u8` AT command parameter.
+///
+/// The XBee expects all numeric AT command parameters in big-endian byte order. The returned
+/// array can be passed directly as the `params` of an AT command.
+pub fn at_param_u8(v: u8) -> [u8; 1] {
+    [v]
+}
+
+/// Encodes a `u16` AT command parameter in big-endian byte order.
+pub fn at_param_u16(v: u16) -> [u8; 2] {
+    [(v >> 8) as u8, v as u8]
+}
+
+/// Encodes a `u32` AT command parameter in big-endian byte order.
+pub fn at_param_u32(v: u32) -> [u8; 4] {
+    [(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+}
+
+/// Encodes a `u64` AT command parameter in big-endian byte order.
+pub fn at_param_u64(v: u64) -> [u8; 8] {
+    [
+        (v >> 56) as u8,
+        (v >> 48) as u8,
+        (v >> 40) as u8,
+        (v >> 32) as u8,
+        (v >> 24) as u8,
+        (v >> 16) as u8,
+        (v >> 8) as u8,
+        v as u8,
+    ]
+}
+
+/// Data of an AT command response, with conversions from the big-endian values the radio sends.
+///
+/// The numeric conversions return `None` unless the data is exactly as long as the type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtResponseData<'a>(pub &'a [u8]);
+
+impl<'a> AtResponseData<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn as_u8(&self) -> Option<u8> {
+        match *self.0 {
+            [v] => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        self.as_uint(2).map(|v| v as u16)
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_uint(4).map(|v| v as u32)
+    }
+
+    /// Also decodes the `SH` and `SL` responses put together, high half first.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_uint(8)
+    }
+
+    /// Text such as the node identifier from `NI`, or `None` if it is not valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.0).ok()
+    }
+
+    fn as_uint(&self, len: usize) -> Option<u64> {
+        if self.0.len() != len {
+            return None;
+        }
+        Some(self.0.iter().fold(0, |v, &b| v << 8 | b as u64))
+    }
+}
+
 bitflags! {
     pub struct TxOptions: u8 {
         const DISABLE_ACK = 0x01;
@@ -147,6 +433,22 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Options of a remote AT command (0x17) frame.
+    pub struct RemoteAtOptions: u8 {
+        const DISABLE_ACK = 0x01;
+        /// Applies the change straight away rather than queueing it until `AC` or another
+        /// command with this option.
+        const APPLY_CHANGES = 0x02;
+    }
+}
+
+impl Default for RemoteAtOptions {
+    fn default() -> RemoteAtOptions {
+        RemoteAtOptions::APPLY_CHANGES
+    }
+}
+
 bitflags! {
     pub struct RxOptions: u8 {
         const ADDR_BROADCAST = 0x02;
@@ -154,6 +456,13 @@ bitflags! {
     }
 }
 
+impl RxOptions {
+    /// Whether the packet was sent to every node rather than to this one specifically.
+    pub fn is_broadcast(&self) -> bool {
+        self.intersects(RxOptions::ADDR_BROADCAST | RxOptions::PAN_BROADCAST)
+    }
+}
+
 /// bitfield
 /// [0..2] reserved
 /// [3..6] analog
@@ -178,8 +487,21 @@ bitflags! {
 }
 
 impl ChannelIndicator {
+    /// Number of bytes in one set of samples of the enabled channels.
+    fn sample_set_len(&self) -> usize {
+        let digital = if self.contains_digital() { 2 } else { 0 };
+        let analog = (*self
+            & (ChannelIndicator::A0
+                | ChannelIndicator::A1
+                | ChannelIndicator::A2
+                | ChannelIndicator::A3))
+            .bits()
+            .count_ones() as usize;
+        digital + analog * 2
+    }
+
     fn contains_digital(&self) -> bool {
-        self.contains(
+        self.intersects(
             ChannelIndicator::D0
                 | ChannelIndicator::D1
                 | ChannelIndicator::D2
@@ -193,7 +515,307 @@ impl ChannelIndicator {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// One set of IO line samples, as sent in an IO RX packet or a response to `IS`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IoSample {
+    pub samples: u8,
+    pub channel_indicator: ChannelIndicator,
+    /// Levels of the enabled digital lines, bit n for DIOn.
+    pub digital_samples: Option<u16>,
+    /// 10-bit readings of A0 to A3, if enabled.
+    pub analog_samples: [Option<u16>; 4],
+}
+
+impl IoSample {
+    /// Parses the first set of samples, or `None` unless `data` holds exactly as many sets as it
+    /// says, each with the channels it says are enabled.
+    pub fn parse(data: &[u8]) -> Option<IoSample> {
+        if data.len() < 3 {
+            return None;
+        }
+        let samples = data[0];
+        let channel_indicator =
+            ChannelIndicator::from_bits_truncate(((data[1] as u16) << 8) | (data[2] as u16));
+        let mut rest = &data[3..];
+        if samples == 0 || rest.len() != samples as usize * channel_indicator.sample_set_len() {
+            return None;
+        }
+
+        let mut next_u16 = || {
+            let value = ((rest[0] as u16) << 8) | (rest[1] as u16);
+            rest = &rest[2..];
+            value
+        };
+        let digital_samples = if channel_indicator.contains_digital() {
+            Some(next_u16())
+        } else {
+            None
+        };
+        let mut analog_samples = [None; 4];
+        let analog = [
+            ChannelIndicator::A0,
+            ChannelIndicator::A1,
+            ChannelIndicator::A2,
+            ChannelIndicator::A3,
+        ];
+        for (sample, &channel) in analog_samples.iter_mut().zip(analog.iter()) {
+            if channel_indicator.contains(channel) {
+                *sample = Some(next_u16());
+            }
+        }
+
+        Some(IoSample {
+            samples,
+            channel_indicator,
+            digital_samples,
+            analog_samples,
+        })
+    }
+}
+
+/// AT commands of the S2C 802.15.4 firmware.
+///
+/// Functions taking an AT command accept either one of these or its two characters, such as
+/// `[b'D', b'L']`.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AtCmd {
+    // Addressing
+    /// Destination address high (`DH`).
+    Dh,
+    /// Destination address low (`DL`).
+    Dl,
+    /// 16-bit source address (`MY`).
+    My,
+    /// Serial number high (`SH`).
+    Sh,
+    /// Serial number low (`SL`).
+    Sl,
+    /// Node identifier (`NI`).
+    Ni,
+    // Network
+    /// Channel (`CH`).
+    Ch,
+    /// PAN ID (`ID`).
+    Id,
+    /// Coordinator enable (`CE`).
+    Ce,
+    /// End device association (`A1`).
+    A1,
+    /// Coordinator association (`A2`).
+    A2,
+    /// Association indication (`AI`).
+    Ai,
+    /// Scan channels (`SC`).
+    Sc,
+    /// Scan duration (`SD`).
+    Sd,
+    /// Node discovery time (`NT`).
+    Nt,
+    /// AES encryption enable (`EE`).
+    Ee,
+    /// AES encryption key (`KY`).
+    Ky,
+    // RF
+    /// Power level (`PL`).
+    Pl,
+    /// CCA threshold (`CA`).
+    Ca,
+    /// XBee retries (`RR`).
+    Rr,
+    /// Random delay slots (`RN`).
+    Rn,
+    /// MAC mode (`MM`).
+    Mm,
+    // Serial
+    /// Interface data rate (`BD`).
+    Bd,
+    /// API enable (`AP`).
+    Ap,
+    /// Packetization timeout (`RO`).
+    Ro,
+    // IO, with D0 to D8 and P0 to P2 configuring the lines of `DioPin`
+    D0,
+    D1,
+    D2,
+    D3,
+    D4,
+    D5,
+    D6,
+    D7,
+    D8,
+    P0,
+    P1,
+    P2,
+    /// IO sample rate (`IR`).
+    Ir,
+    /// Digital IO change detection (`IC`).
+    Ic,
+    /// Force sample (`IS`).
+    Is,
+    // Sleep
+    /// Sleep mode (`SM`).
+    Sm,
+    /// Cyclic sleep period (`SP`).
+    Sp,
+    /// Time before sleep (`ST`).
+    St,
+    // Diagnostics
+    /// Last packet RSSI (`DB`).
+    Db,
+    /// Firmware version (`VR`).
+    Vr,
+    /// Hardware version (`HV`).
+    Hv,
+    /// Supply voltage (`%V`).
+    PercentV,
+    // Execution
+    /// Apply changes (`AC`).
+    Ac,
+    /// Write to flash (`WR`).
+    Wr,
+    /// Software reset (`FR`).
+    Fr,
+    /// Network reset (`NR`).
+    Nr,
+    /// Node discovery (`ND`).
+    Nd,
+    /// Energy detect (`ED`).
+    Ed,
+}
+
+impl AtCmd {
+    /// Every command, in declaration order.
+    pub const ALL: [AtCmd; 53] = [
+        AtCmd::Dh,
+        AtCmd::Dl,
+        AtCmd::My,
+        AtCmd::Sh,
+        AtCmd::Sl,
+        AtCmd::Ni,
+        AtCmd::Ch,
+        AtCmd::Id,
+        AtCmd::Ce,
+        AtCmd::A1,
+        AtCmd::A2,
+        AtCmd::Ai,
+        AtCmd::Sc,
+        AtCmd::Sd,
+        AtCmd::Nt,
+        AtCmd::Ee,
+        AtCmd::Ky,
+        AtCmd::Pl,
+        AtCmd::Ca,
+        AtCmd::Rr,
+        AtCmd::Rn,
+        AtCmd::Mm,
+        AtCmd::Bd,
+        AtCmd::Ap,
+        AtCmd::Ro,
+        AtCmd::D0,
+        AtCmd::D1,
+        AtCmd::D2,
+        AtCmd::D3,
+        AtCmd::D4,
+        AtCmd::D5,
+        AtCmd::D6,
+        AtCmd::D7,
+        AtCmd::D8,
+        AtCmd::P0,
+        AtCmd::P1,
+        AtCmd::P2,
+        AtCmd::Ir,
+        AtCmd::Ic,
+        AtCmd::Is,
+        AtCmd::Sm,
+        AtCmd::Sp,
+        AtCmd::St,
+        AtCmd::Db,
+        AtCmd::Vr,
+        AtCmd::Hv,
+        AtCmd::PercentV,
+        AtCmd::Ac,
+        AtCmd::Wr,
+        AtCmd::Fr,
+        AtCmd::Nr,
+        AtCmd::Nd,
+        AtCmd::Ed,
+    ];
+
+    pub fn as_bytes(self) -> [u8; 2] {
+        match self {
+            AtCmd::Dh => [b'D', b'H'],
+            AtCmd::Dl => [b'D', b'L'],
+            AtCmd::My => [b'M', b'Y'],
+            AtCmd::Sh => [b'S', b'H'],
+            AtCmd::Sl => [b'S', b'L'],
+            AtCmd::Ni => [b'N', b'I'],
+            AtCmd::Ch => [b'C', b'H'],
+            AtCmd::Id => [b'I', b'D'],
+            AtCmd::Ce => [b'C', b'E'],
+            AtCmd::A1 => [b'A', b'1'],
+            AtCmd::A2 => [b'A', b'2'],
+            AtCmd::Ai => [b'A', b'I'],
+            AtCmd::Sc => [b'S', b'C'],
+            AtCmd::Sd => [b'S', b'D'],
+            AtCmd::Nt => [b'N', b'T'],
+            AtCmd::Ee => [b'E', b'E'],
+            AtCmd::Ky => [b'K', b'Y'],
+            AtCmd::Pl => [b'P', b'L'],
+            AtCmd::Ca => [b'C', b'A'],
+            AtCmd::Rr => [b'R', b'R'],
+            AtCmd::Rn => [b'R', b'N'],
+            AtCmd::Mm => [b'M', b'M'],
+            AtCmd::Bd => [b'B', b'D'],
+            AtCmd::Ap => [b'A', b'P'],
+            AtCmd::Ro => [b'R', b'O'],
+            AtCmd::D0 => [b'D', b'0'],
+            AtCmd::D1 => [b'D', b'1'],
+            AtCmd::D2 => [b'D', b'2'],
+            AtCmd::D3 => [b'D', b'3'],
+            AtCmd::D4 => [b'D', b'4'],
+            AtCmd::D5 => [b'D', b'5'],
+            AtCmd::D6 => [b'D', b'6'],
+            AtCmd::D7 => [b'D', b'7'],
+            AtCmd::D8 => [b'D', b'8'],
+            AtCmd::P0 => [b'P', b'0'],
+            AtCmd::P1 => [b'P', b'1'],
+            AtCmd::P2 => [b'P', b'2'],
+            AtCmd::Ir => [b'I', b'R'],
+            AtCmd::Ic => [b'I', b'C'],
+            AtCmd::Is => [b'I', b'S'],
+            AtCmd::Sm => [b'S', b'M'],
+            AtCmd::Sp => [b'S', b'P'],
+            AtCmd::St => [b'S', b'T'],
+            AtCmd::Db => [b'D', b'B'],
+            AtCmd::Vr => [b'V', b'R'],
+            AtCmd::Hv => [b'H', b'V'],
+            AtCmd::PercentV => [b'%', b'V'],
+            AtCmd::Ac => [b'A', b'C'],
+            AtCmd::Wr => [b'W', b'R'],
+            AtCmd::Fr => [b'F', b'R'],
+            AtCmd::Nr => [b'N', b'R'],
+            AtCmd::Nd => [b'N', b'D'],
+            AtCmd::Ed => [b'E', b'D'],
+        }
+    }
+
+    /// The command with these two characters, or `None` if it is not one of `AtCmd`.
+    pub fn from_bytes(bytes: [u8; 2]) -> Option<AtCmd> {
+        AtCmd::ALL
+            .iter()
+            .cloned()
+            .find(|cmd| cmd.as_bytes() == bytes)
+    }
+}
+
+impl From<AtCmd> for [u8; 2] {
+    fn from(cmd: AtCmd) -> [u8; 2] {
+        cmd.as_bytes()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AtCommandStatus {
     Ok = 0,
     Error = 1,
@@ -203,7 +825,23 @@ pub enum AtCommandStatus {
     Unknown,
 }
 
-#[derive(Debug, PartialEq)]
+/// Fails with the code itself if it is not a known status.
+impl TryFrom<u8> for AtCommandStatus {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<AtCommandStatus, u8> {
+        match val {
+            0 => Ok(AtCommandStatus::Ok),
+            1 => Ok(AtCommandStatus::Error),
+            2 => Ok(AtCommandStatus::InvalidCommand),
+            3 => Ok(AtCommandStatus::InvalidParam),
+            4 => Ok(AtCommandStatus::NoResponse),
+            _ => Err(val),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TxStatus {
     Standard = 0x00,
     NoAck = 0x01,
@@ -217,25 +855,27 @@ pub enum TxStatus {
     Unknown,
 }
 
-impl TxStatus {
-    // Done instead of using the "From" trait to keep the conversion private
-    fn from(val: u8) -> TxStatus {
+/// Fails with the code itself if it is not a known status.
+impl TryFrom<u8> for TxStatus {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<TxStatus, u8> {
         match val {
-            0x00 => TxStatus::Standard,
-            0x01 => TxStatus::NoAck,
-            0x02 => TxStatus::CcaFailure,
-            0x03 => TxStatus::TxPurged,
-            0x21 => TxStatus::NetworkAckFailure,
-            0x22 => TxStatus::NotConnected,
-            0x31 => TxStatus::InternalError,
-            0x32 => TxStatus::ResourceDepletion,
-            0x74 => TxStatus::PayloadTooLarge,
-            _ => TxStatus::Unknown,
+            0x00 => Ok(TxStatus::Standard),
+            0x01 => Ok(TxStatus::NoAck),
+            0x02 => Ok(TxStatus::CcaFailure),
+            0x03 => Ok(TxStatus::TxPurged),
+            0x21 => Ok(TxStatus::NetworkAckFailure),
+            0x22 => Ok(TxStatus::NotConnected),
+            0x31 => Ok(TxStatus::InternalError),
+            0x32 => Ok(TxStatus::ResourceDepletion),
+            0x74 => Ok(TxStatus::PayloadTooLarge),
+            _ => Err(val),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ModemStatus {
     HardwareReset = 0x00,
     WatchdogReset = 0x01,
@@ -246,109 +886,537 @@ pub enum ModemStatus {
     Unknown,
 }
 
-impl ModemStatus {
-    fn from(val: u8) -> ModemStatus {
+/// Fails with the code itself if it is not a known status.
+impl TryFrom<u8> for ModemStatus {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<ModemStatus, u8> {
         match val {
-            0x00 => ModemStatus::HardwareReset,
-            0x01 => ModemStatus::WatchdogReset,
-            0x02 => ModemStatus::AssociatedCoordinator,
-            0x03 => ModemStatus::DissociatedCoordinator,
-            0x06 => ModemStatus::CoordinatorNewNetwork,
-            0x0D => ModemStatus::InputVoltageTooHigh,
-            _ => ModemStatus::Unknown,
+            0x00 => Ok(ModemStatus::HardwareReset),
+            0x01 => Ok(ModemStatus::WatchdogReset),
+            0x02 => Ok(ModemStatus::AssociatedCoordinator),
+            0x03 => Ok(ModemStatus::DissociatedCoordinator),
+            0x06 => Ok(ModemStatus::CoordinatorNewNetwork),
+            0x0D => Ok(ModemStatus::InputVoltageTooHigh),
+            _ => Err(val),
         }
     }
 }
 
-// TODO: maybe make separate public facing enums for send and recieve packets
-#[derive(Debug, PartialEq)]
-pub enum ApiData<'a> {
-    // Send
-    TxRequest64Addr {
-        frame_id: u8,
-        dest_addr: u64,
-        options: TxOptions,
-        data: &'a [u8],
-    },
-    // Send
-    TxRequest16Addr {
-        frame_id: u8,
-        dest_addr: u16,
-        options: TxOptions,
-        data: &'a [u8],
-    },
-    // Send
-    AtCommand {
-        frame_id: u8,
-        at_cmd: [u8; 2],
-        params: &'a [u8],
-    },
-    // Send
-    AtCommandQueueParam {
-        frame_id: u8,
-        at_cmd: [u8; 2],
-        params: &'a [u8],
-    },
-    // Send
-    RemoteAtCommand {
-        frame_id: u8,
-        // TODO: combine the addr into an enum
-        dest_addr_64: u64,
-        dest_addr_16: u16,
-        at_cmd: [u8; 2],
-        params: &'a [u8],
-    },
-    // Receive
-    RxPacket64Addr {
-        source_addr: u64,
-        /// in -dBm
-        rssi: u8,
-        options: RxOptions,
-        data: &'a [u8],
-    },
-    // Receive
-    RxPacket16Addr {
-        source_addr: u16,
-        /// in -dBm
-        rssi: u8,
-        options: RxOptions,
-        data: &'a [u8],
-    },
-    // Receive
-    RxPacketIo64Addr {
-        source_addr: u64,
-        /// in -dBm
-        rssi: u8,
-        options: RxOptions,
-        samples: u8,
-        channel_indicator: ChannelIndicator,
-        digital_samples: Option<u16>,
-        analog_samples: [Option<u16>; 4],
-    },
-    // Receive
-    RxPacketIo16Addr {
-        source_addr: u16,
-        /// in -dBm
-        rssi: u8,
-        options: RxOptions,
-        samples: u8,
-        channel_indicator: ChannelIndicator,
-        digital_samples: Option<u16>,
-        analog_samples: [Option<u16>; 4],
-    },
-    // Receive
-    AtCommandResponse {
-        frame_id: u8,
-        at_cmd: [u8; 2],
-        status: AtCommandStatus,
-        data: &'a [u8],
-    },
-    // Receive
-    TxStatus {
-        frame_id: u8,
-        status: TxStatus,
-    },
-    // Receive
+/// Association state reported by the `AI` command.
+///
+/// Codes 0x01 through 0x13 are reported by the 802.15.4 firmware, 0x21 and up by the Zigbee
+/// firmware.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AssociationStatus {
+    Success = 0x00,
+    ActiveScanTimeout = 0x01,
+    ActiveScanNoPans = 0x02,
+    /// A PAN was found, but its coordinator is not allowing association.
+    ActiveScanAssociationNotAllowed = 0x03,
+    /// A PAN was found, but the coordinator and end device do not agree on beacon support.
+    ActiveScanBeaconMismatch = 0x04,
+    ActiveScanPanIdMismatch = 0x05,
+    ActiveScanChannelMismatch = 0x06,
+    EnergyScanTimeout = 0x07,
+    CoordinatorStartFailed = 0x08,
+    CoordinatorInvalidParam = 0x09,
+    CoordinatorRealignment = 0x0A,
+    AssociationRequestNotSent = 0x0B,
+    AssociationRequestTimeout = 0x0C,
+    AssociationRequestInvalidParam = 0x0D,
+    AssociationRequestChannelAccessFailure = 0x0E,
+    CoordinatorNoAck = 0x0F,
+    CoordinatorNoReply = 0x10,
+    SyncLost = 0x12,
+    Disassociated = 0x13,
+    NoPanFound = 0x21,
+    NoValidPanFound = 0x22,
+    JoiningNotAllowed = 0x23,
+    NoJoinableBeacons = 0x24,
+    JoinFailed = 0x27,
+    Scanning = 0xFF,
+    Unknown,
+}
+
+impl AssociationStatus {
+    pub(crate) fn from(val: u8) -> AssociationStatus {
+        match val {
+            0x00 => AssociationStatus::Success,
+            0x01 => AssociationStatus::ActiveScanTimeout,
+            0x02 => AssociationStatus::ActiveScanNoPans,
+            0x03 => AssociationStatus::ActiveScanAssociationNotAllowed,
+            0x04 => AssociationStatus::ActiveScanBeaconMismatch,
+            0x05 => AssociationStatus::ActiveScanPanIdMismatch,
+            0x06 => AssociationStatus::ActiveScanChannelMismatch,
+            0x07 => AssociationStatus::EnergyScanTimeout,
+            0x08 => AssociationStatus::CoordinatorStartFailed,
+            0x09 => AssociationStatus::CoordinatorInvalidParam,
+            0x0A => AssociationStatus::CoordinatorRealignment,
+            0x0B => AssociationStatus::AssociationRequestNotSent,
+            0x0C => AssociationStatus::AssociationRequestTimeout,
+            0x0D => AssociationStatus::AssociationRequestInvalidParam,
+            0x0E => AssociationStatus::AssociationRequestChannelAccessFailure,
+            0x0F => AssociationStatus::CoordinatorNoAck,
+            0x10 => AssociationStatus::CoordinatorNoReply,
+            0x12 => AssociationStatus::SyncLost,
+            0x13 => AssociationStatus::Disassociated,
+            0x21 => AssociationStatus::NoPanFound,
+            0x22 => AssociationStatus::NoValidPanFound,
+            0x23 => AssociationStatus::JoiningNotAllowed,
+            0x24 => AssociationStatus::NoJoinableBeacons,
+            0x27 => AssociationStatus::JoinFailed,
+            0xFF => AssociationStatus::Scanning,
+            _ => AssociationStatus::Unknown,
+        }
+    }
+}
+
+/// High byte of the hardware version (`HV`) reported by XBee S2C modules.
+pub const S2C_HARDWARE_SERIES: u8 = 0x22;
+
+/// High byte of the firmware version (`VR`) of the 802.15.4 firmware.
+pub const IEEE_802_15_4_FIRMWARE_SERIES: u8 = 0x20;
+
+/// Whether a firmware version reported by `VR` belongs to the 802.15.4 firmware, the only
+/// firmware this crate supports.
+pub fn is_802_15_4_firmware(firmware_version: u16) -> bool {
+    (firmware_version >> 8) as u8 == IEEE_802_15_4_FIRMWARE_SERIES
+}
+
+/// Whether a hardware version reported by `HV` belongs to an XBee S2C, the only hardware this
+/// crate supports.
+pub fn is_s2c_hardware(hardware_version: u16) -> bool {
+    (hardware_version >> 8) as u8 == S2C_HARDWARE_SERIES
+}
+
+/// Sleep modes set with the `SM` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SleepMode {
+    NoSleep = 0,
+    /// Sleeps while the SLEEP_RQ pin is asserted, with the lowest current draw.
+    PinHibernate = 1,
+    /// Sleeps while the SLEEP_RQ pin is asserted, waking faster than `PinHibernate`.
+    PinDoze = 2,
+    /// Wakes every `SP` to poll its coordinator for data.
+    CyclicSleep = 4,
+    /// Like `CyclicSleep`, but can also be woken early through the SLEEP_RQ pin.
+    CyclicSleepPinWake = 5,
+}
+
+/// Fails with the value itself if it is not a sleep mode.
+impl TryFrom<u8> for SleepMode {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<SleepMode, u8> {
+        match val {
+            0 => Ok(SleepMode::NoSleep),
+            1 => Ok(SleepMode::PinHibernate),
+            2 => Ok(SleepMode::PinDoze),
+            4 => Ok(SleepMode::CyclicSleep),
+            5 => Ok(SleepMode::CyclicSleepPinWake),
+            _ => Err(val),
+        }
+    }
+}
+
+/// MAC modes set with the `MM` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MacMode {
+    /// Digi header, which filters out duplicate packets, with acknowledgements.
+    DigiWithAcks = 0,
+    /// Plain 802.15.4 without acknowledgements.
+    Ieee802154NoAcks = 1,
+    /// Plain 802.15.4 with acknowledgements.
+    Ieee802154WithAcks = 2,
+    /// Digi header without acknowledgements.
+    DigiNoAcks = 3,
+}
+
+/// Fails with the value itself if it is not a MAC mode.
+impl TryFrom<u8> for MacMode {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<MacMode, u8> {
+        match val {
+            0 => Ok(MacMode::DigiWithAcks),
+            1 => Ok(MacMode::Ieee802154NoAcks),
+            2 => Ok(MacMode::Ieee802154WithAcks),
+            3 => Ok(MacMode::DigiNoAcks),
+            _ => Err(val),
+        }
+    }
+}
+
+/// Transmit power levels set with the `PL` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PowerLevel {
+    Lowest = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    Highest = 4,
+}
+
+impl PowerLevel {
+    /// Approximate output power of the S2C at this level, in dBm, with boost mode (`PM`) on or
+    /// off. Boost mode is on by default and adds 2 dB at every level but the lowest.
+    pub fn dbm(self, boost: bool) -> i8 {
+        let dbm = match self {
+            PowerLevel::Lowest => return -5,
+            PowerLevel::Low => -1,
+            PowerLevel::Medium => 1,
+            PowerLevel::High => 3,
+            PowerLevel::Highest => 5,
+        };
+        if boost {
+            dbm
+        } else {
+            dbm - 2
+        }
+    }
+}
+
+/// Fails with the value itself if it is not a power level.
+impl TryFrom<u8> for PowerLevel {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<PowerLevel, u8> {
+        match val {
+            0 => Ok(PowerLevel::Lowest),
+            1 => Ok(PowerLevel::Low),
+            2 => Ok(PowerLevel::Medium),
+            3 => Ok(PowerLevel::High),
+            4 => Ok(PowerLevel::Highest),
+            _ => Err(val),
+        }
+    }
+}
+
+/// Standard serial interface rates set with the `BD` command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BaudRate {
+    Baud1200 = 0,
+    Baud2400 = 1,
+    Baud4800 = 2,
+    Baud9600 = 3,
+    Baud19200 = 4,
+    Baud38400 = 5,
+    Baud57600 = 6,
+    Baud115200 = 7,
+}
+
+impl BaudRate {
+    /// The rate in bits per second.
+    pub fn bps(self) -> u32 {
+        match self {
+            BaudRate::Baud1200 => 1200,
+            BaudRate::Baud2400 => 2400,
+            BaudRate::Baud4800 => 4800,
+            BaudRate::Baud9600 => 9600,
+            BaudRate::Baud19200 => 19200,
+            BaudRate::Baud38400 => 38400,
+            BaudRate::Baud57600 => 57600,
+            BaudRate::Baud115200 => 115_200,
+        }
+    }
+}
+
+/// Fails with the value itself if it is not a standard rate.
+impl TryFrom<u8> for BaudRate {
+    type Error = u8;
+
+    fn try_from(val: u8) -> Result<BaudRate, u8> {
+        match val {
+            0 => Ok(BaudRate::Baud1200),
+            1 => Ok(BaudRate::Baud2400),
+            2 => Ok(BaudRate::Baud4800),
+            3 => Ok(BaudRate::Baud9600),
+            4 => Ok(BaudRate::Baud19200),
+            5 => Ok(BaudRate::Baud38400),
+            6 => Ok(BaudRate::Baud57600),
+            7 => Ok(BaudRate::Baud115200),
+            _ => Err(val),
+        }
+    }
+}
+
+/// A node found by node discovery (`ND`), from one of the answers to it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NodeDiscoveryResult<'a> {
+    /// 16-bit address (`MY`), 0xFFFE if the node has none.
+    pub my: u16,
+    /// 64-bit serial number (`SH` and `SL`).
+    pub serial: u64,
+    /// Signal strength the node's answer arrived with, as a magnitude in -dBm.
+    pub rssi: u8,
+    /// Node identifier (`NI`).
+    pub identifier: &'a str,
+}
+
+impl<'a> NodeDiscoveryResult<'a> {
+    /// Parses the data of an answer to `ND`. Anything after the NUL ending the identifier is
+    /// ignored.
+    pub fn parse(data: &'a [u8]) -> Option<NodeDiscoveryResult<'a>> {
+        if data.len() < 11 {
+            return None;
+        }
+        let (header, identifier) = data.split_at(11);
+        let identifier = match identifier.iter().position(|&b| b == 0) {
+            Some(end) => &identifier[..end],
+            None => identifier,
+        };
+
+        Some(NodeDiscoveryResult {
+            my: AtResponseData(&header[..2]).as_u16()?,
+            serial: AtResponseData(&header[2..10]).as_u64()?,
+            rssi: header[10],
+            identifier: core::str::from_utf8(identifier).ok()?,
+        })
+    }
+}
+
+/// Energy read on each channel by an energy scan (`ED`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnergyScan {
+    readings: [Option<u8>; 16],
+}
+
+impl EnergyScan {
+    /// Channel of bit 0 of the scan channel mask (`SC`).
+    pub const FIRST_CHANNEL: u8 = 0x0B;
+
+    /// Parses the answer to `ED`, which has one reading for each channel set in `channel_mask`,
+    /// the scan channels (`SC`) it was made with. Fails if the number of readings does not match.
+    pub fn parse(channel_mask: u16, data: &[u8]) -> Option<EnergyScan> {
+        if data.len() != channel_mask.count_ones() as usize {
+            return None;
+        }
+
+        let mut readings = [None; 16];
+        let mut data = data.iter();
+        for (bit, reading) in readings.iter_mut().enumerate() {
+            if channel_mask & (1 << bit) != 0 {
+                *reading = data.next().cloned();
+            }
+        }
+        Some(EnergyScan { readings })
+    }
+
+    /// Energy on `channel` as a magnitude in -dBm, or `None` if it was not scanned.
+    pub fn reading(&self, channel: u8) -> Option<u8> {
+        let idx = channel.checked_sub(EnergyScan::FIRST_CHANNEL)?;
+        *self.readings.get(idx as usize)?
+    }
+
+    /// Each channel scanned with its energy as a magnitude in -dBm, lowest channel first.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.readings
+            .iter()
+            .zip(EnergyScan::FIRST_CHANNEL..)
+            .filter_map(|(reading, channel)| reading.map(|reading| (channel, reading)))
+    }
+
+    /// The scanned channel with the least energy on it, the lowest such channel if several
+    /// tie, or `None` if no channel was scanned.
+    pub fn quietest_channel(&self) -> Option<u8> {
+        // Readings are magnitudes, so the quietest channel has the largest
+        self.iter()
+            .fold(
+                None,
+                |quietest: Option<(u8, u8)>, (channel, reading)| match quietest {
+                    Some((_, best)) if best >= reading => quietest,
+                    _ => Some((channel, reading)),
+                },
+            )
+            .map(|(channel, _)| channel)
+    }
+}
+
+/// Converts a raw `%V` reading to millivolts, given the ADC reference voltage in millivolts, or
+/// `None` if the reading is wider than the ADC's 10 bits.
+pub(crate) fn supply_voltage_mv(raw: u16, vref_mv: u16) -> Option<u16> {
+    if raw < 1 << 10 {
+        Some((raw as u32 * vref_mv as u32 / 1024) as u16)
+    } else {
+        None
+    }
+}
+
+/// Frame type the radio answers a request frame of type `request` with, or `None` if it is not
+/// a request the radio answers. No answer is sent for a request with frame ID 0.
+pub fn expected_response_type(request: u8) -> Option<u8> {
+    match request {
+        // TX requests
+        0x00 | 0x01 => Some(0x89),
+        // AT commands, queued or not
+        0x08 | 0x09 => Some(0x88),
+        0x17 => Some(0x97),
+        _ => None,
+    }
+}
+
+/// Converts an RSSI given as a magnitude in -dBm, as in received packets and `DB`, to dBm.
+pub(crate) fn rssi_to_dbm(rssi: u8) -> i16 {
+    -(rssi as i16)
+}
+
+/// IO lines that can be configured with `D0` through `D8` and `P0` through `P2`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DioPin {
+    D0,
+    D1,
+    D2,
+    D3,
+    D4,
+    D5,
+    D6,
+    D7,
+    D8,
+    P0,
+    P1,
+    P2,
+}
+
+impl DioPin {
+    /// The AT command that configures the line.
+    pub fn at_command(self) -> [u8; 2] {
+        match self {
+            DioPin::D0 => [b'D', b'0'],
+            DioPin::D1 => [b'D', b'1'],
+            DioPin::D2 => [b'D', b'2'],
+            DioPin::D3 => [b'D', b'3'],
+            DioPin::D4 => [b'D', b'4'],
+            DioPin::D5 => [b'D', b'5'],
+            DioPin::D6 => [b'D', b'6'],
+            DioPin::D7 => [b'D', b'7'],
+            DioPin::D8 => [b'D', b'8'],
+            DioPin::P0 => [b'P', b'0'],
+            DioPin::P1 => [b'P', b'1'],
+            DioPin::P2 => [b'P', b'2'],
+        }
+    }
+}
+
+/// Level driven on an IO line used as a digital output.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PinLevel {
+    Low,
+    High,
+}
+
+/// What an IO line is used for, as set with its `Dn` or `Pn` command. Not every line supports
+/// every function.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PinFunction {
+    Disabled = 0,
+    /// The line's dedicated function, such as CTS, RSSI PWM or the association LED.
+    Peripheral = 1,
+    Adc = 2,
+    DigitalInput = 3,
+    OutputLow = 4,
+    OutputHigh = 5,
+}
+
+impl From<PinLevel> for PinFunction {
+    fn from(level: PinLevel) -> PinFunction {
+        match level {
+            PinLevel::Low => PinFunction::OutputLow,
+            PinLevel::High => PinFunction::OutputHigh,
+        }
+    }
+}
+
+// TODO: maybe make separate public facing enums for send and recieve packets
+#[derive(Debug, PartialEq)]
+pub enum ApiData<'a> {
+    // Send
+    TxRequest64Addr {
+        frame_id: u8,
+        dest_addr: u64,
+        options: TxOptions,
+        data: &'a [u8],
+    },
+    // Send
+    TxRequest16Addr {
+        frame_id: u8,
+        dest_addr: u16,
+        options: TxOptions,
+        data: &'a [u8],
+    },
+    // Send
+    AtCommand {
+        frame_id: u8,
+        at_cmd: [u8; 2],
+        params: &'a [u8],
+    },
+    // Send
+    AtCommandQueueParam {
+        frame_id: u8,
+        at_cmd: [u8; 2],
+        params: &'a [u8],
+    },
+    // Send
+    RemoteAtCommand {
+        frame_id: u8,
+        // TODO: combine the addr into an enum
+        dest_addr_64: u64,
+        dest_addr_16: u16,
+        options: RemoteAtOptions,
+        at_cmd: [u8; 2],
+        params: &'a [u8],
+    },
+    // Receive
+    RxPacket64Addr {
+        source_addr: u64,
+        /// in -dBm
+        rssi: u8,
+        options: RxOptions,
+        data: &'a [u8],
+    },
+    // Receive
+    RxPacket16Addr {
+        source_addr: u16,
+        /// in -dBm
+        rssi: u8,
+        options: RxOptions,
+        data: &'a [u8],
+    },
+    // Receive
+    RxPacketIo64Addr {
+        source_addr: u64,
+        /// in -dBm
+        rssi: u8,
+        options: RxOptions,
+        samples: u8,
+        channel_indicator: ChannelIndicator,
+        digital_samples: Option<u16>,
+        analog_samples: [Option<u16>; 4],
+    },
+    // Receive
+    RxPacketIo16Addr {
+        source_addr: u16,
+        /// in -dBm
+        rssi: u8,
+        options: RxOptions,
+        samples: u8,
+        channel_indicator: ChannelIndicator,
+        digital_samples: Option<u16>,
+        analog_samples: [Option<u16>; 4],
+    },
+    // Receive
+    AtCommandResponse {
+        frame_id: u8,
+        at_cmd: [u8; 2],
+        status: AtCommandStatus,
+        data: &'a [u8],
+    },
+    // Receive
+    TxStatus {
+        frame_id: u8,
+        status: TxStatus,
+    },
+    // Receive
     ModemStatus {
         status: ModemStatus,
     },
@@ -362,6 +1430,12 @@ pub enum ApiData<'a> {
         status: AtCommandStatus,
         data: &'a [u8],
     },
+    // Receive
+    /// A frame of a type this crate does not know, only given by `ApiData::parse_lenient`.
+    Unknown {
+        frame_type: u8,
+        data: &'a [u8],
+    },
 }
 
 impl<'a> ApiData<'a> {
@@ -380,15 +1454,22 @@ impl<'a> ApiData<'a> {
             ApiData::TxStatus { .. } => 0x89,
             ApiData::ModemStatus { .. } => 0x8A,
             ApiData::RemoteAtCommandResponse { .. } => 0x97,
+            ApiData::Unknown { frame_type, .. } => frame_type,
         }
     }
 
-    pub fn parse<'b>(data: &'b [u8]) -> Result<ApiData<'b>, ()> {
+    pub fn parse<'b>(data: &'b [u8]) -> Result<ApiData<'b>, ApiParseError> {
         let len = data.len();
         let mut iter = data.iter();
-        match *iter.next().unwrap() {
-            // TODO: test if you can have en empty payload. Currently assumes no.
-            0x00 if len > 10 => {
+        let frame_type = *iter.next().unwrap();
+        let malformed = ApiParseError::Malformed(frame_type);
+        match frame_type {
+            // The firmware accepts TX requests with no RF data, which are sent as empty packets
+            // (useful for presence signaling), so only the 11 byte header is required.
+            0x00 => {
+                if len <= 10 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let dest_addr = ((*iter.next().unwrap() as u64) << 56)
                     | ((*iter.next().unwrap() as u64) << 48)
@@ -407,8 +1488,11 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            // TODO: test if you can have en empty payload
-            0x01 if len > 4 => {
+            // Header is 5 bytes, the payload may be empty
+            0x01 => {
+                if len <= 4 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let dest_addr =
                     ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16);
@@ -421,7 +1505,10 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            0x08 if len > 3 => {
+            0x08 => {
+                if len <= 3 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let at_cmd = [*iter.next().unwrap(), *iter.next().unwrap()];
 
@@ -431,7 +1518,10 @@ impl<'a> ApiData<'a> {
                     params: iter.as_slice(),
                 })
             }
-            0x09 if len > 3 => {
+            0x09 => {
+                if len <= 3 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let at_cmd = [*iter.next().unwrap(), *iter.next().unwrap()];
 
@@ -441,7 +1531,10 @@ impl<'a> ApiData<'a> {
                     params: iter.as_slice(),
                 })
             }
-            0x17 if len > 13 => {
+            0x17 => {
+                if len <= 14 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let dest_addr_64 = ((*iter.next().unwrap() as u64) << 56)
                     | ((*iter.next().unwrap() as u64) << 48)
@@ -453,17 +1546,22 @@ impl<'a> ApiData<'a> {
                     | (*iter.next().unwrap() as u64);
                 let dest_addr_16 =
                     ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16);
+                let options = RemoteAtOptions::from_bits_truncate(*iter.next().unwrap());
                 let at_cmd = [*iter.next().unwrap(), *iter.next().unwrap()];
 
                 Ok(ApiData::RemoteAtCommand {
                     frame_id,
                     dest_addr_64,
                     dest_addr_16,
+                    options,
                     at_cmd,
                     params: iter.as_slice(),
                 })
             }
-            0x80 if len > 10 => {
+            0x80 => {
+                if len <= 10 {
+                    return Err(malformed);
+                }
                 let source_addr = ((*iter.next().unwrap() as u64) << 56)
                     | ((*iter.next().unwrap() as u64) << 48)
                     | ((*iter.next().unwrap() as u64) << 40)
@@ -482,7 +1580,10 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            0x81 if len > 4 => {
+            0x81 => {
+                if len <= 4 {
+                    return Err(malformed);
+                }
                 let source_addr =
                     ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16);
                 let rssi = *iter.next().unwrap();
@@ -495,7 +1596,10 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            0x82 if len > 13 => {
+            0x82 => {
+                if len <= 13 {
+                    return Err(malformed);
+                }
                 let source_addr = ((*iter.next().unwrap() as u64) << 56)
                     | ((*iter.next().unwrap() as u64) << 48)
                     | ((*iter.next().unwrap() as u64) << 40)
@@ -506,36 +1610,12 @@ impl<'a> ApiData<'a> {
                     | (*iter.next().unwrap() as u64);
                 let rssi = *iter.next().unwrap();
                 let options = RxOptions::from_bits_truncate(*iter.next().unwrap());
-                let samples = *iter.next().unwrap();
-                let channel_indicator = ChannelIndicator::from_bits_truncate(
-                    ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16),
-                );
-                let digital_samples = if channel_indicator.contains_digital() {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog0 = if channel_indicator.contains(ChannelIndicator::A0) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog1 = if channel_indicator.contains(ChannelIndicator::A1) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog2 = if channel_indicator.contains(ChannelIndicator::A2) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog3 = if channel_indicator.contains(ChannelIndicator::A3) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog_samples = [analog0, analog1, analog2, analog3];
+                let IoSample {
+                    samples,
+                    channel_indicator,
+                    digital_samples,
+                    analog_samples,
+                } = IoSample::parse(iter.as_slice()).ok_or(malformed)?;
 
                 Ok(ApiData::RxPacketIo64Addr {
                     source_addr,
@@ -547,41 +1627,20 @@ impl<'a> ApiData<'a> {
                     analog_samples,
                 })
             }
-            0x83 if len > 7 => {
+            0x83 => {
+                if len <= 7 {
+                    return Err(malformed);
+                }
                 let source_addr =
                     ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16);
                 let rssi = *iter.next().unwrap();
                 let options = RxOptions::from_bits_truncate(*iter.next().unwrap());
-                let samples = *iter.next().unwrap();
-                let channel_indicator = ChannelIndicator::from_bits_truncate(
-                    ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16),
-                );
-                let digital_samples = if channel_indicator.contains_digital() {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog0 = if channel_indicator.contains(ChannelIndicator::A0) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog1 = if channel_indicator.contains(ChannelIndicator::A1) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog2 = if channel_indicator.contains(ChannelIndicator::A2) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog3 = if channel_indicator.contains(ChannelIndicator::A3) {
-                    Some(((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16))
-                } else {
-                    None
-                };
-                let analog_samples = [analog0, analog1, analog2, analog3];
+                let IoSample {
+                    samples,
+                    channel_indicator,
+                    digital_samples,
+                    analog_samples,
+                } = IoSample::parse(iter.as_slice()).ok_or(malformed)?;
 
                 Ok(ApiData::RxPacketIo16Addr {
                     source_addr,
@@ -593,16 +1652,14 @@ impl<'a> ApiData<'a> {
                     analog_samples,
                 })
             }
-            0x88 if len > 4 => {
+            0x88 => {
+                if len <= 4 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let at_cmd = [*iter.next().unwrap(), *iter.next().unwrap()];
-                let status = match *iter.next().unwrap() {
-                    0 => AtCommandStatus::Ok,
-                    1 => AtCommandStatus::Error,
-                    2 => AtCommandStatus::InvalidCommand,
-                    3 => AtCommandStatus::InvalidParam,
-                    _ => AtCommandStatus::Unknown,
-                };
+                let status = AtCommandStatus::try_from(*iter.next().unwrap())
+                    .unwrap_or(AtCommandStatus::Unknown);
 
                 Ok(ApiData::AtCommandResponse {
                     frame_id,
@@ -611,18 +1668,28 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            0x89 if len == 3 => {
+            0x89 => {
+                if len != 3 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
-                let status = TxStatus::from(*iter.next().unwrap());
+                let status = TxStatus::try_from(*iter.next().unwrap()).unwrap_or(TxStatus::Unknown);
 
                 Ok(ApiData::TxStatus { frame_id, status })
             }
-            0x8A if len == 2 => {
-                let status = ModemStatus::from(*iter.next().unwrap());
+            0x8A => {
+                if len != 2 {
+                    return Err(malformed);
+                }
+                let status =
+                    ModemStatus::try_from(*iter.next().unwrap()).unwrap_or(ModemStatus::Unknown);
 
                 Ok(ApiData::ModemStatus { status })
             }
-            0x97 if len > 14 => {
+            0x97 => {
+                if len <= 14 {
+                    return Err(malformed);
+                }
                 let frame_id = *iter.next().unwrap();
                 let source_addr_64 = ((*iter.next().unwrap() as u64) << 56)
                     | ((*iter.next().unwrap() as u64) << 48)
@@ -635,14 +1702,8 @@ impl<'a> ApiData<'a> {
                 let source_addr_16 =
                     ((*iter.next().unwrap() as u16) << 8) | (*iter.next().unwrap() as u16);
                 let at_cmd = [*iter.next().unwrap(), *iter.next().unwrap()];
-                let status = match *iter.next().unwrap() {
-                    0 => AtCommandStatus::Ok,
-                    1 => AtCommandStatus::Error,
-                    2 => AtCommandStatus::InvalidCommand,
-                    3 => AtCommandStatus::InvalidParam,
-                    4 => AtCommandStatus::NoResponse,
-                    _ => AtCommandStatus::Unknown,
-                };
+                let status = AtCommandStatus::try_from(*iter.next().unwrap())
+                    .unwrap_or(AtCommandStatus::Unknown);
 
                 Ok(ApiData::RemoteAtCommandResponse {
                     frame_id,
@@ -653,36 +1714,340 @@ impl<'a> ApiData<'a> {
                     data: iter.as_slice(),
                 })
             }
-            _ => Err(()),
+            _ => Err(ApiParseError::UnknownType(frame_type)),
         }
     }
-}
 
-enum TxRequestState {
-    FrameType,
-    FrameId,
-    Addr,
-    Options,
-    Data,
-}
+    /// Like `parse`, but a frame of a type this crate does not know is given as
+    /// `ApiData::Unknown` instead of failing, such as one added by newer firmware. Frames of a
+    /// known type that cannot be parsed still fail.
+    pub fn parse_lenient<'b>(data: &'b [u8]) -> Result<ApiData<'b>, ApiParseError> {
+        match ApiData::parse(data) {
+            Err(ApiParseError::UnknownType(frame_type)) => Ok(ApiData::Unknown {
+                frame_type,
+                data: &data[1..],
+            }),
+            ret => ret,
+        }
+    }
 
-pub struct TxRequestIter<I> {
-    state: TxRequestState,
-    frame_id: u8,
-    addr: u64,
-    addr_shift: u8,
-    options: TxOptions,
-    data: I,
-}
+    /// Decodes the association state from a successful response to an `AI` command.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_association(&self) -> Option<AssociationStatus> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd: [b'A', b'I'],
+                status: AtCommandStatus::Ok,
+                data: &[val],
+                ..
+            } => Some(AssociationStatus::from(val)),
+            _ => None,
+        }
+    }
 
-impl<I> TxRequestIter<I>
-where
-    I: ExactSizeIterator<Item = u8>,
-{
-    pub fn new(frame_id: u8, addr: Addr, options: TxOptions, data: I) -> TxRequestIter<I> {
-        let (addr, addr_shift) = match addr {
-            Addr::Long(addr) => (addr, 56),
+    /// Whether a received packet was broadcast rather than addressed to this node, by address or
+    /// to every PAN.
+    ///
+    /// Returns `false` for frames other than received packets.
+    pub fn is_broadcast(&self) -> bool {
+        match *self {
+            ApiData::RxPacket64Addr { options, .. }
+            | ApiData::RxPacket16Addr { options, .. }
+            | ApiData::RxPacketIo64Addr { options, .. }
+            | ApiData::RxPacketIo16Addr { options, .. } => options.is_broadcast(),
+            _ => false,
+        }
+    }
+
+    /// Decodes the firmware version from a successful response to a `VR` command.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_firmware_version(&self) -> Option<u16> {
+        self.parse_u16_response([b'V', b'R'])
+    }
+
+    /// Decodes the hardware version from a successful response to an `HV` command. The high
+    /// byte identifies the hardware series, see `is_s2c_hardware`.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_hardware_version(&self) -> Option<u16> {
+        self.parse_u16_response([b'H', b'V'])
+    }
+
+    /// Decodes the RSSI of the last packet received from a successful response to a `DB`
+    /// command, as a magnitude in -dBm.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_db(&self) -> Option<u8> {
+        self.parse_u8_response([b'D', b'B'])
+    }
+
+    /// Like `parse_db`, but in dBm.
+    pub fn parse_db_dbm(&self) -> Option<i16> {
+        self.parse_db().map(rssi_to_dbm)
+    }
+
+    /// Converts a successful response to a `%V` command to millivolts, given the ADC reference
+    /// voltage in millivolts.
+    ///
+    /// Returns `None` for any other frame, or if the reading is wider than the ADC's 10 bits.
+    pub fn parse_supply_voltage(&self, vref_mv: u16) -> Option<u16> {
+        supply_voltage_mv(self.parse_u16_response([b'%', b'V'])?, vref_mv)
+    }
+
+    /// Decodes the operating channel from a successful response to a `CH` command.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_channel(&self) -> Option<u8> {
+        self.parse_param().map(|params::Channel(channel)| channel)
+    }
+
+    /// Decodes the PAN ID from a successful response to an `ID` command.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_pan_id(&self) -> Option<u16> {
+        self.parse_param().map(|params::PanId(pan_id)| pan_id)
+    }
+
+    /// Decodes a typed parameter from a successful response to its command, such as
+    /// `params::Channel` from a `CH` response.
+    ///
+    /// Returns `None` for any other frame.
+    pub fn parse_param<P: AtParam>(&self) -> Option<P> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd,
+                status: AtCommandStatus::Ok,
+                data,
+                ..
+            } if at_cmd == P::AT_CMD => P::from_response(data),
+            _ => None,
+        }
+    }
+
+    fn parse_u8_response(&self, at_cmd: [u8; 2]) -> Option<u8> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd: cmd,
+                status: AtCommandStatus::Ok,
+                data,
+                ..
+            } if cmd == at_cmd => AtResponseData(data).as_u8(),
+            _ => None,
+        }
+    }
+
+    fn parse_u16_response(&self, at_cmd: [u8; 2]) -> Option<u16> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd: cmd,
+                status: AtCommandStatus::Ok,
+                data,
+                ..
+            } if cmd == at_cmd => AtResponseData(data).as_u16(),
+            _ => None,
+        }
+    }
+
+    /// Data of a local or remote AT command response, or `None` for any other frame.
+    pub fn at_response_data(&self) -> Option<AtResponseData<'a>> {
+        match *self {
+            ApiData::AtCommandResponse { data, .. }
+            | ApiData::RemoteAtCommandResponse { data, .. } => Some(AtResponseData(data)),
+            _ => None,
+        }
+    }
+
+    /// The node identifier in a successful answer to `NI`, or `None` for any other frame or if
+    /// the identifier is not ASCII.
+    pub fn parse_node_identifier(&self) -> Option<&'a str> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd: [b'N', b'I'],
+                status: AtCommandStatus::Ok,
+                data,
+                ..
+            }
+            | ApiData::RemoteAtCommandResponse {
+                at_cmd: [b'N', b'I'],
+                status: AtCommandStatus::Ok,
+                data,
+                ..
+            } if data.is_ascii() => AtResponseData(data).as_str(),
+            _ => None,
+        }
+    }
+
+    /// Signal strength a received packet arrived with, in dBm, or `None` if this is not a
+    /// received packet.
+    pub fn rssi_dbm(&self) -> Option<i16> {
+        match *self {
+            ApiData::RxPacket64Addr { rssi, .. }
+            | ApiData::RxPacket16Addr { rssi, .. }
+            | ApiData::RxPacketIo64Addr { rssi, .. }
+            | ApiData::RxPacketIo16Addr { rssi, .. } => Some(rssi_to_dbm(rssi)),
+            _ => None,
+        }
+    }
+
+    /// Status of a response to the AT command `at_cmd`, or `None` for any other frame.
+    pub fn at_response_status(&self, at_cmd: [u8; 2]) -> Option<&AtCommandStatus> {
+        match *self {
+            ApiData::AtCommandResponse {
+                at_cmd: cmd,
+                ref status,
+                ..
+            } if cmd == at_cmd => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Packs a frame to send to the radio, the inverse of `parse` for the frames the host sends.
+    ///
+    /// Remote AT commands cannot be packed yet.
+    pub fn pack(self, format: FrameFormat) -> Result<FramePacker<ApiDataIter<'a>>, ApiPackError> {
+        let inner = match self {
+            ApiData::TxRequest64Addr {
+                frame_id,
+                dest_addr,
+                options,
+                data,
+            } => ApiDataIterInner::TxRequest(TxRequestIter::new(
+                frame_id,
+                Addr::Long(dest_addr),
+                options,
+                data.iter().cloned(),
+            )),
+            ApiData::TxRequest16Addr {
+                frame_id,
+                dest_addr,
+                options,
+                data,
+            } => ApiDataIterInner::TxRequest(TxRequestIter::new(
+                frame_id,
+                Addr::Short(dest_addr),
+                options,
+                data.iter().cloned(),
+            )),
+            ApiData::AtCommand {
+                frame_id,
+                at_cmd,
+                params,
+            } => ApiDataIterInner::AtCommand(AtCommandIter::new(
+                frame_id,
+                at_cmd,
+                params.iter().cloned(),
+            )),
+            ApiData::AtCommandQueueParam {
+                frame_id,
+                at_cmd,
+                params,
+            } => ApiDataIterInner::AtCommand(AtCommandIter::queue_param(
+                frame_id,
+                at_cmd,
+                params.iter().cloned(),
+            )),
+            _ => return Err(ApiPackError::NotSendable),
+        };
+
+        FramePacker::new(ApiDataIter { inner }, format.is_escaped(), false)
+    }
+}
+
+enum ApiDataIterInner<'a> {
+    TxRequest(TxRequestIter<Cloned<slice::Iter<'a, u8>>>),
+    AtCommand(AtCommandIter<Cloned<slice::Iter<'a, u8>>>),
+}
+
+/// Data portion of a frame built from an `ApiData` by `ApiData::pack`.
+pub struct ApiDataIter<'a> {
+    inner: ApiDataIterInner<'a>,
+}
+
+impl<'a> Iterator for ApiDataIter<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            ApiDataIterInner::TxRequest(ref mut iter) => iter.next(),
+            ApiDataIterInner::AtCommand(ref mut iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.len();
+        (size, Some(size))
+    }
+}
+
+impl<'a> ExactSizeIterator for ApiDataIter<'a> {
+    fn len(&self) -> usize {
+        match self.inner {
+            ApiDataIterInner::TxRequest(ref iter) => iter.len(),
+            ApiDataIterInner::AtCommand(ref iter) => iter.len(),
+        }
+    }
+}
+
+/// Largest frame data portion that `ApiDataOwned` can hold.
+pub const MAX_FRAME_DATA: usize = 256;
+
+/// Data portion of a received frame copied out of the receive buffer, so that it can be kept
+/// after the buffer is reused.
+#[derive(Clone, Debug)]
+pub struct ApiDataOwned {
+    data: ArrayVec<[u8; MAX_FRAME_DATA]>,
+}
+
+impl ApiDataOwned {
+    /// Copies the data portion of a frame. Returns `None` if it is longer than
+    /// `MAX_FRAME_DATA`.
+    pub fn from_slice(data: &[u8]) -> Option<ApiDataOwned> {
+        if data.len() > MAX_FRAME_DATA {
+            return None;
+        }
+
+        let mut owned = ArrayVec::new();
+        owned.extend(data.iter().cloned());
+        Some(ApiDataOwned { data: owned })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    pub fn parse(&self) -> Result<ApiData<'_>, ApiParseError> {
+        ApiData::parse(self.data.as_slice())
+    }
+}
+
+enum TxRequestState {
+    FrameType,
+    FrameId,
+    Addr,
+    Options,
+    Data,
+}
+
+pub struct TxRequestIter<I> {
+    state: TxRequestState,
+    frame_id: u8,
+    addr: u64,
+    addr_shift: u8,
+    options: TxOptions,
+    data: I,
+}
+
+impl<I> TxRequestIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    pub fn new(frame_id: u8, addr: Addr, options: TxOptions, data: I) -> TxRequestIter<I> {
+        let (addr, addr_shift) = match addr {
+            Addr::Long(addr) => (addr, 56),
             Addr::Short(addr) => (addr as u64, 8),
+            Addr::Broadcast => (BROADCAST_ADDR as u64, 8),
         };
 
         TxRequestIter {
@@ -770,10 +2135,237 @@ where
     }
 }
 
+enum AtCommandState {
+    FrameType,
+    FrameId,
+    AtCmd,
+    Params,
+}
+
+/// Builds the data portion of an AT command (0x08) or AT command queue parameter value (0x09)
+/// frame.
+pub struct AtCommandIter<I> {
+    state: AtCommandState,
+    queue_param: bool,
+    frame_id: u8,
+    at_cmd: [u8; 2],
+    at_cmd_idx: usize,
+    params: I,
+}
+
+impl<I> AtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    /// AT command that is applied immediately.
+    pub fn new(frame_id: u8, at_cmd: [u8; 2], params: I) -> AtCommandIter<I> {
+        AtCommandIter {
+            state: AtCommandState::FrameType,
+            queue_param: false,
+            frame_id,
+            at_cmd,
+            at_cmd_idx: 0,
+            params,
+        }
+    }
+
+    /// AT command whose parameter is queued until changes are applied with `AC` or another
+    /// AT command.
+    pub fn queue_param(frame_id: u8, at_cmd: [u8; 2], params: I) -> AtCommandIter<I> {
+        AtCommandIter {
+            queue_param: true,
+            ..AtCommandIter::new(frame_id, at_cmd, params)
+        }
+    }
+}
+
+impl<I> Iterator for AtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.state {
+            AtCommandState::FrameType => {
+                self.state = AtCommandState::FrameId;
+                if self.queue_param {
+                    Some(0x09) // AtCommandQueueParam
+                } else {
+                    Some(0x08) // AtCommand
+                }
+            }
+            AtCommandState::FrameId => {
+                self.state = AtCommandState::AtCmd;
+                Some(self.frame_id)
+            }
+            AtCommandState::AtCmd => {
+                let val = self.at_cmd[self.at_cmd_idx];
+                self.at_cmd_idx += 1;
+                if self.at_cmd_idx == self.at_cmd.len() {
+                    self.state = AtCommandState::Params;
+                }
+                Some(val)
+            }
+            AtCommandState::Params => self.params.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.len();
+        (size, Some(size))
+    }
+}
+
+impl<I> ExactSizeIterator for AtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    fn len(&self) -> usize {
+        let at_cmd_left = self.at_cmd.len() - self.at_cmd_idx;
+        match self.state {
+            AtCommandState::FrameType => 2 + at_cmd_left + self.params.len(),
+            AtCommandState::FrameId => 1 + at_cmd_left + self.params.len(),
+            AtCommandState::AtCmd => at_cmd_left + self.params.len(),
+            AtCommandState::Params => self.params.len(),
+        }
+    }
+}
+
+/// Bytes of a remote AT command frame before its parameters.
+const REMOTE_AT_HEADER_LEN: usize = 15;
+
+/// Builds the data portion of a remote AT command (0x17) frame.
+pub struct RemoteAtCommandIter<I> {
+    header: [u8; REMOTE_AT_HEADER_LEN],
+    header_idx: usize,
+    params: I,
+}
+
+impl<I> RemoteAtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    pub fn new(
+        frame_id: u8,
+        addr: Addr,
+        options: RemoteAtOptions,
+        at_cmd: [u8; 2],
+        params: I,
+    ) -> RemoteAtCommandIter<I> {
+        // The 16-bit address is 0xFFFE when the 64-bit one is to be used, and the 64-bit one is
+        // all ones when the 16-bit one is
+        let (addr_64, addr_16) = match addr {
+            Addr::Long(addr) => (addr, 0xFFFE),
+            Addr::Short(addr) => (u64::MAX, addr),
+            Addr::Broadcast => (BROADCAST_ADDR as u64, 0xFFFE),
+        };
+
+        let mut header = [0; REMOTE_AT_HEADER_LEN];
+        header[0] = 0x17;
+        header[1] = frame_id;
+        header[2..10].copy_from_slice(&at_param_u64(addr_64));
+        header[10..12].copy_from_slice(&at_param_u16(addr_16));
+        header[12] = options.bits();
+        header[13..].copy_from_slice(&at_cmd);
+
+        RemoteAtCommandIter {
+            header,
+            header_idx: 0,
+            params,
+        }
+    }
+}
+
+impl<I> Iterator for RemoteAtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.header_idx < REMOTE_AT_HEADER_LEN {
+            self.header_idx += 1;
+            Some(self.header[self.header_idx - 1])
+        } else {
+            self.params.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.len();
+        (size, Some(size))
+    }
+}
+
+impl<I> ExactSizeIterator for RemoteAtCommandIter<I>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    fn len(&self) -> usize {
+        REMOTE_AT_HEADER_LEN - self.header_idx + self.params.len()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn remote_at_command_test() {
+        let iter = RemoteAtCommandIter::new(
+            0x01,
+            Addr::Long(0x0013_A200_415D_1DBB),
+            RemoteAtOptions::default(),
+            [b'D', b'0'],
+            [0x05].iter().cloned(),
+        );
+        assert_eq!(iter.len(), 16);
+        let data: ArrayVec<[u8; 16]> = iter.collect();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        assert_eq!(
+            data.as_slice(),
+            &[
+                0x17, 0x01,
+                0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+                0xFF, 0xFE,
+                // Options come between the addresses and the command
+                0x02,
+                b'D', b'0', 0x05,
+            ]
+        );
+        assert_eq!(
+            ApiData::parse(&data).unwrap(),
+            ApiData::RemoteAtCommand {
+                frame_id: 0x01,
+                dest_addr_64: 0x0013_A200_415D_1DBB,
+                dest_addr_16: 0xFFFE,
+                options: RemoteAtOptions::APPLY_CHANGES,
+                at_cmd: [b'D', b'0'],
+                params: &[0x05],
+            }
+        );
+
+        let data: ArrayVec<[u8; 16]> = RemoteAtCommandIter::new(
+            0x02,
+            Addr::Short(0x1234),
+            RemoteAtOptions::DISABLE_ACK,
+            [b'C', b'H'],
+            [].iter().cloned(),
+        )
+        .collect();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        assert_eq!(
+            &data[2..],
+            &[
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x12, 0x34,
+                0x01,
+                b'C', b'H',
+            ]
+        );
+    }
+
     #[test]
     fn at_command_nh_parse_test() {
         let unpacked_data = [0x08, 0x52, 0x4E, 0x48];
@@ -802,6 +2394,51 @@ mod test {
         assert_eq!(parsed_data, test_data);
     }
 
+    #[test]
+    fn tx_request_64_empty_parse_test() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let unpacked_data = [
+            0x00,
+            0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x00,
+        ];
+        let parsed_data = ApiData::parse(&unpacked_data[..]).unwrap();
+
+        let test_data = ApiData::TxRequest64Addr {
+            frame_id: 0x01,
+            dest_addr: 0x0013_A200_415D_1DBB,
+            options: TxOptions::empty(),
+            data: &[],
+        };
+
+        assert_eq!(parsed_data, test_data);
+    }
+
+    #[test]
+    fn tx_request_16_empty_parse_test() {
+        let unpacked_data = [0x01, 0x01, 0x12, 0x34, 0x01];
+        let parsed_data = ApiData::parse(&unpacked_data[..]).unwrap();
+
+        let test_data = ApiData::TxRequest16Addr {
+            frame_id: 0x01,
+            dest_addr: 0x1234,
+            options: TxOptions::DISABLE_ACK,
+            data: &[],
+        };
+
+        assert_eq!(parsed_data, test_data);
+    }
+
+    #[test]
+    fn tx_request_truncated_parse_test() {
+        let unpacked_data = [0x01, 0x01, 0x12, 0x34];
+        assert_eq!(
+            ApiData::parse(&unpacked_data[..]),
+            Err(ApiParseError::Malformed(0x01))
+        );
+    }
+
     #[test]
     fn at_commmand_response_bd_parse_test() {
         let unpacked_data = [0x88, 0x01, 0x42, 0x44, 0x00];
@@ -843,22 +2480,587 @@ mod test {
     }
 
     #[test]
-    fn unpack_frame_test() {
-        let frame = [
-            0x7E, 0x00, 0x0A, 0x01, 0x01, 0x50, 0x01, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0xB8,
-            // extra data
-            0x7E, 0x01, 0x02,
-        ];
-        let (unpacked_data, rem) = unpack_frame(&frame[..], false, false).unwrap();
+    fn api_data_owned_test() {
+        let unpacked_data = [0x89, 0x01, 0x00];
+        let owned = ApiDataOwned::from_slice(&unpacked_data).unwrap();
+
+        assert_eq!(owned.as_slice(), &unpacked_data[..]);
         assert_eq!(
-            unpacked_data,
-            &[0x01, 0x01, 0x50, 0x01, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F]
+            owned.parse().unwrap(),
+            ApiData::TxStatus {
+                frame_id: 0x01,
+                status: TxStatus::Standard,
+            }
         );
-        assert_eq!(rem, &[0x7E, 0x01, 0x02]);
+        assert!(ApiDataOwned::from_slice(&[0; MAX_FRAME_DATA + 1]).is_none());
+
+        let owned = ApiDataOwned::from_slice(&[0x8B, 0x01]).unwrap();
+        assert_eq!(owned.parse(), Err(ApiParseError::UnknownType(0x8B)));
     }
 
     #[test]
-    fn create_tx_request_test() {
+    fn pack_round_trip_test() {
+        let at_command = ApiData::AtCommand {
+            frame_id: 0x52,
+            at_cmd: [b'D', b'L'],
+            params: &[0x00, 0x00, 0x0F, 0xFF],
+        };
+        let mut buf: ArrayVec<[u8; 32]> = ArrayVec::new();
+        buf.extend(at_command.pack(FrameFormat::Unescaped).unwrap());
+
+        let (data, rem) = unpack_frame(&buf, false, false).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(
+            ApiData::parse(data),
+            Ok(ApiData::AtCommand {
+                frame_id: 0x52,
+                at_cmd: [b'D', b'L'],
+                params: &[0x00, 0x00, 0x0F, 0xFF],
+            })
+        );
+
+        let tx_request = ApiData::TxRequest16Addr {
+            frame_id: 0x01,
+            dest_addr: 0x1234,
+            options: TxOptions::DISABLE_ACK,
+            data: b"hi",
+        };
+        buf.clear();
+        buf.extend(tx_request.pack(FrameFormat::Unescaped).unwrap());
+
+        let (data, _) = unpack_frame(&buf, false, false).unwrap();
+        assert_eq!(
+            ApiData::parse(data),
+            Ok(ApiData::TxRequest16Addr {
+                frame_id: 0x01,
+                dest_addr: 0x1234,
+                options: TxOptions::DISABLE_ACK,
+                data: b"hi",
+            })
+        );
+
+        let modem_status = ApiData::ModemStatus {
+            status: ModemStatus::HardwareReset,
+        };
+        match modem_status.pack(FrameFormat::Unescaped) {
+            Err(ApiPackError::NotSendable) => {}
+            _ => panic!("expected a receive-only frame to be rejected"),
+        }
+    }
+
+    #[test]
+    fn write_response_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'W', b'R', 0x00]).unwrap();
+        assert_eq!(
+            data,
+            ApiData::AtCommandResponse {
+                frame_id: 0x01,
+                at_cmd: [b'W', b'R'],
+                status: AtCommandStatus::Ok,
+                data: &[],
+            }
+        );
+        assert_eq!(
+            data.at_response_status([b'W', b'R']),
+            Some(&AtCommandStatus::Ok)
+        );
+        assert_eq!(data.at_response_status([b'A', b'C']), None);
+
+        let data = ApiData::parse(&[0x88, 0x01, b'W', b'R', 0x01]).unwrap();
+        assert_eq!(
+            data.at_response_status([b'W', b'R']),
+            Some(&AtCommandStatus::Error)
+        );
+    }
+
+    #[test]
+    fn version_parse_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'V', b'R', 0x00, 0x20, 0x03]).unwrap();
+        assert_eq!(data.parse_firmware_version(), Some(0x2003));
+        assert_eq!(data.parse_hardware_version(), None);
+
+        let data = ApiData::parse(&[0x88, 0x01, b'H', b'V', 0x00, 0x22, 0x41]).unwrap();
+        assert_eq!(data.parse_hardware_version(), Some(0x2241));
+        assert!(is_s2c_hardware(0x2241));
+        // XBee Series 1
+        assert!(!is_s2c_hardware(0x1744));
+
+        // Failed command
+        let data = ApiData::parse(&[0x88, 0x01, b'H', b'V', 0x01]).unwrap();
+        assert_eq!(data.parse_hardware_version(), None);
+    }
+
+    #[test]
+    fn db_parse_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'D', b'B', 0x00, 0x35]).unwrap();
+        assert_eq!(data.parse_db(), Some(0x35));
+        assert_eq!(data.parse_db_dbm(), Some(-53));
+
+        // Failed command
+        let data = ApiData::parse(&[0x88, 0x01, b'D', b'B', 0x01]).unwrap();
+        assert_eq!(data.parse_db(), None);
+        let data = ApiData::parse(&[0x88, 0x01, b'C', b'H', 0x00, 0x35]).unwrap();
+        assert_eq!(data.parse_db_dbm(), None);
+    }
+
+    #[test]
+    fn channel_pan_id_parse_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'C', b'H', 0x00, 0x0C]).unwrap();
+        assert_eq!(data.parse_channel(), Some(0x0C));
+        assert_eq!(data.parse_pan_id(), None);
+        let data = ApiData::parse(&[0x88, 0x02, b'I', b'D', 0x00, 0x33, 0x32]).unwrap();
+        assert_eq!(data.parse_pan_id(), Some(0x3332));
+        assert_eq!(data.parse_channel(), None);
+
+        // Failed command and wrong length
+        let data = ApiData::parse(&[0x88, 0x01, b'C', b'H', 0x03]).unwrap();
+        assert_eq!(data.parse_channel(), None);
+        let data = ApiData::parse(&[0x88, 0x02, b'I', b'D', 0x00, 0x33]).unwrap();
+        assert_eq!(data.parse_pan_id(), None);
+    }
+
+    #[test]
+    fn supply_voltage_parse_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'%', b'V', 0x00, 0x03, 0x00]).unwrap();
+        assert_eq!(data.parse_supply_voltage(1200), Some(900));
+        let data = ApiData::parse(&[0x88, 0x01, b'%', b'V', 0x00, 0x03, 0xFF]).unwrap();
+        assert_eq!(data.parse_supply_voltage(3300), Some(3296));
+
+        // Too wide for the ADC
+        let data = ApiData::parse(&[0x88, 0x01, b'%', b'V', 0x00, 0x04, 0x00]).unwrap();
+        assert_eq!(data.parse_supply_voltage(1200), None);
+        let data = ApiData::parse(&[0x88, 0x01, b'V', b'R', 0x00, 0x03, 0x00]).unwrap();
+        assert_eq!(data.parse_supply_voltage(1200), None);
+    }
+
+    #[test]
+    fn broadcast_options_test() {
+        let data = ApiData::parse(&[0x81, 0x12, 0x34, 0x28, 0x00, b'h', b'i']).unwrap();
+        assert!(!data.is_broadcast());
+        let data = ApiData::parse(&[0x81, 0x12, 0x34, 0x28, 0x02, b'h', b'i']).unwrap();
+        assert!(data.is_broadcast());
+        let data = ApiData::parse(&[0x81, 0x12, 0x34, 0x28, 0x04, b'h', b'i']).unwrap();
+        assert!(data.is_broadcast());
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = ApiData::parse(&[
+            0x80,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x28,
+            0x02,
+            b'h', b'i',
+        ])
+        .unwrap();
+        assert!(data.is_broadcast());
+
+        let data = ApiData::parse(&[0x8A, 0x00]).unwrap();
+        assert!(!data.is_broadcast());
+    }
+
+    #[test]
+    fn parse_node_identifier_test() {
+        let response = [
+            0x88, 0x01, b'N', b'I', 0x00, b'N', b'o', b'd', b'e', b' ', b'1',
+        ];
+        let data = ApiData::parse(&response).unwrap();
+        assert_eq!(data.parse_node_identifier(), Some("Node 1"));
+
+        let not_ascii = [0x88, 0x01, b'N', b'I', 0x00, b'N', 0xC3, 0xA9];
+        let data = ApiData::parse(&not_ascii).unwrap();
+        assert_eq!(data.parse_node_identifier(), None);
+
+        let other_command = [0x88, 0x01, b'N', b'D', 0x00, b'N'];
+        let data = ApiData::parse(&other_command).unwrap();
+        assert_eq!(data.parse_node_identifier(), None);
+    }
+
+    #[test]
+    fn node_discovery_result_test() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = [
+            0x12, 0x34,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x28,
+            b'N', b'o', b'd', b'e', b' ', b'1', 0x00,
+        ];
+        let expected = NodeDiscoveryResult {
+            my: 0x1234,
+            serial: 0x0013_A200_415D_1DBB,
+            rssi: 0x28,
+            identifier: "Node 1",
+        };
+        assert_eq!(NodeDiscoveryResult::parse(&data), Some(expected));
+        // An identifier missing its NUL
+        assert_eq!(
+            NodeDiscoveryResult::parse(&data[..13]).map(|node| node.identifier),
+            Some("No")
+        );
+        assert_eq!(
+            NodeDiscoveryResult::parse(&data[..11]).map(|node| node.identifier),
+            Some("")
+        );
+        assert_eq!(NodeDiscoveryResult::parse(&data[..10]), None);
+    }
+
+    #[test]
+    fn energy_scan_test() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = [
+            0x4A, 0x50, 0x3C, 0x58, 0x55, 0x46, 0x5C, 0x4E,
+            0x5C, 0x52, 0x41, 0x48, 0x57, 0x53, 0x4F, 0x51,
+        ];
+        let scan = EnergyScan::parse(0xFFFF, &data).unwrap();
+        assert_eq!(scan.iter().count(), 16);
+        assert_eq!(scan.iter().next(), Some((0x0B, 0x4A)));
+        assert_eq!(scan.reading(0x0D), Some(0x3C));
+        assert_eq!(scan.reading(0x1A), Some(0x51));
+        assert_eq!(scan.reading(0x0A), None);
+        assert_eq!(scan.reading(0x1B), None);
+        // 0x11 and 0x13 tie at -92 dBm
+        assert_eq!(scan.quietest_channel(), Some(0x11));
+
+        // Channels 0x0B, 0x10 and 0x15 only
+        let scan = EnergyScan::parse(0x0421, &data[..3]).unwrap();
+        let mut channels = scan.iter();
+        assert_eq!(channels.next(), Some((0x0B, 0x4A)));
+        assert_eq!(channels.next(), Some((0x10, 0x50)));
+        assert_eq!(channels.next(), Some((0x15, 0x3C)));
+        assert_eq!(channels.next(), None);
+        assert_eq!(scan.reading(0x0C), None);
+        assert_eq!(scan.quietest_channel(), Some(0x10));
+
+        // One reading per scanned channel
+        assert_eq!(EnergyScan::parse(0x0421, &data), None);
+        assert_eq!(
+            EnergyScan::parse(0x0000, &[]).unwrap().quietest_channel(),
+            None
+        );
+    }
+
+    #[test]
+    fn io_sample_length_test() {
+        // One set with D0 and A0 enabled
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let packet = [
+            0x83, 0x12, 0x34, 0x28, 0x00,
+            0x01, 0x02, 0x01,
+            0x00, 0x01, 0x01, 0x23,
+        ];
+        match ApiData::parse(&packet) {
+            Ok(ApiData::RxPacketIo16Addr {
+                samples: 1,
+                digital_samples: Some(0x0001),
+                analog_samples: [Some(0x0123), None, None, None],
+                ..
+            }) => {}
+            _ => panic!("expected an IO sample"),
+        }
+        // The analog sample cut short
+        assert!(ApiData::parse(&packet[..11]).is_err());
+        assert!(ApiData::parse(&packet[..10]).is_err());
+        // More bytes than one set
+        let mut two_sets = [0; 16];
+        two_sets[..12].copy_from_slice(&packet);
+        two_sets[12..].copy_from_slice(&[0x00, 0x00, 0x01, 0x24]);
+        assert!(ApiData::parse(&two_sets).is_err());
+        // Unless it says there are two
+        two_sets[5] = 0x02;
+        match ApiData::parse(&two_sets) {
+            Ok(ApiData::RxPacketIo16Addr {
+                samples: 2,
+                analog_samples: [Some(0x0123), None, None, None],
+                ..
+            }) => {}
+            _ => panic!("expected an IO sample"),
+        }
+    }
+
+    #[test]
+    fn at_cmd_test() {
+        let table = [
+            (AtCmd::Dh, *b"DH"),
+            (AtCmd::Dl, *b"DL"),
+            (AtCmd::My, *b"MY"),
+            (AtCmd::Sh, *b"SH"),
+            (AtCmd::Sl, *b"SL"),
+            (AtCmd::Ni, *b"NI"),
+            (AtCmd::Ch, *b"CH"),
+            (AtCmd::Id, *b"ID"),
+            (AtCmd::Ce, *b"CE"),
+            (AtCmd::A1, *b"A1"),
+            (AtCmd::A2, *b"A2"),
+            (AtCmd::Ai, *b"AI"),
+            (AtCmd::Sc, *b"SC"),
+            (AtCmd::Sd, *b"SD"),
+            (AtCmd::Nt, *b"NT"),
+            (AtCmd::Ee, *b"EE"),
+            (AtCmd::Ky, *b"KY"),
+            (AtCmd::Pl, *b"PL"),
+            (AtCmd::Ca, *b"CA"),
+            (AtCmd::Rr, *b"RR"),
+            (AtCmd::Rn, *b"RN"),
+            (AtCmd::Mm, *b"MM"),
+            (AtCmd::Bd, *b"BD"),
+            (AtCmd::Ap, *b"AP"),
+            (AtCmd::Ro, *b"RO"),
+            (AtCmd::D0, *b"D0"),
+            (AtCmd::D1, *b"D1"),
+            (AtCmd::D2, *b"D2"),
+            (AtCmd::D3, *b"D3"),
+            (AtCmd::D4, *b"D4"),
+            (AtCmd::D5, *b"D5"),
+            (AtCmd::D6, *b"D6"),
+            (AtCmd::D7, *b"D7"),
+            (AtCmd::D8, *b"D8"),
+            (AtCmd::P0, *b"P0"),
+            (AtCmd::P1, *b"P1"),
+            (AtCmd::P2, *b"P2"),
+            (AtCmd::Ir, *b"IR"),
+            (AtCmd::Ic, *b"IC"),
+            (AtCmd::Is, *b"IS"),
+            (AtCmd::Sm, *b"SM"),
+            (AtCmd::Sp, *b"SP"),
+            (AtCmd::St, *b"ST"),
+            (AtCmd::Db, *b"DB"),
+            (AtCmd::Vr, *b"VR"),
+            (AtCmd::Hv, *b"HV"),
+            (AtCmd::PercentV, *b"%V"),
+            (AtCmd::Ac, *b"AC"),
+            (AtCmd::Wr, *b"WR"),
+            (AtCmd::Fr, *b"FR"),
+            (AtCmd::Nr, *b"NR"),
+            (AtCmd::Nd, *b"ND"),
+            (AtCmd::Ed, *b"ED"),
+        ];
+        assert_eq!(table.len(), AtCmd::ALL.len());
+        for (&(cmd, bytes), &listed) in table.iter().zip(AtCmd::ALL.iter()) {
+            assert_eq!(cmd, listed);
+            assert_eq!(cmd.as_bytes(), bytes);
+            assert_eq!(AtCmd::from_bytes(bytes), Some(cmd));
+            assert_eq!(<[u8; 2]>::from(cmd), bytes);
+        }
+        assert_eq!(AtCmd::from_bytes(*b"LD"), None);
+    }
+
+    #[test]
+    fn parse_lenient_test() {
+        // 0x8B is the TX status of the Zigbee firmware
+        let frame = [0x8B, 0x01, 0xFF, 0xFE, 0x00, 0x00, 0x00];
+        assert!(ApiData::parse(&frame).is_err());
+        assert_eq!(
+            ApiData::parse_lenient(&frame),
+            Ok(ApiData::Unknown {
+                frame_type: 0x8B,
+                data: &frame[1..],
+            })
+        );
+
+        // Known frame types are parsed as usual, and still fail when malformed
+        let modem_status = [0x8A, 0x00];
+        assert_eq!(
+            ApiData::parse_lenient(&modem_status),
+            ApiData::parse(&modem_status)
+        );
+        assert_eq!(
+            ApiData::parse_lenient(&[0x8A]),
+            Err(ApiParseError::Malformed(0x8A))
+        );
+    }
+
+    #[test]
+    fn rssi_dbm_test() {
+        let packet = [0x81, 0x12, 0x34, 0x28, 0x00, b'h', b'i'];
+        assert_eq!(ApiData::parse(&packet).unwrap().rssi_dbm(), Some(-40));
+        let modem_status = [0x8A, 0x00];
+        assert_eq!(ApiData::parse(&modem_status).unwrap().rssi_dbm(), None);
+        assert_eq!(rssi_to_dbm(0x00), 0);
+        assert_eq!(rssi_to_dbm(0xFF), -255);
+    }
+
+    #[test]
+    fn at_response_data_test() {
+        assert_eq!(AtResponseData(&at_param_u8(0x0C)).as_u8(), Some(0x0C));
+        assert_eq!(AtResponseData(&at_param_u16(0x3332)).as_u16(), Some(0x3332));
+        assert_eq!(
+            AtResponseData(&at_param_u32(0x0013_A200)).as_u32(),
+            Some(0x0013_A200)
+        );
+        assert_eq!(
+            AtResponseData(&at_param_u64(0x0013_A200_415D_1DBB)).as_u64(),
+            Some(0x0013_A200_415D_1DBB)
+        );
+
+        // SH then SL make up the serial number
+        let sh = at_param_u32(0x0013_A200);
+        let sl = at_param_u32(0x415D_1DBB);
+        let mut serial = [0; 8];
+        serial[..4].copy_from_slice(&sh);
+        serial[4..].copy_from_slice(&sl);
+        assert_eq!(
+            AtResponseData(&serial).as_u64(),
+            Some(0x0013_A200_415D_1DBB)
+        );
+
+        // Wrong length
+        assert_eq!(AtResponseData(&[0x01, 0x02]).as_u8(), None);
+        assert_eq!(AtResponseData(&[0x01]).as_u16(), None);
+        assert_eq!(AtResponseData(&[]).as_u32(), None);
+
+        let data = ApiData::parse(&[0x88, 0x01, b'N', b'I', 0x00, b'n', b'o', b'd', b'e']).unwrap();
+        assert_eq!(data.at_response_data().unwrap().as_str(), Some("node"));
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = ApiData::parse(&[
+            0x97, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0xFF, 0xFE,
+            b'C', b'H', 0x00, 0x0C,
+        ])
+        .unwrap();
+        assert_eq!(data.at_response_data().unwrap().as_u8(), Some(0x0C));
+        let data = ApiData::parse(&[0x8A, 0x00]).unwrap();
+        assert_eq!(data.at_response_data(), None);
+    }
+
+    #[test]
+    fn association_parse_test() {
+        let data = ApiData::parse(&[0x88, 0x01, b'A', b'I', 0x00, 0x00]).unwrap();
+        assert_eq!(data.parse_association(), Some(AssociationStatus::Success));
+
+        let data = ApiData::parse(&[0x88, 0x01, b'A', b'I', 0x00, 0x21]).unwrap();
+        assert_eq!(
+            data.parse_association(),
+            Some(AssociationStatus::NoPanFound)
+        );
+
+        // Not an AI response
+        let data = ApiData::parse(&[0x88, 0x01, b'N', b'H', 0x00, 0x00]).unwrap();
+        assert_eq!(data.parse_association(), None);
+    }
+
+    #[test]
+    fn unpack_frame_test() {
+        let frame = [
+            0x7E, 0x00, 0x0A, 0x01, 0x01, 0x50, 0x01, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0xB8,
+            // extra data
+            0x7E, 0x01, 0x02,
+        ];
+        let (unpacked_data, rem) = unpack_frame(&frame[..], false, false).unwrap();
+        assert_eq!(
+            unpacked_data,
+            &[0x01, 0x01, 0x50, 0x01, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F]
+        );
+        assert_eq!(rem, &[0x7E, 0x01, 0x02]);
+
+        assert!(matches!(
+            unpack_frame(&frame[..], true, false),
+            Err(ApiUnpackError::Escaped)
+        ));
+    }
+
+    #[test]
+    fn status_try_from_test() {
+        assert_eq!(AtCommandStatus::try_from(0x00), Ok(AtCommandStatus::Ok));
+        assert_eq!(AtCommandStatus::try_from(0x01), Ok(AtCommandStatus::Error));
+        assert_eq!(
+            AtCommandStatus::try_from(0x02),
+            Ok(AtCommandStatus::InvalidCommand)
+        );
+        assert_eq!(
+            AtCommandStatus::try_from(0x03),
+            Ok(AtCommandStatus::InvalidParam)
+        );
+        assert_eq!(
+            AtCommandStatus::try_from(0x04),
+            Ok(AtCommandStatus::NoResponse)
+        );
+        assert_eq!(AtCommandStatus::try_from(0x05), Err(0x05));
+
+        let tx_statuses = [
+            (0x00, TxStatus::Standard),
+            (0x01, TxStatus::NoAck),
+            (0x02, TxStatus::CcaFailure),
+            (0x03, TxStatus::TxPurged),
+            (0x21, TxStatus::NetworkAckFailure),
+            (0x22, TxStatus::NotConnected),
+            (0x31, TxStatus::InternalError),
+            (0x32, TxStatus::ResourceDepletion),
+            (0x74, TxStatus::PayloadTooLarge),
+        ];
+        for &(code, status) in tx_statuses.iter() {
+            assert_eq!(TxStatus::try_from(code), Ok(status));
+        }
+        assert_eq!(TxStatus::try_from(0x04), Err(0x04));
+        assert_eq!(TxStatus::try_from(0xFF), Err(0xFF));
+
+        let modem_statuses = [
+            (0x00, ModemStatus::HardwareReset),
+            (0x01, ModemStatus::WatchdogReset),
+            (0x02, ModemStatus::AssociatedCoordinator),
+            (0x03, ModemStatus::DissociatedCoordinator),
+            (0x06, ModemStatus::CoordinatorNewNetwork),
+            (0x0D, ModemStatus::InputVoltageTooHigh),
+        ];
+        for &(code, status) in modem_statuses.iter() {
+            assert_eq!(ModemStatus::try_from(code), Ok(status));
+        }
+        assert_eq!(ModemStatus::try_from(0x04), Err(0x04));
+        assert_eq!(ModemStatus::try_from(0x80), Err(0x80));
+    }
+
+    #[test]
+    fn frame_len_from_header_test() {
+        assert_eq!(frame_len_from_header(&[0x7E, 0x00, 0x0A]), Some(14));
+        assert_eq!(frame_len_from_header(&[0x7E, 0x01, 0x02, 0x88]), Some(262));
+        assert_eq!(frame_len_from_header(&[0x7E, 0x00]), None);
+        assert_eq!(frame_len_from_header(&[0x00, 0x00, 0x0A]), None);
+    }
+
+    #[test]
+    fn escape_round_trip_test() {
+        let data = [0x01, START, 0x02, ESCAPE, XON, 0x03, XOFF, 0x20];
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let escaped = [
+            0x01,
+            0x7D, 0x5E,
+            0x02,
+            0x7D, 0x5D,
+            0x7D, 0x31,
+            0x03,
+            0x7D, 0x33,
+            0x20,
+        ];
+
+        let mut buf = [0; 16];
+        let len = escape_into(&data, &mut buf).unwrap();
+        assert_eq!(&buf[..len], &escaped[..]);
+
+        let mut out = [0; 16];
+        let len = unescape_into(&buf[..len], &mut out).unwrap();
+        assert_eq!(&out[..len], &data[..]);
+    }
+
+    #[test]
+    fn escape_errors_test() {
+        let mut buf = [0; 3];
+        assert!(escape_into(&[0x01, START], &mut buf).is_ok());
+        assert!(escape_into(&[0x01, 0x02, START], &mut buf).is_err());
+        assert!(unescape_into(&[0x01, ESCAPE], &mut buf).is_err());
+        assert!(unescape_into(&[0x01, 0x02, 0x03, 0x04], &mut buf).is_err());
+    }
+
+    #[test]
+    fn at_param_test() {
+        assert_eq!(at_param_u8(0x12), [0x12]);
+        assert_eq!(at_param_u16(0x1234), [0x12, 0x34]);
+        assert_eq!(at_param_u32(0x1234_5678), [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(
+            at_param_u64(0x0013_A200_415D_1DBB),
+            [0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB]
+        );
+    }
+
+    #[test]
+    fn create_tx_request_test() {
         use arrayvec::ArrayVec;
 
         #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -880,6 +3082,100 @@ mod test {
         assert_eq!(vec.as_slice(), &frame[..]);
     }
 
+    #[test]
+    fn create_empty_tx_request_test() {
+        use arrayvec::ArrayVec;
+
+        let frame = [0x01, 0x01, 0x12, 0x34, 0x00];
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        let tx_request = TxRequestIter::new(
+            1,
+            Addr::Short(0x1234),
+            TxOptions::empty(),
+            [].iter().cloned(),
+        );
+        vec.extend(tx_request);
+        assert_eq!(vec.as_slice(), &frame[..]);
+    }
+
+    #[test]
+    fn create_at_command_test() {
+        use arrayvec::ArrayVec;
+
+        let frame = [0x08, 0x4D, 0x44, 0x4C, 0x00, 0x00, 0x0F, 0xFF];
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        let at_command =
+            AtCommandIter::new(0x4D, [b'D', b'L'], [0x00, 0x00, 0x0F, 0xFF].iter().cloned());
+        assert_eq!(at_command.len(), frame.len());
+        vec.extend(at_command);
+        assert_eq!(vec.as_slice(), &frame[..]);
+
+        let frame = [0x09, 0x52, 0x4E, 0x48];
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        vec.extend(AtCommandIter::queue_param(
+            0x52,
+            [b'N', b'H'],
+            [].iter().cloned(),
+        ));
+        assert_eq!(vec.as_slice(), &frame[..]);
+    }
+
+    #[test]
+    fn at_command_frame_packing_test() {
+        use arrayvec::ArrayVec;
+
+        let mut iter = AtCommandIter::new(0x52, [b'N', b'H'], [].iter().cloned());
+        assert_eq!(iter.next(), Some(0x08));
+        assert_eq!(iter.len(), 3);
+        let packer = FramePacker::new(
+            AtCommandIter::new(0x52, [b'N', b'H'], [].iter().cloned()),
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(packer.exact_len(), Some(8));
+        let frame: ArrayVec<[u8; 32]> = packer.collect();
+        assert_eq!(
+            frame.as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x52, b'N', b'H', 0x0F]
+        );
+
+        let frame: ArrayVec<[u8; 32]> = FramePacker::new(
+            AtCommandIter::queue_param(0x01, [b'C', b'H'], [0x0C].iter().cloned()),
+            false,
+            false,
+        )
+        .unwrap()
+        .collect();
+        assert_eq!(
+            frame.as_slice(),
+            &[0x7E, 0x00, 0x05, 0x09, 0x01, b'C', b'H', 0x0C, 0x5E]
+        );
+
+        let remote = RemoteAtCommandIter::new(
+            0x01,
+            Addr::Long(0x0013_A200_415D_1DBB),
+            RemoteAtOptions::default(),
+            [b'D', b'0'],
+            [0x05].iter().cloned(),
+        );
+        let frame: ArrayVec<[u8; 32]> = FramePacker::new(remote, false, false).unwrap().collect();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        assert_eq!(
+            frame.as_slice(),
+            &[
+                0x7E,
+                0x00, 0x10,
+                0x17, 0x01,
+                0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+                0xFF, 0xFE,
+                0x02,
+                b'D', b'0', 0x05,
+                0x44,
+            ]
+        );
+    }
+
     #[test]
     fn packing_test() {
         use arrayvec::ArrayVec;
@@ -906,7 +3202,183 @@ mod test {
         let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
         let packed_frame =
             FramePacker::new(data.iter().map(|v| *v), false, false).expect("packing error");
+        assert_eq!(packed_frame.exact_len(), Some(test_frame.len()));
+        assert_eq!(
+            packed_frame.size_hint(),
+            (test_frame.len(), Some(test_frame.len()))
+        );
         vec.extend(packed_frame);
         assert_eq!(vec.as_slice(), &test_frame[..]);
     }
+
+    #[test]
+    fn baud_rate_test() {
+        assert_eq!(BaudRate::Baud9600.bps(), 9600);
+        assert_eq!(BaudRate::Baud115200.bps(), 115_200);
+        for val in 0..8 {
+            assert_eq!(BaudRate::try_from(val).map(|baud| baud as u8), Ok(val));
+        }
+        assert_eq!(BaudRate::try_from(8), Err(8));
+    }
+
+    #[test]
+    fn expected_response_type_test() {
+        assert_eq!(expected_response_type(0x00), Some(0x89));
+        assert_eq!(expected_response_type(0x01), Some(0x89));
+        assert_eq!(expected_response_type(0x08), Some(0x88));
+        assert_eq!(expected_response_type(0x09), Some(0x88));
+        assert_eq!(expected_response_type(0x17), Some(0x97));
+        // Responses and unsolicited frames are not answered
+        assert_eq!(expected_response_type(0x88), None);
+        assert_eq!(expected_response_type(0x8A), None);
+        assert_eq!(expected_response_type(0x10), None);
+
+        let request = ApiData::AtCommand {
+            frame_id: 0x01,
+            at_cmd: [b'W', b'R'],
+            params: &[],
+        };
+        let response = ApiData::parse(&[0x88, 0x01, b'W', b'R', 0x00]).unwrap();
+        assert_eq!(
+            expected_response_type(request.frame_type()),
+            Some(response.frame_type())
+        );
+    }
+
+    #[test]
+    fn drifting_len_packing_test() {
+        use arrayvec::ArrayVec;
+        use core::cell::Cell;
+
+        /// Gives a different `len` after the first time it is asked.
+        struct Drifting<'a> {
+            data: core::slice::Iter<'a, u8>,
+            asked: Cell<bool>,
+        }
+
+        impl Iterator for Drifting<'_> {
+            type Item = u8;
+
+            fn next(&mut self) -> Option<u8> {
+                self.data.next().cloned()
+            }
+        }
+
+        impl ExactSizeIterator for Drifting<'_> {
+            fn len(&self) -> usize {
+                if self.asked.replace(true) {
+                    0x1234
+                } else {
+                    self.data.len()
+                }
+            }
+        }
+
+        let data = Drifting {
+            data: [0x01, 0x02, 0x03].iter(),
+            asked: Cell::new(false),
+        };
+        let packer = FramePacker::new(data, false, false).unwrap();
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        vec.extend(packer);
+        assert_eq!(vec.as_slice(), &[0x7E, 0x00, 0x03, 0x01, 0x02, 0x03, 0xF9]);
+    }
+
+    #[test]
+    fn escaped_size_hint_test() {
+        use arrayvec::ArrayVec;
+
+        let data = [0x08, 0x01, 0x7E, 0x11];
+        let mut packer = FramePacker::new(data.iter().cloned(), true, false).unwrap();
+        assert_eq!(packer.exact_len(), None);
+        assert_eq!(packer.size_hint(), (8, Some(15)));
+
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        vec.extend(packer.by_ref().take(6));
+        // Part way through an escaped byte
+        assert_eq!(vec.last(), Some(&ESCAPE));
+        assert_eq!(packer.size_hint(), (3, Some(5)));
+        vec.extend(packer.by_ref());
+        assert_eq!(vec.len(), 10);
+        assert_eq!(packer.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn pack_into_test() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let test_frame = [
+            0x7E,
+            0x00, 0x12,
+            0x00,
+            0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x00,
+            0x54, 0x65, 0x73, 0x74, 0x69, 0x6E, 0x67,
+            0xF5,
+        ];
+        let req = || {
+            TxRequestIter::new(
+                0x01,
+                Addr::Long(0x0013_A200_415D_1DBB),
+                TxOptions::empty(),
+                b"Testing".iter().cloned(),
+            )
+        };
+
+        let mut out = [0; 32];
+        let len = pack_into(req(), false, &mut out).unwrap();
+        assert_eq!(&out[..len], &test_frame[..]);
+
+        let mut out = [0; 16];
+        match pack_into(req(), false, &mut out) {
+            Err(ApiPackError::BufferTooSmall) => {}
+            _ => panic!("expected the buffer to be too small"),
+        }
+    }
+
+    #[test]
+    fn escaped_packing_test() {
+        use arrayvec::ArrayVec;
+
+        let data = [0x01, 0x01, 0x12, 0x34, 0x00, 0x7E, 0x11];
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let test_frame = [
+            0x7E,
+            0x00, 0x07,
+            0x01, 0x01, 0x12, 0x34, 0x00, 0x7D, 0x5E, 0x7D, 0x31,
+            0x28,
+        ];
+        let mut vec: ArrayVec<[u8; 32]> = ArrayVec::new();
+        let packed_frame =
+            FramePacker::new(data.iter().cloned(), true, false).expect("packing error");
+        vec.extend(packed_frame);
+        assert_eq!(vec.as_slice(), &test_frame[..]);
+    }
+
+    #[test]
+    fn frame_format_ap_test() {
+        for &format in [FrameFormat::Unescaped, FrameFormat::Escaped].iter() {
+            assert_eq!(FrameFormat::try_from(format.ap_value()), Ok(format));
+        }
+        // Transparent mode
+        assert_eq!(FrameFormat::try_from(0), Err(0));
+    }
+
+    #[test]
+    fn power_level_test() {
+        let levels = [
+            (PowerLevel::Lowest, -5, -5),
+            (PowerLevel::Low, -1, -3),
+            (PowerLevel::Medium, 1, -1),
+            (PowerLevel::High, 3, 1),
+            (PowerLevel::Highest, 5, 3),
+        ];
+        for (pl, &(level, boosted, normal)) in levels.iter().enumerate() {
+            assert_eq!(level as u8, pl as u8);
+            assert_eq!(PowerLevel::try_from(pl as u8), Ok(level));
+            assert_eq!(level.dbm(true), boosted);
+            assert_eq!(level.dbm(false), normal);
+        }
+        assert_eq!(PowerLevel::try_from(5), Err(5));
+    }
 }
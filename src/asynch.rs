@@ -0,0 +1,227 @@
+//! Async API mode driver built on embedded-hal-async.
+//!
+//! Instead of polling `transmit_and_receive`, `recv_frame` suspends on the ATTN line until the
+//! radio has data. Framing and parsing are shared with the blocking drivers.
+
+use core::convert::Infallible;
+
+use embedded_hal_1::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::api_frame::{
+    self, ApiDataOwned, ApiPackError, ApiUnpackError, FrameFormat, FramePacker,
+};
+use crate::eh1::SPI_CHUNK_SIZE;
+use crate::queue::{RxQueue, XBeeQueue};
+use crate::{RxFramer, SpiIoError};
+
+#[derive(Debug)]
+pub enum AsyncError<SE, AE> {
+    Io(SpiIoError<SE, Infallible, AE>),
+    Pack(ApiPackError),
+    /// A corrupt frame was received and dropped.
+    Unpack(ApiUnpackError),
+    /// A received frame was larger than `api_frame::MAX_FRAME_DATA` and was dropped.
+    FrameTooLong,
+    /// The receive queue filled up without holding a complete frame and was cleared.
+    Overflow,
+}
+
+/// Async API mode driver for an XBee on an embedded-hal-async `SpiDevice`.
+// TODO: xbee reset pin
+pub struct XBeeApiSpiAsync<S, A> {
+    spi: S,
+    attn: A,
+
+    // TODO: make generic and allow passing in buffers
+    rx_queue: RxQueue,
+    rx_frame: RxFramer,
+}
+
+impl<S, A> XBeeApiSpiAsync<S, A>
+where
+    S: SpiDevice<u8>,
+    A: Wait + InputPin,
+{
+    pub fn new(spi: S, attn: A) -> XBeeApiSpiAsync<S, A> {
+        XBeeApiSpiAsync {
+            spi,
+            attn,
+            rx_queue: RxQueue::new(),
+            rx_frame: RxFramer::new(FrameFormat::Unescaped),
+        }
+    }
+
+    /// Releases the SPI device and ATTN pin.
+    pub fn free(self) -> (S, A) {
+        (self.spi, self.attn)
+    }
+
+    pub fn rx_queue_empty(&self) -> bool {
+        self.rx_queue.is_empty()
+    }
+
+    /// Sets the frame format the radio has been configured for with `AP`, which defaults to
+    /// `FrameFormat::Unescaped`.
+    pub fn set_frame_format(&mut self, format: FrameFormat) {
+        self.rx_frame = RxFramer::new(format);
+    }
+
+    /// Packs and sends the data portion of a frame, such as a `TxRequestIter`.
+    ///
+    /// Bytes the radio sends while the frame is going out are kept for `recv_frame`, as long as
+    /// there is room in the receive queue.
+    pub async fn send_frame<I>(&mut self, data: I) -> Result<(), AsyncError<S::Error, A::Error>>
+    where
+        I: ExactSizeIterator<Item = u8>,
+    {
+        let mut frame = FramePacker::new(data, self.rx_frame.format.is_escaped(), false)
+            .map_err(AsyncError::Pack)?;
+
+        loop {
+            let mut buf = [0xFF; SPI_CHUNK_SIZE];
+            let mut len = 0;
+            for (dst, src) in buf.iter_mut().zip(frame.by_ref()) {
+                *dst = src;
+                len += 1;
+            }
+            if len == 0 {
+                return Ok(());
+            }
+
+            let capture = self.attn_asserted()?;
+            self.spi
+                .transfer_in_place(&mut buf[..len])
+                .await
+                .map_err(|err| AsyncError::Io(SpiIoError::Serial(err)))?;
+            if capture {
+                self.accept(&buf[..len]);
+            }
+        }
+    }
+
+    /// Waits for the next complete frame from the radio.
+    pub async fn recv_frame(&mut self) -> Result<ApiDataOwned, AsyncError<S::Error, A::Error>> {
+        loop {
+            if let Some(frame) = self.take_frame()? {
+                return Ok(frame);
+            }
+
+            if !self.attn_asserted()? {
+                self.attn
+                    .wait_for_low()
+                    .await
+                    .map_err(|err| AsyncError::Io(SpiIoError::Attn(err)))?;
+            }
+
+            let free = self.rx_queue.capacity() - self.rx_queue.len();
+            if free == 0 {
+                self.rx_queue.clear();
+                self.rx_frame.reset();
+                return Err(AsyncError::Overflow);
+            }
+
+            let len = self.rx_frame.bytes_expected().min(SPI_CHUNK_SIZE).min(free);
+            let mut buf = [0xFF; SPI_CHUNK_SIZE];
+            self.spi
+                .transfer_in_place(&mut buf[..len])
+                .await
+                .map_err(|err| AsyncError::Io(SpiIoError::Serial(err)))?;
+            self.accept(&buf[..len]);
+        }
+    }
+
+    fn attn_asserted(&mut self) -> Result<bool, AsyncError<S::Error, A::Error>> {
+        self.attn
+            .is_low()
+            .map_err(|err| AsyncError::Io(SpiIoError::Attn(err)))
+    }
+
+    fn accept(&mut self, bytes: &[u8]) {
+        for &rx in bytes {
+            // Bytes outside of a frame are line fill, not data
+            self.rx_frame.receive(&mut self.rx_queue, rx);
+        }
+    }
+
+    /// Removes the first complete frame from the receive queue, if there is one.
+    fn take_frame(&mut self) -> Result<Option<ApiDataOwned>, AsyncError<S::Error, A::Error>> {
+        let (frame, used) = match api_frame::unpack_frame(self.rx_queue.as_slice(), false, false) {
+            Ok((data, rem)) => (
+                ApiDataOwned::from_slice(data),
+                self.rx_queue.len() - rem.len(),
+            ),
+            // Not enough bytes for a whole frame yet
+            Err(ApiUnpackError::BadLength(_)) => return Ok(None),
+            Err(ApiUnpackError::NoStart) => {
+                self.rx_queue.remove_until_start();
+                return Ok(None);
+            }
+            Err(err) => {
+                if self.rx_frame.format.is_escaped() {
+                    // Frame boundaries are known, so a start delimiter value in the data is not
+                    // mistaken for the next frame
+                    self.rx_queue.remove_frame();
+                } else {
+                    // Skip past the bad frame's start delimiter
+                    self.rx_queue.remove_exact(1);
+                    self.rx_queue.remove_until_start();
+                }
+                return Err(AsyncError::Unpack(err));
+            }
+        };
+
+        self.rx_queue.remove_exact(used);
+        frame.map(Some).ok_or(AsyncError::FrameTooLong)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::api_frame::{ApiData, AtCommandIter, AtCommandStatus};
+    use crate::mock::{block_on, MockRadio};
+
+    #[test]
+    fn async_round_trip_test() {
+        let radio = MockRadio::new();
+        radio.respond(&[0x7E, 0x00, 0x05, 0x88, 0x01, b'N', b'H', 0x00, 0xE0]);
+
+        let mut xbee = XBeeApiSpiAsync::new(radio.spi_device(), radio.attn());
+        block_on(xbee.send_frame(AtCommandIter::new(0x01, [b'N', b'H'], [].iter().cloned())))
+            .unwrap();
+        assert_eq!(
+            radio.sent().as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'H', 0x60]
+        );
+
+        let frame = block_on(xbee.recv_frame()).unwrap();
+        assert_eq!(
+            frame.parse().unwrap(),
+            ApiData::AtCommandResponse {
+                frame_id: 0x01,
+                at_cmd: [b'N', b'H'],
+                status: AtCommandStatus::Ok,
+                data: &[],
+            }
+        );
+        assert!(xbee.rx_queue_empty());
+    }
+
+    #[test]
+    fn async_bad_checksum_test() {
+        let radio = MockRadio::new();
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x00]);
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74]);
+
+        let mut xbee = XBeeApiSpiAsync::new(radio.spi_device(), radio.attn());
+        match block_on(xbee.recv_frame()) {
+            Err(AsyncError::Unpack(ApiUnpackError::BadChecksum(_))) => {}
+            _ => panic!("expected a checksum error"),
+        }
+        let frame = block_on(xbee.recv_frame()).unwrap();
+        assert_eq!(frame.as_slice(), &[0x8A, 0x01]);
+    }
+}
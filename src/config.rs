@@ -0,0 +1,781 @@
+//! Network, sleep and MAC settings applied and read back as a whole.
+
+use core::convert::TryFrom;
+
+use embedded_hal::blocking::delay::DelayMs;
+
+use crate::api_frame::{self, AtResponseData, MacMode, SleepMode};
+use crate::params::{self, AtParam};
+use crate::queue::TxQueue;
+use crate::transport::XBeeTransport;
+use crate::{AtError, AtResponse, XBeeApi};
+
+/// Value of `MY` that turns off the 16-bit source address.
+const NO_SHORT_ADDR: u16 = 0xFFFE;
+
+/// Longest cyclic sleep period (`SP` and `DP`) the radio accepts, in units of 10 ms.
+const MAX_SLEEP_PERIOD: u16 = 0x68B0;
+
+/// Most retries (`RR`) the radio accepts.
+const MAX_RETRIES: u8 = 6;
+
+/// Largest random delay slot exponent (`RN`) the radio accepts.
+const MAX_BACKOFF_EXPONENT: u8 = 3;
+
+/// Range of the CCA threshold (`CA`), as a magnitude in -dBm.
+const MIN_CCA_THRESHOLD: u8 = 0x24;
+const MAX_CCA_THRESHOLD: u8 = 0x50;
+
+/// Error from applying or reading a config, with the AT command that failed.
+#[derive(Debug)]
+pub struct ConfigError<E> {
+    pub command: [u8; 2],
+    pub error: AtError<E>,
+}
+
+/// The settings that decide which network a node is on and how it is addressed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConfig {
+    /// Channel (`CH`), from 0x0B to 0x1A.
+    pub channel: u8,
+    /// PAN ID (`ID`).
+    pub pan_id: u16,
+    /// 16-bit source address (`MY`), or `None` to only be reachable by the 64-bit serial number.
+    pub my_addr: Option<u16>,
+    /// Whether the node is the coordinator of its PAN (`CE`).
+    pub coordinator: bool,
+    /// End device association options (`A1`).
+    pub end_device_association: u8,
+    /// Coordinator association options (`A2`).
+    pub coordinator_association: u8,
+    /// Destination of transparent mode data and I/O samples (`DH` and `DL`). Left as it is by
+    /// `apply` when `None`.
+    pub destination: Option<u64>,
+}
+
+impl NetworkConfig {
+    /// Sets every field on the radio, waiting up to `timeout_ms` milliseconds for each answer.
+    ///
+    /// The values are sent as AT queue parameter frames and only take effect together with the
+    /// final `AC`, so the radio never runs with half of the new settings. Nothing is written to
+    /// flash.
+    pub fn apply<T, Q, D>(
+        &self,
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let mut set = |at_cmd: [u8; 2], params: &[u8], queue_param: bool| {
+            xbee.at_exchange(at_cmd, params, queue_param, delay, timeout_ms)
+                .map(|_| ())
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        let channel = params::Channel(self.channel);
+        set(params::Channel::AT_CMD, &channel.to_params(), true)?;
+        let pan_id = params::PanId(self.pan_id);
+        set(params::PanId::AT_CMD, &pan_id.to_params(), true)?;
+        let my_addr = params::SourceAddr(self.my_addr.unwrap_or(NO_SHORT_ADDR));
+        set(params::SourceAddr::AT_CMD, &my_addr.to_params(), true)?;
+        let coordinator = api_frame::at_param_u8(self.coordinator as u8);
+        set([b'C', b'E'], &coordinator, true)?;
+        let a1 = api_frame::at_param_u8(self.end_device_association);
+        set([b'A', b'1'], &a1, true)?;
+        let a2 = api_frame::at_param_u8(self.coordinator_association);
+        set([b'A', b'2'], &a2, true)?;
+        if let Some(destination) = self.destination {
+            let high = params::DestAddrHigh((destination >> 32) as u32);
+            set(params::DestAddrHigh::AT_CMD, &high.to_params(), true)?;
+            let low = params::DestAddrLow(destination as u32);
+            set(params::DestAddrLow::AT_CMD, &low.to_params(), true)?;
+        }
+        set([b'A', b'C'], &[], false)
+    }
+
+    /// Reads the current settings from the radio, waiting up to `timeout_ms` milliseconds for
+    /// each answer.
+    pub fn read<T, Q, D>(
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<NetworkConfig, ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let mut get = |at_cmd: [u8; 2]| {
+            xbee.at_exchange(at_cmd, &[], false, delay, timeout_ms)
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        let params::Channel(channel) = decode_param(get(params::Channel::AT_CMD)?)?;
+        let params::PanId(pan_id) = decode_param(get(params::PanId::AT_CMD)?)?;
+        let params::SourceAddr(my_addr) = decode_param(get(params::SourceAddr::AT_CMD)?)?;
+        let coordinator = decode(get([b'C', b'E'])?, [b'C', b'E'], |data| data.as_u8())?;
+        let a1 = decode(get([b'A', b'1'])?, [b'A', b'1'], |data| data.as_u8())?;
+        let a2 = decode(get([b'A', b'2'])?, [b'A', b'2'], |data| data.as_u8())?;
+        let params::DestAddrHigh(high) = decode_param(get(params::DestAddrHigh::AT_CMD)?)?;
+        let params::DestAddrLow(low) = decode_param(get(params::DestAddrLow::AT_CMD)?)?;
+
+        Ok(NetworkConfig {
+            channel,
+            pan_id,
+            // 0xFFFF turns off the 16-bit address as well
+            my_addr: if my_addr >= NO_SHORT_ADDR {
+                None
+            } else {
+                Some(my_addr)
+            },
+            coordinator: coordinator != 0,
+            end_device_association: a1,
+            coordinator_association: a2,
+            destination: Some((high as u64) << 32 | low as u64),
+        })
+    }
+}
+
+/// How and when the radio sleeps.
+///
+/// A coordinator with cyclic sleepers should be given the same `sleep_period_ms` and
+/// `time_before_sleep_ms` as them, so it knows how long to hold their data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SleepConfig {
+    /// Sleep mode (`SM`).
+    pub mode: SleepMode,
+    /// How long a cyclic sleeper sleeps (`SP`), in milliseconds. The radio counts in units of
+    /// 10 ms, so this must be a multiple of 10, and anything over 268 seconds is cut down to it.
+    pub sleep_period_ms: u32,
+    /// How long the radio waits without activity before sleeping (`ST`), in milliseconds. Must
+    /// be at least 1.
+    pub time_before_sleep_ms: u16,
+    /// How long a cyclic sleeper sleeps while it is not associated (`DP`), in milliseconds. Same
+    /// units and limit as `sleep_period_ms`, but must be at least 10 ms.
+    pub disassociated_period_ms: u32,
+}
+
+impl SleepConfig {
+    /// `sleep_period_ms` in the units of `SP`, or `None` if it is not a multiple of 10 ms.
+    pub fn sleep_period_units(&self) -> Option<u16> {
+        period_units(self.sleep_period_ms)
+    }
+
+    /// `disassociated_period_ms` in the units of `DP`, or `None` if it is not a multiple of 10
+    /// ms or is 0.
+    pub fn disassociated_period_units(&self) -> Option<u16> {
+        period_units(self.disassociated_period_ms).filter(|&units| units != 0)
+    }
+
+    /// Sets every field on the radio, waiting up to `timeout_ms` milliseconds for each answer.
+    ///
+    /// Like `NetworkConfig::apply`, the values only take effect together with the final `AC`.
+    /// Nothing is sent if a field is out of range.
+    pub fn apply<T, Q, D>(
+        &self,
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let out_of_range = |at_cmd: [u8; 2]| ConfigError {
+            command: at_cmd,
+            error: AtError::OutOfRange,
+        };
+        let sleep_period = self
+            .sleep_period_units()
+            .ok_or_else(|| out_of_range([b'S', b'P']))?;
+        if self.time_before_sleep_ms == 0 {
+            return Err(out_of_range([b'S', b'T']));
+        }
+        let disassociated_period = self
+            .disassociated_period_units()
+            .ok_or_else(|| out_of_range([b'D', b'P']))?;
+
+        let mut set = |at_cmd: [u8; 2], params: &[u8], queue_param: bool| {
+            xbee.at_exchange(at_cmd, params, queue_param, delay, timeout_ms)
+                .map(|_| ())
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        set([b'S', b'M'], &api_frame::at_param_u8(self.mode as u8), true)?;
+        set([b'S', b'P'], &api_frame::at_param_u16(sleep_period), true)?;
+        let st = api_frame::at_param_u16(self.time_before_sleep_ms);
+        set([b'S', b'T'], &st, true)?;
+        let dp = api_frame::at_param_u16(disassociated_period);
+        set([b'D', b'P'], &dp, true)?;
+        set([b'A', b'C'], &[], false)
+    }
+
+    /// Reads the current settings from the radio, waiting up to `timeout_ms` milliseconds for
+    /// each answer.
+    pub fn read<T, Q, D>(
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<SleepConfig, ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let mut get = |at_cmd: [u8; 2]| {
+            xbee.at_exchange(at_cmd, &[], false, delay, timeout_ms)
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        let mode = decode(get([b'S', b'M'])?, [b'S', b'M'], |data| {
+            data.as_u8().and_then(|mode| SleepMode::try_from(mode).ok())
+        })?;
+        let sleep_period = decode(get([b'S', b'P'])?, [b'S', b'P'], |data| data.as_u16())?;
+        let st = decode(get([b'S', b'T'])?, [b'S', b'T'], |data| data.as_u16())?;
+        let dp = decode(get([b'D', b'P'])?, [b'D', b'P'], |data| data.as_u16())?;
+
+        Ok(SleepConfig {
+            mode,
+            sleep_period_ms: sleep_period as u32 * 10,
+            time_before_sleep_ms: st,
+            disassociated_period_ms: dp as u32 * 10,
+        })
+    }
+}
+
+/// `ms` in units of 10 ms, cut down to the longest sleep period, or `None` if it is not a
+/// multiple of 10.
+fn period_units(ms: u32) -> Option<u16> {
+    let units = ms / 10;
+    if units * 10 != ms {
+        return None;
+    }
+    Some(units.min(MAX_SLEEP_PERIOD as u32) as u16)
+}
+
+/// How the 802.15.4 MAC acknowledges, retries and backs off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RadioTuning {
+    /// MAC mode (`MM`).
+    pub mac_mode: MacMode,
+    /// Retries the radio makes on top of the 3 of the MAC when a unicast is not acknowledged
+    /// (`RR`), from 0 to 6.
+    pub retries: u8,
+    /// Exponent of the random number of backoff slots waited before transmitting (`RN`), from 0
+    /// to 3.
+    pub backoff_slots: u8,
+    /// Energy level above which the channel is taken to be busy (`CA`), in dBm from -80 to -36.
+    /// The radio stores it as a magnitude in -dBm.
+    pub cca_threshold_dbm: i8,
+}
+
+impl RadioTuning {
+    /// Sets every field on the radio, waiting up to `timeout_ms` milliseconds for each answer.
+    ///
+    /// Like `NetworkConfig::apply`, the values only take effect together with the final `AC`.
+    /// Nothing is sent if a field is out of range.
+    pub fn apply<T, Q, D>(
+        &self,
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let out_of_range = |at_cmd: [u8; 2]| ConfigError {
+            command: at_cmd,
+            error: AtError::OutOfRange,
+        };
+        if self.retries > MAX_RETRIES {
+            return Err(out_of_range([b'R', b'R']));
+        }
+        if self.backoff_slots > MAX_BACKOFF_EXPONENT {
+            return Err(out_of_range([b'R', b'N']));
+        }
+        let cca_threshold = cca_threshold_from_dbm(self.cca_threshold_dbm)
+            .ok_or_else(|| out_of_range([b'C', b'A']))?;
+
+        let mut set = |at_cmd: [u8; 2], params: &[u8], queue_param: bool| {
+            xbee.at_exchange(at_cmd, params, queue_param, delay, timeout_ms)
+                .map(|_| ())
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        let mac_mode = api_frame::at_param_u8(self.mac_mode as u8);
+        set([b'M', b'M'], &mac_mode, true)?;
+        set([b'R', b'R'], &api_frame::at_param_u8(self.retries), true)?;
+        set(
+            [b'R', b'N'],
+            &api_frame::at_param_u8(self.backoff_slots),
+            true,
+        )?;
+        set([b'C', b'A'], &api_frame::at_param_u8(cca_threshold), true)?;
+        set([b'A', b'C'], &[], false)
+    }
+
+    /// Reads the current settings from the radio, waiting up to `timeout_ms` milliseconds for
+    /// each answer.
+    pub fn read<T, Q, D>(
+        xbee: &mut XBeeApi<T, Q>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<RadioTuning, ConfigError<T::Error>>
+    where
+        T: XBeeTransport,
+        Q: TxQueue,
+        D: DelayMs<u16>,
+    {
+        let mut get = |at_cmd: [u8; 2]| {
+            xbee.at_exchange(at_cmd, &[], false, delay, timeout_ms)
+                .map_err(|error| ConfigError {
+                    command: at_cmd,
+                    error,
+                })
+        };
+
+        let mac_mode = decode(get([b'M', b'M'])?, [b'M', b'M'], |data| {
+            data.as_u8().and_then(|mode| MacMode::try_from(mode).ok())
+        })?;
+        let retries = decode(get([b'R', b'R'])?, [b'R', b'R'], |data| data.as_u8())?;
+        let backoff_slots = decode(get([b'R', b'N'])?, [b'R', b'N'], |data| data.as_u8())?;
+        let cca_threshold_dbm = decode(get([b'C', b'A'])?, [b'C', b'A'], |data| {
+            data.as_u8().and_then(cca_threshold_to_dbm)
+        })?;
+
+        Ok(RadioTuning {
+            mac_mode,
+            retries,
+            backoff_slots,
+            cca_threshold_dbm,
+        })
+    }
+}
+
+/// `CA` for a threshold in dBm, or `None` if the radio does not accept it.
+fn cca_threshold_from_dbm(dbm: i8) -> Option<u8> {
+    let magnitude = -(dbm as i16);
+    if !(MIN_CCA_THRESHOLD as i16..=MAX_CCA_THRESHOLD as i16).contains(&magnitude) {
+        return None;
+    }
+    Some(magnitude as u8)
+}
+
+/// The threshold in dBm set by a `CA` value, or `None` if it is outside the range `CA` takes.
+fn cca_threshold_to_dbm(raw: u8) -> Option<i8> {
+    if !(MIN_CCA_THRESHOLD..=MAX_CCA_THRESHOLD).contains(&raw) {
+        return None;
+    }
+    Some(-(raw as i8))
+}
+
+fn decode<E, V>(
+    response: AtResponse,
+    at_cmd: [u8; 2],
+    f: fn(AtResponseData) -> Option<V>,
+) -> Result<V, ConfigError<E>> {
+    f(AtResponseData(response.data())).ok_or(ConfigError {
+        command: at_cmd,
+        error: AtError::InvalidResponse,
+    })
+}
+
+fn decode_param<E, P: AtParam>(response: AtResponse) -> Result<P, ConfigError<E>> {
+    P::from_response(response.data()).ok_or(ConfigError {
+        command: P::AT_CMD,
+        error: AtError::InvalidResponse,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrayvec::ArrayVec;
+
+    use crate::api_frame::AtCommandStatus;
+    use crate::mock::MockSerial;
+    use crate::{FlushError, XBeeApiUart};
+
+    /// Frame type and AT command of each frame written.
+    fn sent_commands(serial: &MockSerial) -> ArrayVec<[(u8, [u8; 2]); 16]> {
+        let written = serial.written();
+        let mut rest = &written[..];
+        let mut commands = ArrayVec::new();
+        while !rest.is_empty() {
+            let (frame, rem) = api_frame::unpack_frame(rest, false, false).unwrap();
+            commands.push((frame[0], [frame[2], frame[3]]));
+            rest = rem;
+        }
+        commands
+    }
+
+    type SentParam = ([u8; 2], ArrayVec<[u8; 4]>);
+
+    /// AT command and parameter of each frame written.
+    fn sent_params(serial: &MockSerial) -> ArrayVec<[SentParam; 8]> {
+        let written = serial.written();
+        let mut rest = &written[..];
+        let mut params = ArrayVec::new();
+        while !rest.is_empty() {
+            let (frame, rem) = api_frame::unpack_frame(rest, false, false).unwrap();
+            params.push(([frame[2], frame[3]], frame[4..].iter().cloned().collect()));
+            rest = rem;
+        }
+        params
+    }
+
+    fn config() -> NetworkConfig {
+        NetworkConfig {
+            channel: 0x0C,
+            pan_id: 0x3332,
+            my_addr: Some(0x1234),
+            coordinator: true,
+            end_device_association: 0x00,
+            coordinator_association: 0x04,
+            destination: Some(0x0013_A200_415D_1DBB),
+        }
+    }
+
+    #[test]
+    fn apply_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        let commands = [
+            *b"CH", *b"ID", *b"MY", *b"CE", *b"A1", *b"A2", *b"DH", *b"DL", *b"AC",
+        ];
+        for (id, &at_cmd) in commands.iter().enumerate() {
+            serial.respond_at(id as u8 + 1, at_cmd, 0x00, &[]);
+        }
+        config().apply(&mut xbee, &mut delay, 10).unwrap();
+
+        let sent = sent_commands(&serial);
+        assert_eq!(sent.len(), commands.len());
+        for (&(frame_type, at_cmd), &expected) in sent.iter().zip(commands.iter()) {
+            assert_eq!(at_cmd, expected);
+            // Only AC is an AT command frame, the rest are queued
+            let expected_type = if at_cmd == *b"AC" { 0x08 } else { 0x09 };
+            assert_eq!(frame_type, expected_type);
+        }
+
+        // Stops at the first command the radio refuses
+        serial.respond_at(10, *b"CH", 0x00, &[]);
+        serial.respond_at(11, *b"ID", 0x03, &[]);
+        match config().apply(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::Status(AtCommandStatus::InvalidParam),
+            }) => assert_eq!(&command, b"ID"),
+            _ => panic!("expected ID to fail"),
+        }
+    }
+
+    #[test]
+    fn read_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"CH", 0x00, &[0x0C]);
+        serial.respond_at(2, *b"ID", 0x00, &[0x33, 0x32]);
+        serial.respond_at(3, *b"MY", 0x00, &[0x12, 0x34]);
+        serial.respond_at(4, *b"CE", 0x00, &[0x01]);
+        serial.respond_at(5, *b"A1", 0x00, &[0x00]);
+        serial.respond_at(6, *b"A2", 0x00, &[0x04]);
+        serial.respond_at(7, *b"DH", 0x00, &[0x00, 0x13, 0xA2, 0x00]);
+        serial.respond_at(8, *b"DL", 0x00, &[0x41, 0x5D, 0x1D, 0xBB]);
+        assert_eq!(
+            NetworkConfig::read(&mut xbee, &mut delay, 10).unwrap(),
+            config()
+        );
+
+        // MY answered with the wrong length
+        serial.respond_at(9, *b"CH", 0x00, &[0x0C]);
+        serial.respond_at(10, *b"ID", 0x00, &[0x33, 0x32]);
+        serial.respond_at(11, *b"MY", 0x00, &[0xFF]);
+        match NetworkConfig::read(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::InvalidResponse,
+            }) => assert_eq!(&command, b"MY"),
+            _ => panic!("expected MY to fail"),
+        }
+
+        match NetworkConfig::read(&mut xbee, &mut delay, 2) {
+            Err(ConfigError {
+                command,
+                error: AtError::Flush(FlushError::Timeout),
+            }) => assert_eq!(&command, b"CH"),
+            _ => panic!("expected a timeout"),
+        }
+    }
+
+    fn sleep_config() -> SleepConfig {
+        SleepConfig {
+            mode: SleepMode::CyclicSleep,
+            sleep_period_ms: 4000,
+            time_before_sleep_ms: 5000,
+            disassociated_period_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn sleep_period_units_test() {
+        let mut config = sleep_config();
+        assert_eq!(config.sleep_period_units(), Some(400));
+        config.sleep_period_ms = 0;
+        assert_eq!(config.sleep_period_units(), Some(0));
+        config.sleep_period_ms = 10;
+        assert_eq!(config.sleep_period_units(), Some(1));
+        // The longest period, 268 seconds
+        config.sleep_period_ms = 268_000;
+        assert_eq!(config.sleep_period_units(), Some(0x68B0));
+        config.sleep_period_ms = 268_010;
+        assert_eq!(config.sleep_period_units(), Some(0x68B0));
+        config.sleep_period_ms = u32::MAX - 5;
+        assert_eq!(config.sleep_period_units(), Some(0x68B0));
+        config.sleep_period_ms = 15;
+        assert_eq!(config.sleep_period_units(), None);
+
+        assert_eq!(config.disassociated_period_units(), Some(100));
+        config.disassociated_period_ms = 10;
+        assert_eq!(config.disassociated_period_units(), Some(1));
+        config.disassociated_period_ms = 300_000;
+        assert_eq!(config.disassociated_period_units(), Some(0x68B0));
+        config.disassociated_period_ms = 0;
+        assert_eq!(config.disassociated_period_units(), None);
+        config.disassociated_period_ms = 25;
+        assert_eq!(config.disassociated_period_units(), None);
+    }
+
+    #[test]
+    fn sleep_apply_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        let commands = [*b"SM", *b"SP", *b"ST", *b"DP", *b"AC"];
+        for (id, &at_cmd) in commands.iter().enumerate() {
+            serial.respond_at(id as u8 + 1, at_cmd, 0x00, &[]);
+        }
+        sleep_config().apply(&mut xbee, &mut delay, 10).unwrap();
+
+        let written = serial.written();
+        let params = sent_params(&serial);
+        assert_eq!(params.len(), commands.len());
+        assert_eq!(params[0], (*b"SM", [0x04].iter().cloned().collect()));
+        // 4000 ms in units of 10 ms
+        assert_eq!(params[1], (*b"SP", [0x01, 0x90].iter().cloned().collect()));
+        assert_eq!(params[2], (*b"ST", [0x13, 0x88].iter().cloned().collect()));
+        assert_eq!(params[3], (*b"DP", [0x00, 0x64].iter().cloned().collect()));
+        assert_eq!(params[4], (*b"AC", ArrayVec::new()));
+
+        // Out of range values are refused before anything is sent
+        let mut config = sleep_config();
+        config.sleep_period_ms = 4005;
+        match config.apply(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::OutOfRange,
+            }) => assert_eq!(&command, b"SP"),
+            _ => panic!("expected SP to be refused"),
+        }
+        let mut config = sleep_config();
+        config.time_before_sleep_ms = 0;
+        match config.apply(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::OutOfRange,
+            }) => assert_eq!(&command, b"ST"),
+            _ => panic!("expected ST to be refused"),
+        }
+        let mut config = sleep_config();
+        config.disassociated_period_ms = 0;
+        match config.apply(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::OutOfRange,
+            }) => assert_eq!(&command, b"DP"),
+            _ => panic!("expected DP to be refused"),
+        }
+        assert_eq!(serial.written().len(), written.len());
+    }
+
+    #[test]
+    fn sleep_read_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"SM", 0x00, &[0x04]);
+        serial.respond_at(2, *b"SP", 0x00, &[0x01, 0x90]);
+        serial.respond_at(3, *b"ST", 0x00, &[0x13, 0x88]);
+        serial.respond_at(4, *b"DP", 0x00, &[0x00, 0x64]);
+        assert_eq!(
+            SleepConfig::read(&mut xbee, &mut delay, 10).unwrap(),
+            sleep_config()
+        );
+
+        // 3 is not a sleep mode
+        serial.respond_at(5, *b"SM", 0x00, &[0x03]);
+        match SleepConfig::read(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::InvalidResponse,
+            }) => assert_eq!(&command, b"SM"),
+            _ => panic!("expected SM to fail"),
+        }
+    }
+
+    fn radio_tuning() -> RadioTuning {
+        RadioTuning {
+            mac_mode: MacMode::Ieee802154WithAcks,
+            retries: 6,
+            backoff_slots: 1,
+            cca_threshold_dbm: -44,
+        }
+    }
+
+    #[test]
+    fn radio_tuning_apply_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        let commands = [*b"MM", *b"RR", *b"RN", *b"CA", *b"AC"];
+        for (id, &at_cmd) in commands.iter().enumerate() {
+            serial.respond_at(id as u8 + 1, at_cmd, 0x00, &[]);
+        }
+        radio_tuning().apply(&mut xbee, &mut delay, 10).unwrap();
+
+        let written = serial.written();
+        let params = sent_params(&serial);
+        assert_eq!(params.len(), commands.len());
+        assert_eq!(params[0], (*b"MM", [0x02].iter().cloned().collect()));
+        assert_eq!(params[1], (*b"RR", [0x06].iter().cloned().collect()));
+        assert_eq!(params[2], (*b"RN", [0x01].iter().cloned().collect()));
+        // -44 dBm is sent as its magnitude
+        assert_eq!(params[3], (*b"CA", [0x2C].iter().cloned().collect()));
+        assert_eq!(params[4], (*b"AC", ArrayVec::new()));
+
+        // Out of range values are refused before anything is sent
+        let refused: [(RadioTuning, &[u8; 2]); 5] = [
+            (
+                RadioTuning {
+                    retries: 7,
+                    ..radio_tuning()
+                },
+                b"RR",
+            ),
+            (
+                RadioTuning {
+                    backoff_slots: 4,
+                    ..radio_tuning()
+                },
+                b"RN",
+            ),
+            (
+                RadioTuning {
+                    cca_threshold_dbm: -35,
+                    ..radio_tuning()
+                },
+                b"CA",
+            ),
+            (
+                RadioTuning {
+                    cca_threshold_dbm: -81,
+                    ..radio_tuning()
+                },
+                b"CA",
+            ),
+            // A positive threshold is not the magnitude
+            (
+                RadioTuning {
+                    cca_threshold_dbm: 44,
+                    ..radio_tuning()
+                },
+                b"CA",
+            ),
+        ];
+        for (tuning, expected) in refused.iter() {
+            match tuning.apply(&mut xbee, &mut delay, 10) {
+                Err(ConfigError {
+                    command,
+                    error: AtError::OutOfRange,
+                }) => assert_eq!(&command, *expected),
+                _ => panic!("expected {:?} to be refused", tuning),
+            }
+        }
+        assert_eq!(serial.written().len(), written.len());
+    }
+
+    #[test]
+    fn radio_tuning_read_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"MM", 0x00, &[0x02]);
+        serial.respond_at(2, *b"RR", 0x00, &[0x06]);
+        serial.respond_at(3, *b"RN", 0x00, &[0x01]);
+        serial.respond_at(4, *b"CA", 0x00, &[0x2C]);
+        assert_eq!(
+            RadioTuning::read(&mut xbee, &mut delay, 10).unwrap(),
+            radio_tuning()
+        );
+
+        // The ends of the CCA threshold range
+        for (i, &(raw, dbm)) in [(0x24, -36), (0x50, -80)].iter().enumerate() {
+            let id = 5 + i as u8 * 4;
+            serial.respond_at(id, *b"MM", 0x00, &[0x02]);
+            serial.respond_at(id + 1, *b"RR", 0x00, &[0x06]);
+            serial.respond_at(id + 2, *b"RN", 0x00, &[0x01]);
+            serial.respond_at(id + 3, *b"CA", 0x00, &[raw]);
+            let tuning = RadioTuning::read(&mut xbee, &mut delay, 10).unwrap();
+            assert_eq!(tuning.cca_threshold_dbm, dbm);
+        }
+
+        // 0x51 is past the range CA takes
+        serial.respond_at(13, *b"MM", 0x00, &[0x02]);
+        serial.respond_at(14, *b"RR", 0x00, &[0x06]);
+        serial.respond_at(15, *b"RN", 0x00, &[0x01]);
+        serial.respond_at(16, *b"CA", 0x00, &[0x51]);
+        match RadioTuning::read(&mut xbee, &mut delay, 10) {
+            Err(ConfigError {
+                command,
+                error: AtError::InvalidResponse,
+            }) => assert_eq!(&command, b"CA"),
+            _ => panic!("expected CA to fail"),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Support for HALs implementing the embedded-hal 1.0 traits.
+//!
+//! The UART based transparent mode driver is used through the `Serial` and `Delay` adapters,
+//! which present embedded-hal-nb serial ports and `DelayNs` providers as the embedded-hal 0.2
+//! traits `XBeeTransparent` is written against. The SPI API mode driver gets its own transport,
+//! `SpiDeviceTransport`, since a `SpiDevice` manages CS itself and only offers whole transactions
+//! rather than single byte exchanges.
+
+use core::convert::Infallible;
+
+use arrayvec::ArrayVec;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::serial::write::Default as BlockingWriteDefault;
+use embedded_hal::serial;
+use embedded_hal_1::delay::DelayNs;
+use embedded_hal_1::digital::InputPin;
+use embedded_hal_1::spi::SpiDevice;
+use embedded_hal_nb::serial as serial_nb;
+
+use super::transport::{take_stashed, XBeeTransport};
+use super::{SpiIoError, XBeeApi};
+
+/// Maximum number of bytes exchanged in a single SPI transaction.
+pub const SPI_CHUNK_SIZE: usize = 32;
+
+/// Presents an embedded-hal-nb serial port as the embedded-hal 0.2 serial traits.
+pub struct Serial<U>(pub U);
+
+impl<U> serial::Read<u8> for Serial<U>
+where
+    U: serial_nb::Read<u8>,
+{
+    type Error = U::Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        match self.0.read() {
+            Ok(word) => Ok(word),
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => Err(nb::Error::Other(err)),
+        }
+    }
+}
+
+impl<U> serial::Write<u8> for Serial<U>
+where
+    U: serial_nb::Write<u8>,
+{
+    type Error = U::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        match self.0.write(word) {
+            Ok(()) => Ok(()),
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => Err(nb::Error::Other(err)),
+        }
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        match self.0.flush() {
+            Ok(()) => Ok(()),
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => Err(nb::Error::Other(err)),
+        }
+    }
+}
+
+impl<U> BlockingWriteDefault<u8> for Serial<U> where U: serial_nb::Write<u8> {}
+
+/// Presents a `DelayNs` provider as the embedded-hal 0.2 `DelayMs<u16>` trait.
+pub struct Delay<D>(pub D);
+
+impl<D> DelayMs<u16> for Delay<D>
+where
+    D: DelayNs,
+{
+    fn delay_ms(&mut self, ms: u16) {
+        self.0.delay_ms(ms as u32);
+    }
+}
+
+/// `SpiDevice` link with the radio's ATTN line signalling when it has data.
+///
+/// Bytes are exchanged in transactions of up to `SPI_CHUNK_SIZE` bytes with ATTN checked between
+/// transactions, so CS is released between chunks. Bytes clocked in while writing are kept for
+/// the next `read` when ATTN was asserted.
+pub struct SpiDeviceTransport<S, A> {
+    spi: S,
+    attn: A,
+
+    /// Bytes received but not yet read.
+    stash: ArrayVec<[u8; SPI_CHUNK_SIZE]>,
+}
+
+impl<S, A> SpiDeviceTransport<S, A>
+where
+    S: SpiDevice<u8>,
+    A: InputPin,
+{
+    pub fn new(spi: S, attn: A) -> SpiDeviceTransport<S, A> {
+        SpiDeviceTransport {
+            spi,
+            attn,
+            stash: ArrayVec::new(),
+        }
+    }
+
+    /// Releases the SPI device and ATTN pin.
+    pub fn free(self) -> (S, A) {
+        (self.spi, self.attn)
+    }
+}
+
+impl<S, A> XBeeTransport for SpiDeviceTransport<S, A>
+where
+    S: SpiDevice<u8>,
+    A: InputPin,
+{
+    type Error = SpiIoError<S::Error, Infallible, A::Error>;
+
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, Self::Error> {
+        let capture = self.attn.is_low().map_err(SpiIoError::Attn)?;
+        let mut len = buf.len().min(SPI_CHUNK_SIZE);
+        if capture {
+            len = len.min(self.stash.capacity() - self.stash.len());
+        }
+
+        let mut chunk = [0; SPI_CHUNK_SIZE];
+        chunk[..len].copy_from_slice(&buf[..len]);
+        self.spi
+            .transfer_in_place(&mut chunk[..len])
+            .map_err(SpiIoError::Serial)?;
+
+        if capture {
+            self.stash.extend(chunk[..len].iter().cloned());
+        }
+        Ok(len)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Self::Error> {
+        let stashed = take_stashed(&mut self.stash, buf);
+
+        let len = (buf.len() - stashed).min(SPI_CHUNK_SIZE);
+        if len == 0 || !self.attn.is_low().map_err(SpiIoError::Attn)? {
+            return Ok(stashed);
+        }
+
+        let chunk = &mut buf[stashed..stashed + len];
+        for byte in chunk.iter_mut() {
+            *byte = 0xFF;
+        }
+        self.spi
+            .transfer_in_place(chunk)
+            .map_err(SpiIoError::Serial)?;
+        Ok(stashed + len)
+    }
+
+    fn rx_pending(&mut self) -> Result<bool, Self::Error> {
+        if !self.stash.is_empty() {
+            return Ok(true);
+        }
+        self.attn.is_low().map_err(SpiIoError::Attn)
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(take_stashed(&mut self.stash, buf))
+    }
+}
+
+/// API mode driver for an XBee on an embedded-hal 1.0 `SpiDevice`.
+pub type XBeeApiSpiDevice<S, A> = XBeeApi<SpiDeviceTransport<S, A>>;
+
+impl<S, A> XBeeApiSpiDevice<S, A>
+where
+    S: SpiDevice<u8>,
+    A: InputPin,
+{
+    pub fn new(spi: S, attn: A) -> XBeeApiSpiDevice<S, A> {
+        XBeeApi::with_transport(SpiDeviceTransport::new(spi, attn))
+    }
+
+    /// Releases the SPI device and ATTN pin.
+    pub fn free(self) -> (S, A) {
+        self.transport.free()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal::serial::{Read, Write};
+
+    use crate::mock::{MockRadio, MockSerial};
+    use crate::XBeeTransparent;
+
+    #[test]
+    fn spi_device_round_trip_test() {
+        let radio = MockRadio::new();
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75, 0xFF, 0xFF]);
+
+        let mut xbee = XBeeApiSpiDevice::new(radio.spi_device(), radio.attn());
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+        }
+
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(
+            xbee.rx_queue.as_slice(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]
+        );
+        assert_eq!(
+            radio.sent().as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'H', 0x60]
+        );
+        // A single transaction carried the whole frame
+        assert_eq!(radio.cs_asserts(), 1);
+    }
+
+    #[test]
+    fn spi_device_receive_only_test() {
+        let radio = MockRadio::new();
+        let mut frame = [0; 54];
+        frame[..3].copy_from_slice(&[0x7E, 0x00, 0x32]);
+        frame[3] = 0x81;
+        radio.respond(&frame);
+
+        let mut xbee = XBeeApiSpiDevice::new(radio.spi_device(), radio.attn());
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.as_slice(), &frame[..]);
+        // Only the bytes of the frame were clocked, split into bounded chunks
+        assert_eq!(radio.sent().len(), frame.len());
+        assert!(radio.sent().iter().all(|&b| b == 0xFF));
+        assert!(radio.cs_asserts() > 2);
+    }
+
+    #[test]
+    fn serial_adapter_test() {
+        let serial = MockSerial::new();
+        serial.respond(b"OK\rB");
+
+        let mut uart = Serial(serial.port());
+        let mut delay = Delay(serial.delay());
+        {
+            let mut xbee = XBeeTransparent::new(&mut uart, &mut delay, b'+', 1000);
+            xbee.enter_command_mode().unwrap();
+            xbee.write(b'A').unwrap();
+            assert_eq!(xbee.read(), Ok(b'B'));
+        }
+        assert_eq!(serial.written().as_slice(), b"+++A");
+        assert_eq!(serial.delayed_ms(), 1000);
+    }
+}
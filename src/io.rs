@@ -0,0 +1,157 @@
+//! embedded-io `Read` and `Write` for the transparent mode driver, so the radio link can be
+//! handed to crates written against those traits.
+//!
+//! Reads block for the first byte and then take whatever else has already arrived, and writes
+//! block for the first byte and then send whatever else the UART accepts without blocking. An
+//! error from the UART on the first byte is returned, while one after it ends the call early with
+//! the bytes already transferred, so none are lost or sent twice.
+
+use core::fmt::Debug;
+
+use embedded_hal::serial::{Read, Write};
+use embedded_io::{ErrorKind, ErrorType};
+
+use super::XBeeTransparent;
+
+/// Error of the UART underneath, which the embedded-hal 0.2 traits give no kind for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IoError<E>(pub E);
+
+impl<E: Debug> embedded_io::Error for IoError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl<E, U, D> ErrorType for XBeeTransparent<U, D>
+where
+    U: Read<u8, Error = E>,
+    E: Debug,
+{
+    type Error = IoError<E>;
+}
+
+impl<E, U, D> embedded_io::Read for XBeeTransparent<U, D>
+where
+    U: Read<u8, Error = E>,
+    E: Debug,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError<E>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = block!(self.serial.read()).map_err(IoError)?;
+        let mut read = 1;
+        while read < buf.len() {
+            match self.serial.read() {
+                Ok(byte) => buf[read] = byte,
+                Err(_) => break,
+            }
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+impl<E, U, D> embedded_io::Write for XBeeTransparent<U, D>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    E: Debug,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError<E>> {
+        let (&first, rest) = match buf.split_first() {
+            Some(split) => split,
+            None => return Ok(0),
+        };
+
+        block!(self.serial.write(first)).map_err(IoError)?;
+        let mut written = 1;
+        for &byte in rest {
+            match self.serial.write(byte) {
+                Ok(()) => {}
+                Err(_) => break,
+            }
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError<E>> {
+        block!(self.serial.flush()).map_err(IoError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_io::{Read, Write};
+
+    use crate::mock::{MockError, MockSerial};
+    use crate::XBeeTransparent;
+
+    use super::IoError;
+
+    #[test]
+    fn read_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+
+        serial.respond(b"Hello");
+        let mut buf = [0; 3];
+        assert_eq!(xbee.read(&mut buf), Ok(3));
+        assert_eq!(&buf, b"Hel");
+        // Only what has already arrived
+        let mut buf = [0; 8];
+        assert_eq!(xbee.read(&mut buf), Ok(2));
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(xbee.read(&mut []), Ok(0));
+
+        serial.respond(b"ab");
+        // An error after the first byte keeps what was read
+        serial.fail_read_at(6);
+        assert_eq!(xbee.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], b'a');
+        assert_eq!(xbee.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], b'b');
+
+        serial.respond(b"X");
+        serial.fail_read_at(7);
+        assert_eq!(xbee.read(&mut buf), Err(IoError(MockError)));
+
+        serial.respond(b"Test");
+        let mut buf = [0; 5];
+        xbee.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"XTest");
+    }
+
+    #[test]
+    fn write_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+
+        serial.set_write_space(3);
+        assert_eq!(xbee.write(b"Hello"), Ok(3));
+        assert_eq!(serial.written().as_slice(), b"Hel");
+        assert_eq!(xbee.write(&[]), Ok(0));
+
+        serial.set_write_space(usize::MAX);
+        xbee.write_all(b"lo").unwrap();
+        xbee.flush().unwrap();
+        assert_eq!(serial.written().as_slice(), b"Hello");
+        assert_eq!(serial.flushes(), (1, 5));
+
+        // An error after the first byte reports what was sent, so nothing is sent twice
+        serial.fail_write_at(6);
+        assert_eq!(xbee.write(b"abc"), Ok(1));
+        assert_eq!(xbee.write(b"bc"), Ok(2));
+        assert_eq!(serial.written().as_slice(), b"Helloabc");
+
+        serial.fail_write_at(8);
+        assert_eq!(xbee.write(b"d"), Err(IoError(MockError)));
+        assert_eq!(serial.written().as_slice(), b"Helloabc");
+    }
+}
@@ -7,372 +7,6368 @@ extern crate arrayvec;
 #[macro_use]
 extern crate bitflags;
 extern crate embedded_hal;
+#[cfg(feature = "eh1")]
+extern crate embedded_hal_1;
+#[cfg(feature = "async")]
+extern crate embedded_hal_async;
+#[cfg(feature = "eh1")]
+extern crate embedded_hal_nb;
+#[cfg(feature = "embedded-io")]
+extern crate embedded_io;
+#[cfg(feature = "heapless")]
+extern crate heapless;
 #[macro_use]
 extern crate nb;
 
 pub mod api_frame;
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod config;
+#[cfg(feature = "eh1")]
+pub mod eh1;
+#[cfg(feature = "embedded-io")]
+pub mod io;
+#[cfg(test)]
+mod mock;
+pub mod params;
+pub mod queue;
+mod retry;
+pub mod sleep;
+pub mod transport;
 
-use core::marker::PhantomData;
+use core::cell::Cell;
+use core::convert::TryFrom;
+use core::ops::{Deref, DerefMut};
 
-use api_frame::{ApiData, ApiUnpackError, FramePacker, TxOptions, TxRequestIter};
+use crate::api_frame::{
+    ApiData, ApiPackError, ApiParseError, ApiUnpackError, AssociationStatus, AtCommandIter,
+    AtCommandStatus, AtResponseData, BaudRate, ChannelIndicator, DioPin, EnergyScan, FrameFormat,
+    FramePacker, IoSample, ModemStatus, NodeDiscoveryResult, PinFunction, PinLevel, PowerLevel,
+    RemoteAtCommandIter, RemoteAtOptions, SleepMode, TxOptions, TxRequestIter,
+};
+use crate::params::AtParam;
+use crate::queue::{RxQueue, TxQueue, XBeeQueue};
+use crate::retry::TxRetry;
+use crate::transport::{
+    AttnPolarity, FlowControlTransport, NoPin, SerialTransport, SpiTransferTransport, SpiTransport,
+    XBeeTransport, READ_CHUNK_SIZE,
+};
 
 use arraydeque::ArrayDeque;
-use arrayvec::{Array, ArrayVec};
+use arrayvec::ArrayVec;
 use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::serial::Write as BlockingWrite;
-use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
 use embedded_hal::serial::{Read, Write};
 use embedded_hal::spi::FullDuplex;
 
 pub const BROADCAST_ADDR: u16 = 0xFFFF;
 pub const COORDINATOR_ADDR: u16 = 0xFFFE;
+/// Largest payload the 802.15.4 firmware sends in a single packet, without encryption.
+pub const MAX_TX_PAYLOAD: usize = 100;
 
-trait XBeeQueue {
-    fn remove_until_start(&mut self) -> Result<usize, ()>;
-    fn remove_exact(&mut self, amount: usize) -> Result<(), ()>;
+// TODO: builders
+
+// TODO: maybe add coordinator
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Addr {
+    Short(u16),
+    Long(u64),
+    /// Every node in range, the same as `Short(BROADCAST_ADDR)`.
+    Broadcast,
+}
+
+/// Packs a TX request for `addr` into `out` in one call, returning the length of the frame.
+///
+/// The frame is unescaped and sent with default options. The reverse of
+/// `api_frame::unpack_frame`.
+pub fn encode_tx_frame(
+    frame_id: u8,
+    addr: Addr,
+    data: &[u8],
+    out: &mut [u8],
+) -> Result<usize, ApiPackError> {
+    if data.len() > MAX_TX_PAYLOAD {
+        return Err(ApiPackError::TooLong);
+    }
+    let req = TxRequestIter::new(frame_id, addr, TxOptions::empty(), data.iter().cloned());
+    api_frame::pack_into(req, false, out)
 }
 
-impl<A> XBeeQueue for ArrayVec<A>
+/// Lends a peripheral to a driver that otherwise takes ownership of it.
+///
+/// Returned by the `new` constructors, which borrow their peripherals, while `take` moves the
+/// peripherals into the driver so it can be stored without lifetimes, such as in a `static`.
+pub struct Borrowed<'a, T: 'a>(pub &'a mut T);
+
+impl<'a, T, W> FullDuplex<W> for Borrowed<'a, T>
 where
-    A: Array<Item = u8>,
+    T: FullDuplex<W>,
 {
-    fn remove_until_start(&mut self) -> Result<usize, ()> {
-        match self.iter().position(|c| c == &api_frame::START) {
-            Some(size) => {
-                self.remove_exact(size)?;
-                Ok(size)
-            }
-            None => {
-                let len = self.len();
-                self.clear();
-                Ok(len)
-            }
-        }
-    }
+    type Error = T::Error;
 
-    fn remove_exact(&mut self, amount: usize) -> Result<(), ()> {
-        if amount == 0 {
-            return Ok(());
-        }
+    fn read(&mut self) -> nb::Result<W, Self::Error> {
+        self.0.read()
+    }
 
-        if amount <= self.len() {
-            self.drain(0..amount);
-            Ok(())
-        } else {
-            Err(())
-        }
+    fn send(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.0.send(word)
     }
 }
 
-// TODO: builders
-
-// TODO: maybe add broadcast
-// TODO: maybe add coordinator
-pub enum Addr {
-    Short(u16),
-    Long(u64),
-}
+// Only for bytes, as other word types would overlap with the blanket impl for
+// `transfer::Default` types
+impl<'a, T> Transfer<u8> for Borrowed<'a, T>
+where
+    T: Transfer<u8>,
+{
+    type Error = T::Error;
 
-// TODO: xbee reset pin
-pub struct XBeeTransparent<'a, 'b, U: 'a, D: 'b> {
-    serial: &'a mut U,
-    timer: &'b mut D,
-    cmd_char: u8,
-    guard_time: u16,
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        self.0.transfer(words)
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum XBeeApiError {
-    Unpack(ApiUnpackError),
-    Parse(()),
-}
+impl<'a, T> InputPin for Borrowed<'a, T>
+where
+    T: InputPin,
+{
+    type Error = T::Error;
 
-// TODO: xbee reset pin
-pub struct XBeeApiSpi<'a, 'b, 'c, S: 'a, C: 'b, A: 'c> {
-    serial: &'a mut S,
-    cs: Option<&'b mut C>,
-    attn: &'c mut A,
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
 
-    // TODO: make generic and allow passing in buffers
-    tx_queue: ArrayDeque<[u8; 512]>,
-    rx_queue: ArrayVec<[u8; 512]>,
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
 }
 
-impl<'a, 'b, E, U, D> XBeeTransparent<'a, 'b, U, D>
+impl<'a, T> OutputPin for Borrowed<'a, T>
 where
-    U: Read<u8, Error = E> + BlockingWrite<u8, Error = E>,
-    D: DelayMs<u16>,
+    T: OutputPin,
 {
-    pub fn new(
-        uart: &'a mut U,
-        delay: &'b mut D,
-        cmd_char: u8,
-        guard_time: u16,
-    ) -> XBeeTransparent<'a, 'b, U, D> {
-        XBeeTransparent {
-            serial: uart,
-            timer: delay,
-            cmd_char,
-            guard_time,
-        }
+    type Error = T::Error;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
     }
 
-    // TODO: maybe return result to show that the command has
-    pub fn enter_command_mode(&mut self) -> Result<(), E> {
-        // wait for guard time
-        self.timer.delay_ms(self.guard_time);
-        // send command character x3
-        self.serial.bwrite_all(&[self.cmd_char; 3])?;
-        // wait for "OK"
-        loop {
-            match self.serial.read() {
-                Ok(b'O') => break,
-                Ok(_) => panic!("Got other character while waiting for OK"), // TODO: error
-                Err(nb::Error::WouldBlock) => {} // keep blocking
-                Err(_) => panic!("Some error while waiting for OK"), // return Err(e.into()),
-            }
-        }
-        loop {
-            match self.serial.read() {
-                Ok(b'K') => break,
-                Ok(_) => panic!("Got other character while waiting for OK"), // TODO: error
-                Err(nb::Error::WouldBlock) => {} // keep blocking
-                Err(_) => panic!("Some error while waiting for OK"), // return Err(e.into()),
-            }
-        }
-        Ok(())
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
     }
 }
 
-impl<'a, 'b, U, D> Read<u8> for XBeeTransparent<'a, 'b, U, D>
+impl<'a, T, W> Read<W> for Borrowed<'a, T>
 where
-    U: Read<u8>,
+    T: Read<W>,
 {
-    type Error = U::Error;
+    type Error = T::Error;
 
-    fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        self.serial.read()
+    fn read(&mut self) -> nb::Result<W, Self::Error> {
+        self.0.read()
     }
 }
 
-impl<'a, 'b, U, D> Write<u8> for XBeeTransparent<'a, 'b, U, D>
+impl<'a, T, W> Write<W> for Borrowed<'a, T>
 where
-    U: Write<u8>,
+    T: Write<W>,
 {
-    type Error = U::Error;
+    type Error = T::Error;
 
-    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
-        self.serial.write(word)
+    fn write(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.0.write(word)
     }
 
     fn flush(&mut self) -> nb::Result<(), Self::Error> {
-        self.serial.flush()
+        self.0.flush()
     }
 }
 
-impl<'a, 'b, 'c, E, S, C, A> XBeeApiSpi<'a, 'b, 'c, S, C, A>
+// Only for bytes, as other word types would overlap with the blanket impl for
+// `write::Default` types
+impl<'a, T> BlockingWrite<u8> for Borrowed<'a, T>
 where
-    S: FullDuplex<u8, Error = E>,
-    C: OutputPin,
-    A: InputPin,
+    T: BlockingWrite<u8>,
 {
-    pub fn new(
-        spi: &'a mut S,
-        cs: Option<&'b mut C>,
-        attn: &'c mut A,
-    ) -> XBeeApiSpi<'a, 'b, 'c, S, C, A> {
-        XBeeApiSpi {
-            serial: spi,
-            cs,
-            attn,
-            tx_queue: ArrayDeque::new(),
-            rx_queue: ArrayVec::new(),
-        }
-    }
+    type Error = T::Error;
 
-    pub fn tx_queue_empty(&self) -> bool {
-        self.tx_queue.is_empty()
+    fn bwrite_all(&mut self, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.0.bwrite_all(buffer)
     }
 
-    pub fn tx_queue_full(&self) -> bool {
-        self.tx_queue.is_full()
+    fn bflush(&mut self) -> Result<(), Self::Error> {
+        self.0.bflush()
     }
+}
 
-    pub fn rx_queue_empty(&self) -> bool {
-        self.rx_queue.is_empty()
+impl<'a, T, UXX> DelayMs<UXX> for Borrowed<'a, T>
+where
+    T: DelayMs<UXX>,
+{
+    fn delay_ms(&mut self, ms: UXX) {
+        self.0.delay_ms(ms)
     }
+}
 
-    pub fn rx_queue_full(&self) -> bool {
-        self.rx_queue.is_full()
-    }
+// TODO: xbee reset pin
+pub struct XBeeTransparent<U, D> {
+    serial: U,
+    timer: D,
+    cmd_char: u8,
+    guard_time: u16,
+    response_timeout_ms: u16,
+    in_command_mode: bool,
+    /// Idle time after `write_packet`, in milliseconds.
+    packetization_ms: u16,
+}
 
-    // TODO: differentiate between errors from reading and writing
-    pub fn transmit_and_receive(&mut self) -> Result<bool, E> {
-        if let Some(ref mut cs) = self.cs {
-            cs.set_low();
-        }
+#[derive(Copy, Clone, Debug)]
+pub enum XBeeApiError {
+    Unpack(ApiUnpackError),
+    Parse(ApiParseError),
+    /// The RX queue is full with `RxOverflowPolicy::Fail` while the radio has more to send.
+    /// Nothing has been dropped, and reading resumes once frames are taken out of the queue.
+    RxOverflow,
+}
 
-        let ret = self.tx_rx_internal();
+/// Errors from queueing a frame with `XBeeApiSender`. Nothing is queued when one is returned.
+#[derive(Debug)]
+pub enum SendError {
+    /// Not enough room is left in the transmit queue for the frame.
+    QueueFull,
+    /// The payload is larger than `MAX_TX_PAYLOAD`.
+    PayloadTooLarge,
+    /// An AT command parameter is longer than the radio accepts.
+    ParamTooLong,
+    Pack(ApiPackError),
+}
 
-        if let Some(ref mut cs) = self.cs {
-            cs.set_high();
-        }
+/// Longest node identifier (`NI`) the radio accepts, in bytes.
+pub const MAX_NODE_IDENTIFIER: usize = 20;
 
-        ret
-    }
+/// Shortest and longest guard times (`GT`) the radio accepts, in milliseconds.
+pub const MIN_GUARD_TIME: u16 = 0x02;
+pub const MAX_GUARD_TIME: u16 = 0x0CE4;
 
-    pub fn tx_rx_internal(&mut self) -> Result<bool, E> {
-        let mut val_read = false;
-        let mut attn_val;
-        while {
-            attn_val = self.attn.is_high();
-            !self.tx_queue.is_empty() || !attn_val
-        } {
-            let tx = if !self.tx_queue.is_empty() {
-                // TODO: don't unwrap, pass up error
-                self.tx_queue.pop_front().unwrap()
-            } else {
-                0xFF
-            };
+/// ADC reference voltage of the S2C in millivolts, which `%V` readings are scaled by.
+const SUPPLY_VREF_MV: u16 = 1200;
 
-            // TODO: better error handling?
-            block!(self.serial.send(tx))?;
+/// Most exchanges `XBeeApi::transmit_and_receive_timeout` makes with the transport between
+/// delays, each moving up to `READ_CHUNK_SIZE` bytes.
+pub const POLL_STEPS_PER_MS: usize = 16;
 
-            let rx = block!(self.serial.read())?;
-            if !attn_val {
-                // TODO: don't unwrap, pass up error
-                self.rx_queue.try_push(rx).unwrap();
-                val_read = true;
-                if self.rx_queue.is_full() {
-                    break;
-                }
-            }
-        }
+/// Command character (`CC`) and guard time (`GT`, in milliseconds) the radio ships with, which
+/// `XBeeTransparent::default_timing` uses.
+pub const DEFAULT_CMD_CHAR: u8 = b'+';
+pub const DEFAULT_GUARD_TIME: u16 = 1000;
 
-        Ok(val_read)
-    }
+/// Packetization timeout (`RO`) the radio ships with, in character times.
+pub const DEFAULT_PACKETIZATION_TIMEOUT: u8 = 3;
 
-    pub fn get_sender_receiver<'d>(&'d mut self) -> (XBeeApiSender<'d, E>, XBeeApiReceiver<'d, E>) {
-        let tx_queue = &mut self.tx_queue;
-        let rx_queue = &mut self.rx_queue;
+/// How long `XBeeTransparent` waits for the radio to answer by default, in milliseconds.
+pub const DEFAULT_RESPONSE_TIMEOUT_MS: u16 = 500;
 
-        let sender = XBeeApiSender {
-            tx_queue,
-            _error: PhantomData,
-        };
-        let receiver = XBeeApiReceiver {
-            rx_queue,
-            _error: PhantomData,
-        };
+/// Most sample sets per IO packet (`IT`) `IoSample::parse` can be handed, as each set takes at
+/// least 2 bytes after the 3 byte header and the packet has to fit in `MAX_TX_PAYLOAD`.
+pub const MAX_IO_SAMPLES_PER_PACKET: u8 = ((MAX_TX_PAYLOAD - 3) / 2) as u8;
 
-        (sender, receiver)
+/// Most TX requests kept for resending at once, see `XBeeApi::set_tx_retry`.
+pub const TX_RETRY_SLOTS: usize = 4;
+
+/// Most response bytes kept by `XBeeApi::at_query`.
+pub const MAX_AT_RESPONSE: usize = 32;
+
+/// Most bytes of a command mode answer kept by `XBeeTransparent`, without the ending CR.
+pub const MAX_COMMAND_RESPONSE: usize = 16;
+
+/// Answer to a local AT command, from `XBeeApi::at_query`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtResponse {
+    pub status: AtCommandStatus,
+    data: ArrayVec<[u8; MAX_AT_RESPONSE]>,
+}
+
+impl AtResponse {
+    /// The response data, cut short after `MAX_AT_RESPONSE` bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 }
 
+/// Errors from `XBeeApi::transmit_and_receive` and the methods sending frames through it.
 #[derive(Debug)]
-pub struct XBeeApiSender<'a, E> {
-    // TODO: make generic
-    tx_queue: &'a mut ArrayDeque<[u8; 512]>,
-    _error: PhantomData<*const E>,
+pub enum FlushError<E> {
+    Io(E),
+    Send(SendError),
+    /// Receiving stopped, see `XBeeApiError::RxOverflow`.
+    Rx(XBeeApiError),
+    /// The TX queue was not empty after the allowed number of exchanges.
+    Timeout,
+    /// Frames were to be sent while the radio is marked as asleep with `XBeeApi::set_asleep`.
+    Asleep,
 }
 
-impl<'a, E> XBeeApiSender<'a, E> {
-    pub fn queue_empty(&self) -> bool {
-        self.tx_queue.is_empty()
+/// Errors from the `XBeeApi` helpers built on `at_query`.
+#[derive(Debug)]
+pub enum AtError<E> {
+    Flush(FlushError<E>),
+    /// The radio answered with a status other than `AtCommandStatus::Ok`.
+    Status(AtCommandStatus),
+    /// The answer was not the length expected for the command, or could not be decoded.
+    InvalidResponse,
+    /// A value was outside the range the radio accepts, so nothing was sent.
+    OutOfRange,
+}
+
+impl<E> From<FlushError<E>> for AtError<E> {
+    fn from(err: FlushError<E>) -> AtError<E> {
+        AtError::Flush(err)
     }
+}
 
-    pub fn queue_full(&self) -> bool {
-        self.tx_queue.is_full()
+/// Errors from `XBeeApi::wait_for_association`.
+#[derive(Debug)]
+pub enum AssociationError<E> {
+    At(AtError<E>),
+    /// The radio gave up joining a PAN.
+    Failed(AssociationStatus),
+    /// The radio was still scanning when the timeout ran out.
+    Timeout,
+}
+
+impl<E> From<AtError<E>> for AssociationError<E> {
+    fn from(err: AtError<E>) -> AssociationError<E> {
+        AssociationError::At(err)
     }
+}
 
-    pub fn send_data_raw(&mut self, data: &[u8]) -> Result<(), E> {
-        // TODO: error handling if we do not have enough space
-        self.tx_queue.extend(data.iter().map(|&x| x));
-        Ok(())
+/// Errors from `XBeeApi::verify_firmware`.
+#[derive(Debug)]
+pub enum FirmwareMismatch<E> {
+    At(AtError<E>),
+    /// The radio runs firmware other than 802.15.4, such as Zigbee or DigiMesh, whose frames
+    /// this crate cannot parse.
+    NotIeee802154 {
+        firmware_version: u16,
+    },
+}
+
+impl<E> From<AtError<E>> for FirmwareMismatch<E> {
+    fn from(err: AtError<E>) -> FirmwareMismatch<E> {
+        FirmwareMismatch::At(err)
     }
+}
 
-    pub fn send_data(&mut self, frame_id: u8, addr: Addr, data: &[u8]) -> Result<(), E> {
-        let tx_request =
-            TxRequestIter::new(frame_id, addr, TxOptions::empty(), data.iter().map(|v| *v));
-        let frame = FramePacker::new(tx_request, false, false).expect("packing error"); // TODO:
+/// Errors from `XBeeTransparent::enter_command_mode`.
+#[derive(Debug)]
+pub enum CommandModeError<E> {
+    Serial(E),
+    /// The radio did not answer `OK\r` in time, such as when it is not there at all.
+    Timeout,
+}
 
-        // TODO: error handling if we do not have enough space
-        self.tx_queue.extend(frame);
-        Ok(())
+/// Errors from the `XBeeTransparent` helpers sending AT commands in command mode.
+#[derive(Debug)]
+pub enum CommandError<E> {
+    /// Command mode could not be entered.
+    CommandMode(CommandModeError<E>),
+    Serial(E),
+    /// The radio answered `ERROR`, such as for an unknown command or a value out of range.
+    Error,
+    /// The answer was longer than `MAX_COMMAND_RESPONSE` or could not be decoded.
+    InvalidResponse,
+    /// The radio did not finish answering within the response timeout, such as when command
+    /// mode has already timed out (`CT`).
+    Timeout,
+}
+
+/// Errors from `XBeeTransparent::switch_to_api_mode`.
+#[derive(Debug)]
+pub enum ApiModeError<E> {
+    /// Setting `AP` in command mode failed.
+    Command(CommandError<E>),
+    Serial(E),
+    /// Bytes were received, but never an answer to the `VR` frame sent to check the switch, such
+    /// as when the radio is at another baud rate.
+    BaudMismatch,
+    /// Nothing was received in answer to the `VR` frame, such as when the radio is not there.
+    NoResponse,
+}
+
+impl<E> From<CommandError<E>> for ApiModeError<E> {
+    fn from(err: CommandError<E>) -> ApiModeError<E> {
+        ApiModeError::Command(err)
     }
+}
 
-    pub fn send_data_no_ack(&mut self, frame_id: u8, addr: Addr, data: &[u8]) -> Result<(), E> {
-        let tx_request = TxRequestIter::new(
-            frame_id,
-            addr,
-            TxOptions::DISABLE_ACK,
-            data.iter().map(|v| *v),
-        );
-        let frame = FramePacker::new(tx_request, false, false).expect("packing error"); // TODO:
+/// Errors from `XBeeTransparent::change_baud`.
+#[derive(Debug)]
+pub enum BaudChangeError<E> {
+    Command(CommandError<E>),
+    /// Writing the new rate to flash (`WR`) failed, but command mode was left, so the radio has
+    /// switched to the new rate until it is next reset.
+    NotPersisted(PendingBaudChange, CommandError<E>),
+}
 
-        // TODO: error handling if we do not have enough space
-        self.tx_queue.extend(frame);
-        Ok(())
+impl<E> From<CommandError<E>> for BaudChangeError<E> {
+    fn from(err: CommandError<E>) -> BaudChangeError<E> {
+        BaudChangeError::Command(err)
     }
+}
 
-    pub fn at_command(&mut self, frame_id: u8, at_cmd: [u8; 2], params: &[u8]) {
-        unimplemented!()
+impl<E> From<CommandModeError<E>> for CommandError<E> {
+    fn from(err: CommandModeError<E>) -> CommandError<E> {
+        CommandError::CommandMode(err)
     }
+}
+
+/// Errors from the SPI transports, telling apart which part of the exchange failed.
+#[derive(Copy, Clone, Debug)]
+pub enum SpiIoError<E, CE, AE> {
+    /// Failure sending a byte with `FullDuplex::send`, such as while the radio resets.
+    Send(E),
+    /// Failure reading the byte clocked in with `FullDuplex::read`.
+    Read(E),
+    /// Failure of a transfer, which sends and reads at once.
+    Serial(E),
+    /// Failure driving CS.
+    Cs(CE),
+    /// Failure reading ATTN.
+    Attn(AE),
+}
+
+/// Position within the frame currently being received.
+///
+/// Used to tell frame bytes apart from the idle fill (0xFF) the radio clocks out when ATTN is
+/// released later than the last byte of a frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RxFrameState {
+    /// Waiting for a start delimiter.
+    Between,
+    LenH,
+    LenL(u8),
+    /// Number of data and checksum bytes still expected.
+    Body(usize),
+}
 
-    pub fn at_queue_param(&mut self, frame_id: u8, at_cmd: [u8; 2], params: &[u8]) {
-        unimplemented!()
+impl RxFrameState {
+    /// Advances the state with a received byte, returning whether the byte belongs to a frame.
+    fn accept(&mut self, byte: u8) -> bool {
+        *self = match *self {
+            RxFrameState::Between if byte == api_frame::START => RxFrameState::LenH,
+            RxFrameState::Between => return false,
+            RxFrameState::LenH => RxFrameState::LenL(byte),
+            RxFrameState::LenL(len_h) => {
+                RxFrameState::Body(((len_h as usize) << 8 | byte as usize) + 1)
+            }
+            RxFrameState::Body(1) => RxFrameState::Between,
+            RxFrameState::Body(left) => RxFrameState::Body(left - 1),
+        };
+        true
     }
 
-    pub fn remote_at_command(&mut self, frame_id: u8, addr: Addr, at_cmd: [u8; 2], params: &[u8]) {
-        unimplemented!()
+    /// Number of bytes known to still belong to the frame, counting a whole header when between
+    /// frames.
+    fn bytes_expected(&self) -> usize {
+        match *self {
+            RxFrameState::Between => 3,
+            RxFrameState::LenH => 2,
+            RxFrameState::LenL(_) => 1,
+            RxFrameState::Body(left) => left,
+        }
     }
 }
 
-impl<'a, E> Drop for XBeeApiSender<'a, E> {
-    fn drop(&mut self) {}
+/// What the driver does with received bytes once the RX queue is full.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RxOverflowPolicy {
+    /// Drops frames that do not fit, keeping the oldest data.
+    DropIncoming,
+    /// Drops whole frames from the front of the queue to make room, keeping the freshest data.
+    DropOldestFrame,
+    /// Stops reading from the radio until the receiver makes room, with `XBeeApi::poll` and
+    /// `XBeeApi::transmit_and_receive` returning `XBeeApiError::RxOverflow` while the radio has
+    /// more to send. Nothing is dropped by the driver, but a UART may lose bytes it cannot hold
+    /// in the meantime. A frame too long to ever fit in the queue is still dropped, as no amount
+    /// of waiting would make room for it.
+    Fail,
 }
 
-pub struct XBeeApiReceiver<'a, E> {
-    // TODO: make generic
-    rx_queue: &'a mut ArrayVec<[u8; 512]>,
-    _error: PhantomData<*const E>,
+/// Counters kept by the driver, which wrap around on overflow.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct XBeeStats {
+    /// Bytes read from the radio, including any between frames.
+    pub bytes_received: u32,
+    /// Frames unpacked and parsed by `XBeeApiReceiver::unpack_and_parse_buffer`.
+    pub frames_parsed: u32,
+    /// Frames `XBeeApiReceiver::unpack_and_parse_buffer` found with a bad checksum.
+    pub checksum_failures: u32,
+    /// Bytes `XBeeApiReceiver` discarded hunting for a start delimiter.
+    pub resync_bytes: u32,
+    /// Number of frames dropped or held back because they did not fit in the RX queue.
+    pub rx_overflows: u32,
+    /// Frames queued by `XBeeApiSender`.
+    pub tx_frames_queued: u32,
+    /// Sends `XBeeApiSender` rejected because the TX queue was full.
+    pub tx_queue_full: u32,
 }
 
-impl<'a, E> XBeeApiReceiver<'a, E> {
-    pub fn queue_empty(&self) -> bool {
-        self.rx_queue.is_empty()
-    }
+/// Counters updated by `XBeeApiSender`.
+#[derive(Copy, Clone, Debug, Default)]
+struct TxCounters {
+    frames_queued: u32,
+    queue_full: u32,
+}
 
-    pub fn queue_full(&self) -> bool {
-        self.rx_queue.is_full()
+/// Counters updated by `XBeeApiReceiver`, some of them while parsing through a shared reference.
+#[derive(Debug, Default)]
+struct RxCounters {
+    frames_parsed: Cell<u32>,
+    checksum_failures: Cell<u32>,
+    resync_bytes: Cell<u32>,
+}
+
+fn count(counter: &Cell<u32>, amount: usize) {
+    counter.set(counter.get().wrapping_add(amount as u32));
+}
+
+/// Removes the first frame anywhere in `rx_queue` that `f` picks out, returning what `f` made of
+/// it and where in the queue the frame was.
+fn take_frame<F, R>(rx_queue: &mut RxQueue, counters: &RxCounters, mut f: F) -> Option<(R, usize)>
+where
+    F: FnMut(&ApiData<'_>) -> Option<R>,
+{
+    let mut offset = 0;
+    let (ret, len) = loop {
+        // Whole frames never wrap around the end of the queue's storage
+        let bytes = rx_queue.slice_from(offset)?;
+        let len = api_frame::frame_len_from_header(bytes)?;
+        let frame = bytes.get(..len)?;
+
+        if let Some(data) = api_frame::unpack_frame(frame, false, false)
+            .ok()
+            .and_then(|(data, _)| ApiData::parse(data).ok())
+        {
+            if let Some(ret) = f(&data) {
+                break (ret, len);
+            }
+        }
+        offset += len;
+    };
+
+    rx_queue.remove(offset, len);
+    count(&counters.frames_parsed, 1);
+    Some((ret, offset))
+}
+
+/// Reads the answer to `DB`, which gives the RSSI as a magnitude in -dBm.
+fn parse_rssi<E>(response: &AtResponse) -> Result<i16, AtError<E>> {
+    AtResponseData(response.data())
+        .as_u8()
+        .map(api_frame::rssi_to_dbm)
+        .ok_or(AtError::InvalidResponse)
+}
+
+fn parse_supply_voltage<E>(response: &AtResponse) -> Result<u16, AtError<E>> {
+    AtResponseData(response.data())
+        .as_u16()
+        .and_then(|raw| api_frame::supply_voltage_mv(raw, SUPPLY_VREF_MV))
+        .ok_or(AtError::InvalidResponse)
+}
+
+/// Receive side framing shared by the drivers.
+///
+/// Received bytes are unescaped before being queued, so the receive queue always holds frames in
+/// the unescaped format whatever the radio's `FrameFormat`.
+#[derive(Copy, Clone, Debug)]
+struct RxFramer {
+    state: RxFrameState,
+    format: FrameFormat,
+    /// An escape byte was received and the next byte must be unescaped.
+    escape: bool,
+    /// Number of bytes of the frame in progress that have been queued.
+    partial: usize,
+    overflow: RxOverflowPolicy,
+    /// The frame in progress did not fit in the queue and the rest of it is being dropped.
+    dropping: bool,
+    /// Overflows not yet taken with `take_overflows`.
+    overflows: u32,
+}
+
+impl RxFramer {
+    fn new(format: FrameFormat) -> RxFramer {
+        RxFramer {
+            state: RxFrameState::Between,
+            format,
+            escape: false,
+            partial: 0,
+            overflow: RxOverflowPolicy::Fail,
+            dropping: false,
+            overflows: 0,
+        }
     }
 
-    pub fn unpack_and_parse_buffer<'d>(&'d self) -> Result<ApiData<'d>, XBeeApiError> {
-        let ret = match api_frame::unpack_frame(self.rx_queue.as_slice(), false, false) {
-            Ok((frame, _rem)) => ApiData::parse(frame).map_err(|err| XBeeApiError::Parse(err)),
-            Err(err) => Err(XBeeApiError::Unpack(err)),
+    /// Queues a received byte if it belongs to a frame, returning whether it was queued.
+    ///
+    /// In escaped mode a start delimiter can only ever begin a frame, so one received partway
+    /// through a frame drops the cut short frame from the queue and starts a new one.
+    ///
+    /// A frame that does not fit in the queue is handled according to the overflow policy.
+    fn receive(&mut self, queue: &mut RxQueue, byte: u8) -> bool {
+        let byte = if !self.format.is_escaped() {
+            byte
+        } else if byte == api_frame::START {
+            if self.state != RxFrameState::Between {
+                let len = queue.len().saturating_sub(self.partial);
+                queue.truncate(len);
+                self.state = RxFrameState::Between;
+                self.partial = 0;
+            }
+            self.escape = false;
+            byte
+        } else if self.escape {
+            self.escape = false;
+            byte ^ 0x20
+        } else if byte == api_frame::ESCAPE && self.state != RxFrameState::Between {
+            self.escape = true;
+            return false;
+        } else {
+            byte
         };
 
-        ret
-    }
+        if !self.state.accept(byte) {
+            return false;
+        }
 
-    pub fn remove_until_packet(&mut self) -> Result<usize, ()> {
-        self.rx_queue.remove_until_start()
+        if !self.dropping && queue.is_full() && !self.make_room(queue) {
+            self.drop_partial(queue);
+        }
+        if self.dropping {
+            self.dropping = self.state != RxFrameState::Between;
+            self.partial = 0;
+            return false;
+        }
+
+        // Cannot fail, there is room for the byte
+        let _ = queue.push(byte);
+        self.partial = match self.state {
+            RxFrameState::Between => 0,
+            _ => self.partial + 1,
+        };
+        if self.state == RxFrameState::Between && !queue.is_contiguous() {
+            // The frame just completed wrapped around the end of the queue's storage, and
+            // frames are parsed in place
+            queue.make_contiguous();
+        }
+        true
     }
 
-    pub fn remove_until_next_packet(&mut self) -> Result<usize, ()> {
-        if let Some(_) = self.rx_queue.pop_at(0) {
-            self.remove_until_packet().map(|len| len + 1)
-        } else {
-            Ok(0)
+    /// Drops the oldest whole frame from a full queue if the policy allows it, returning
+    /// whether room was made.
+    fn make_room(&mut self, queue: &mut RxQueue) -> bool {
+        if self.overflow != RxOverflowPolicy::DropOldestFrame || queue.len() <= self.partial {
+            return false;
         }
+
+        queue.remove_frame();
+        self.overflows = self.overflows.wrapping_add(1);
+        true
     }
 
-    pub fn as_slice(&self) -> &[u8] {
-        self.rx_queue.as_slice()
+    /// Drops the frame in progress from the queue, along with the rest of it as it is received.
+    fn drop_partial(&mut self, queue: &mut RxQueue) {
+        // Only whole frames are ever left in the queue
+        let len = queue.len().saturating_sub(self.partial);
+        queue.truncate(len);
+        self.partial = 0;
+        self.overflows = self.overflows.wrapping_add(1);
+        self.dropping = true;
+    }
+
+    /// Whether the frame in progress takes up the whole queue, so can never be completed.
+    fn fills(&self, queue: &RxQueue) -> bool {
+        queue.is_full() && self.state != RxFrameState::Between && self.partial >= queue.len()
+    }
+
+    fn bytes_expected(&self) -> usize {
+        self.state.bytes_expected()
+    }
+
+    /// Whether reading has to stop while the queue is full.
+    fn blocks_when_full(&self) -> bool {
+        self.overflow == RxOverflowPolicy::Fail
+    }
+
+    /// Returns the number of overflows since the last call.
+    fn take_overflows(&mut self) -> u32 {
+        core::mem::replace(&mut self.overflows, 0)
+    }
+
+    /// Forgets any frame in progress.
+    fn reset(&mut self) {
+        self.state = RxFrameState::Between;
+        self.escape = false;
+        self.partial = 0;
+        self.dropping = false;
+    }
+}
+
+/// API mode driver, generic over the link to the radio.
+///
+/// Frames are queued by an `XBeeApiSender` and received frames are read through an
+/// `XBeeApiReceiver`, while the driver moves bytes between the queues and the transport.
+// TODO: xbee reset pin
+pub struct XBeeApi<T, Q = ArrayDeque<[u8; 512]>> {
+    transport: T,
+
+    tx_queue: Q,
+    // TODO: make generic and allow passing in buffers
+    rx_queue: RxQueue,
+
+    rx_frame: RxFramer,
+    read_any: bool,
+    /// Reading is stopped by a full RX queue while the radio has more to send.
+    rx_refused: bool,
+    asleep: bool,
+    /// Frame ID `at_query` used last.
+    frame_id: u8,
+    tx_retry: TxRetry,
+    // The sender and receiver keep their own counters so they can be used at the same time
+    stats: XBeeStats,
+    tx_counters: TxCounters,
+    rx_counters: RxCounters,
+}
+
+/// API mode driver for an XBee on SPI.
+pub type XBeeApiSpi<S, C, A> = XBeeApi<SpiTransport<S, C, A>>;
+
+/// API mode driver for an XBee on SPI, exchanging bytes in blocking transfers rather than one at a
+/// time.
+pub type XBeeApiSpiTransfer<S, C, A> = XBeeApi<SpiTransferTransport<S, C, A>>;
+
+/// API mode driver for an XBee on SPI, queueing frames to send in a `heapless::Deque` of `N`
+/// bytes rather than an `ArrayDeque`.
+#[cfg(feature = "heapless")]
+pub type XBeeApiSpiHeapless<S, C, A, const N: usize> =
+    XBeeApi<SpiTransport<S, C, A>, heapless::Deque<u8, N>>;
+
+/// API mode driver for an XBee on a UART.
+pub type XBeeApiUart<U> = XBeeApi<SerialTransport<U>>;
+
+/// API mode driver for an XBee on a UART with RTS/CTS hardware flow control. See `FlowControl`.
+pub type XBeeApiUartFlowControl<U, CTS, RTS> = XBeeApi<FlowControlTransport<U, CTS, RTS>>;
+
+impl<'a, 'b, E, U, D> XBeeTransparent<Borrowed<'a, U>, Borrowed<'b, D>>
+where
+    U: Read<u8, Error = E> + BlockingWrite<u8, Error = E>,
+    D: DelayMs<u16>,
+{
+    pub fn new(
+        uart: &'a mut U,
+        delay: &'b mut D,
+        cmd_char: u8,
+        guard_time: u16,
+    ) -> XBeeTransparent<Borrowed<'a, U>, Borrowed<'b, D>> {
+        XBeeTransparent::take(Borrowed(uart), Borrowed(delay), cmd_char, guard_time)
+    }
+
+    /// Like `new`, with the command character and guard time the radio ships with,
+    /// `DEFAULT_CMD_CHAR` and `DEFAULT_GUARD_TIME`.
+    pub fn default_timing(
+        uart: &'a mut U,
+        delay: &'b mut D,
+    ) -> XBeeTransparent<Borrowed<'a, U>, Borrowed<'b, D>> {
+        XBeeTransparent::new(uart, delay, DEFAULT_CMD_CHAR, DEFAULT_GUARD_TIME)
+    }
+
+    /// Like `free`, but hands back the borrowed UART and delay provider themselves, such as to
+    /// send data directly once configuration in command mode is done.
+    pub fn release(self) -> (&'a mut U, &'b mut D) {
+        (self.serial.0, self.timer.0)
     }
 }
 
-impl<'a, E> Drop for XBeeApiReceiver<'a, E> {
-    fn drop(&mut self) {}
+impl<E, U, D> XBeeTransparent<U, D>
+where
+    U: Read<u8, Error = E> + BlockingWrite<u8, Error = E>,
+    D: DelayMs<u16>,
+{
+    /// Like `new`, but takes ownership of the UART and delay provider.
+    pub fn take(uart: U, delay: D, cmd_char: u8, guard_time: u16) -> XBeeTransparent<U, D> {
+        XBeeTransparent {
+            serial: uart,
+            timer: delay,
+            cmd_char,
+            guard_time,
+            response_timeout_ms: DEFAULT_RESPONSE_TIMEOUT_MS,
+            in_command_mode: false,
+            packetization_ms: packetization_time_ms(
+                DEFAULT_PACKETIZATION_TIMEOUT,
+                BaudRate::Baud9600,
+            ),
+        }
+    }
+
+    /// Releases the UART and delay provider.
+    pub fn free(self) -> (U, D) {
+        (self.serial, self.timer)
+    }
+
+    /// Sets the character sent to enter command mode, which should match `CC` on the radio.
+    pub fn set_cmd_char(&mut self, cmd_char: u8) {
+        self.cmd_char = cmd_char;
+    }
+
+    pub fn cmd_char(&self) -> u8 {
+        self.cmd_char
+    }
+
+    /// Sets the guard time waited around the command characters, which should match `GT` on the
+    /// radio. Times outside what `GT` accepts are clamped to `MIN_GUARD_TIME` or
+    /// `MAX_GUARD_TIME`.
+    pub fn set_guard_time(&mut self, ms: u16) {
+        self.guard_time = ms.clamp(MIN_GUARD_TIME, MAX_GUARD_TIME);
+    }
+
+    pub fn guard_time(&self) -> u16 {
+        self.guard_time
+    }
+
+    /// Sets how long to wait for the radio to answer, in milliseconds, which defaults to
+    /// `DEFAULT_RESPONSE_TIMEOUT_MS`. When entering command mode this is on top of the guard
+    /// time. The time is counted in 1 ms delays, so time the radio spends sending the answer is
+    /// not included.
+    pub fn set_response_timeout(&mut self, ms: u16) {
+        self.response_timeout_ms = ms;
+    }
+
+    pub fn response_timeout(&self) -> u16 {
+        self.response_timeout_ms
+    }
+
+    /// Sends the command sequence and waits for the radio to answer `OK\r`, for up to the guard
+    /// time and the response timeout.
+    ///
+    /// Anything received before the answer, such as transparent data the radio was still
+    /// sending, is dropped.
+    pub fn enter_command_mode(&mut self) -> Result<(), CommandModeError<E>> {
+        const OK: &[u8] = b"OK\r";
+
+        // wait for guard time
+        self.timer.delay_ms(self.guard_time);
+        // send command character x3
+        self.serial
+            .bwrite_all(&[self.cmd_char; 3])
+            .map_err(CommandModeError::Serial)?;
+        // the guard time only starts once the characters have actually left the UART
+        self.serial.bflush().map_err(CommandModeError::Serial)?;
+
+        // the radio only answers once the guard time after the characters has passed
+        let timeout_ms = self.guard_time.saturating_add(self.response_timeout_ms);
+        let mut matched = 0;
+        for elapsed in 0..=timeout_ms {
+            loop {
+                match self.serial.read() {
+                    Ok(byte) if byte == OK[matched] => {
+                        matched += 1;
+                        if matched == OK.len() {
+                            self.in_command_mode = true;
+                            return Ok(());
+                        }
+                    }
+                    // Not the answer after all, though it may be where the answer starts
+                    Ok(byte) => matched = if byte == OK[0] { 1 } else { 0 },
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(err)) => return Err(CommandModeError::Serial(err)),
+                }
+            }
+            if elapsed < timeout_ms {
+                self.timer.delay_ms(1);
+            }
+        }
+        Err(CommandModeError::Timeout)
+    }
+
+    /// Leaves command mode (`CN`) so data is sent on again, rather than waiting for the radio's
+    /// command mode timeout (`CT`) to run out.
+    pub fn exit_command_mode(&mut self) -> Result<(), CommandError<E>> {
+        match self.send_command([b'C', b'N'], None)?.as_slice() {
+            b"OK" => {
+                self.in_command_mode = false;
+                Ok(())
+            }
+            _ => Err(CommandError::InvalidResponse),
+        }
+    }
+
+    /// Whether the radio was last put in command mode by this driver and has not been taken out
+    /// of it since. The radio also leaves command mode by itself once its command mode timeout
+    /// (`CT`) runs out, which is not tracked.
+    pub fn in_command_mode(&self) -> bool {
+        self.in_command_mode
+    }
+
+    /// Enters command mode, runs `f` and leaves command mode again, even if `f` gives back an
+    /// error of its own.
+    ///
+    /// Fails without running `f` if command mode could not be entered. If it could not be left,
+    /// that error is returned in place of what `f` gave back.
+    pub fn command_session<F, R>(&mut self, f: F) -> Result<R, CommandError<E>>
+    where
+        F: FnOnce(&mut CommandSession<'_, U, D>) -> R,
+    {
+        self.enter_command_mode()?;
+        let ret = f(&mut CommandSession { xbee: self });
+        self.exit_command_mode()?;
+        Ok(ret)
+    }
+
+    /// Blocks until everything written has been sent by the UART.
+    ///
+    /// Nothing is buffered by the driver itself, so this only waits on the UART.
+    pub fn flush(&mut self) -> Result<(), E> {
+        self.serial.bflush()
+    }
+
+    /// Sets the packetization timeout (`RO`) `write_packet` waits out, in character times at
+    /// `baud`, which should match `RO` and `BD` on the radio. Defaults to
+    /// `DEFAULT_PACKETIZATION_TIMEOUT` at 9600 baud.
+    pub fn set_packetization_timeout(&mut self, ro: u8, baud: BaudRate) {
+        self.packetization_ms = packetization_time_ms(ro, baud);
+    }
+
+    /// How long `write_packet` waits after the data has been sent, in milliseconds.
+    pub fn packetization_time(&self) -> u16 {
+        self.packetization_ms
+    }
+
+    /// Writes `data` and waits until it has been sent by the UART and the packetization timeout
+    /// has passed, so the radio sends it on in one RF packet rather than joining it with whatever
+    /// is written next. `data` is still split over several packets if it is longer than the
+    /// radio sends in one.
+    pub fn write_packet(&mut self, data: &[u8]) -> Result<(), E> {
+        self.serial.bwrite_all(data)?;
+        self.serial.bflush()?;
+        self.timer.delay_ms(self.packetization_ms);
+        Ok(())
+    }
+
+    /// Sets the transmit power level (`PL`). The radio must be in command mode, see
+    /// `enter_command_mode`.
+    pub fn set_power_level(&mut self, level: PowerLevel) -> Result<(), CommandError<E>> {
+        self.command_set([b'P', b'L'], level as u8)
+    }
+
+    /// Reads the transmit power level (`PL`). The radio must be in command mode.
+    pub fn power_level(&mut self) -> Result<PowerLevel, CommandError<E>> {
+        let pl = self.command_query([b'P', b'L'])?;
+        PowerLevel::try_from(pl).map_err(|_| CommandError::InvalidResponse)
+    }
+
+    /// Turns boost mode (`PM`) on or off. The radio must be in command mode.
+    pub fn set_boost_mode(&mut self, boost: bool) -> Result<(), CommandError<E>> {
+        self.command_set([b'P', b'M'], boost as u8)
+    }
+
+    /// Reads whether boost mode (`PM`) is on. The radio must be in command mode.
+    pub fn boost_mode(&mut self) -> Result<bool, CommandError<E>> {
+        match self.command_query([b'P', b'M'])? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CommandError::InvalidResponse),
+        }
+    }
+
+    /// Sets where transparent data is sent (`DH` and `DL`), entering and leaving command mode
+    /// around it unless the radio is already in command mode.
+    ///
+    /// A short address is written as `DH` 0 with the address in `DL`, which the radio takes as
+    /// 16 bit addressing. The setting is not written to flash; `commit_to_flash` does that.
+    pub fn set_destination(&mut self, addr: Addr) -> Result<(), CommandError<E>> {
+        let (dh, dl) = match addr {
+            Addr::Broadcast => (0, 0xFFFF),
+            Addr::Short(short) => (0, short as u32),
+            Addr::Long(long) => ((long >> 32) as u32, long as u32),
+        };
+        self.with_command_mode(|xbee| {
+            xbee.command_set_param(params::DestAddrHigh(dh))?;
+            xbee.command_set_param(params::DestAddrLow(dl))
+        })
+    }
+
+    /// Reads where transparent data is sent (`DH` and `DL`), entering and leaving command mode
+    /// around it unless the radio is already in command mode.
+    ///
+    /// `DH` 0 with `DL` below 0xFFFF is the 16 bit addressing `set_destination` writes for a
+    /// short address, so it is given back as one.
+    pub fn destination(&mut self) -> Result<Addr, CommandError<E>> {
+        let (dh, dl) = self.with_command_mode(|xbee| {
+            Ok((
+                xbee.command_query_u32(params::DestAddrHigh::AT_CMD)?,
+                xbee.command_query_u32(params::DestAddrLow::AT_CMD)?,
+            ))
+        })?;
+        Ok(match (dh, dl) {
+            (0, 0xFFFF) => Addr::Broadcast,
+            (0, short) if short < 0xFFFF => Addr::Short(short as u16),
+            _ => Addr::Long((dh as u64) << 32 | dl as u64),
+        })
+    }
+
+    /// Sets the AES encryption key (`KY`) and turns encryption (`EE`) on, or turns it off when
+    /// `key` is `None`, entering and leaving command mode around it unless the radio is already
+    /// in command mode.
+    ///
+    /// As with `XBeeApi::set_encryption`, the key is sent before encryption is turned on, is left
+    /// as it is when turning encryption off, and cannot be read back. The settings are not
+    /// written to flash; `commit_to_flash` does that.
+    pub fn set_encryption(&mut self, key: Option<&[u8; 16]>) -> Result<(), CommandError<E>> {
+        self.with_command_mode(|xbee| {
+            if let Some(key) = key {
+                xbee.command_set_bytes([b'K', b'Y'], key)?;
+            }
+            xbee.command_set([b'E', b'E'], key.is_some() as u8)
+        })
+    }
+
+    /// Changes the radio's serial interface rate (`BD`), also writing it to flash (`WR`) if
+    /// `persist` is set, entering command mode first unless the radio is already in it.
+    ///
+    /// The radio only switches rates once it leaves command mode, so this always ends by
+    /// leaving it (`CN`), and every answer up to and including the one to `CN` arrives at the
+    /// old rate. Once `Ok` is returned the radio is at the new rate, and the host UART has to be
+    /// switched to `PendingBaudChange::baud` before anything else is sent or read.
+    ///
+    /// If `BD` is refused command mode is left with the rate unchanged. If `WR` fails command
+    /// mode is still left, and `BaudChangeError::NotPersisted` gives back the rate the radio has
+    /// switched to. If leaving command mode fails it is not known which rate the radio is at.
+    pub fn change_baud(
+        &mut self,
+        baud: BaudRate,
+        persist: bool,
+    ) -> Result<PendingBaudChange, BaudChangeError<E>> {
+        if !self.in_command_mode {
+            self.enter_command_mode().map_err(CommandError::from)?;
+        }
+        if let Err(err) = self.command_set([b'B', b'D'], baud as u8) {
+            self.exit_command_mode()?;
+            return Err(err.into());
+        }
+        if persist {
+            if let Err(err) = self.commit_to_flash() {
+                self.exit_command_mode()?;
+                return Err(BaudChangeError::NotPersisted(
+                    PendingBaudChange { baud },
+                    err,
+                ));
+            }
+        }
+        self.exit_command_mode()?;
+        Ok(PendingBaudChange { baud })
+    }
+
+    /// Reads the command character (`CC`) and guard time (`GT`) from the radio and uses them from
+    /// then on, entering and leaving command mode around it unless the radio is already in
+    /// command mode.
+    ///
+    /// Entering command mode needs the right command character, but a guard time longer than the
+    /// radio's works, so a radio with an unknown `GT` can be read by setting the guard time to
+    /// `MAX_GUARD_TIME` first.
+    pub fn read_timing_from_module(&mut self) -> Result<(), CommandError<E>> {
+        let (cmd_char, guard_time) = self.with_command_mode(|xbee| {
+            let cmd_char = xbee.command_query([b'C', b'C'])?;
+            let guard_time = xbee.command_query_u32([b'G', b'T'])?;
+            Ok((cmd_char, guard_time))
+        })?;
+        let guard_time = u16::try_from(guard_time).map_err(|_| CommandError::InvalidResponse)?;
+        self.set_cmd_char(cmd_char);
+        self.set_guard_time(guard_time);
+        Ok(())
+    }
+
+    /// Puts the radio in API mode without escaping (`AP` 1) and writes that to flash (`WR`), then
+    /// checks the radio answers an API frame asking for its firmware version (`VR`).
+    ///
+    /// A radio already in API mode does not answer the command sequence, so if command mode cannot
+    /// be entered the check is still made, and passing it counts as success. Afterwards the radio
+    /// should be driven by an `XBeeApiUart`, see `into_api`.
+    pub fn switch_to_api_mode(&mut self) -> Result<(), ApiModeError<E>> {
+        let entered = if self.in_command_mode {
+            true
+        } else {
+            match self.enter_command_mode() {
+                Ok(()) => true,
+                Err(CommandModeError::Serial(err)) => return Err(ApiModeError::Serial(err)),
+                Err(_) => false,
+            }
+        };
+        if entered {
+            self.command_set([b'A', b'P'], FrameFormat::Unescaped.ap_value())?;
+            self.commit_to_flash()?;
+            self.exit_command_mode()?;
+        }
+        self.check_api_mode()
+    }
+
+    /// Sends a `VR` AT command frame and waits up to the response timeout for the answer.
+    fn check_api_mode(&mut self) -> Result<(), ApiModeError<E>> {
+        const FRAME_ID: u8 = 0x01;
+        const VR_FRAME: [u8; 8] = [0x7E, 0x00, 0x04, 0x08, FRAME_ID, b'V', b'R', 0x4E];
+
+        self.serial
+            .bwrite_all(&VR_FRAME)
+            .map_err(ApiModeError::Serial)?;
+        self.serial.bflush().map_err(ApiModeError::Serial)?;
+
+        let mut frame: ArrayVec<[u8; 32]> = ArrayVec::new();
+        let mut received = false;
+        for elapsed in 0..=self.response_timeout_ms {
+            loop {
+                let byte = match self.serial.read() {
+                    Ok(byte) => byte,
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(err)) => return Err(ApiModeError::Serial(err)),
+                };
+                received = true;
+                if frame.is_empty() && byte != api_frame::START {
+                    continue;
+                }
+                // Nothing the radio answers with is this long
+                if frame.try_push(byte).is_err() {
+                    return Err(ApiModeError::BaudMismatch);
+                }
+                if api_frame::frame_len_from_header(&frame) != Some(frame.len()) {
+                    continue;
+                }
+                if let Ok((data, _)) = api_frame::unpack_frame(&frame, false, false) {
+                    if let Ok(ApiData::AtCommandResponse {
+                        frame_id: FRAME_ID,
+                        at_cmd: [b'V', b'R'],
+                        ..
+                    }) = ApiData::parse(data)
+                    {
+                        return Ok(());
+                    }
+                }
+                frame.clear();
+            }
+            if elapsed < self.response_timeout_ms {
+                self.timer.delay_ms(1);
+            }
+        }
+        if received {
+            Err(ApiModeError::BaudMismatch)
+        } else {
+            Err(ApiModeError::NoResponse)
+        }
+    }
+
+    /// Writes the current settings to flash (`WR`) so they are kept over a power cycle. The
+    /// radio must be in command mode.
+    pub fn commit_to_flash(&mut self) -> Result<(), CommandError<E>> {
+        match self.send_command([b'W', b'R'], None)?.as_slice() {
+            b"OK" => Ok(()),
+            _ => Err(CommandError::InvalidResponse),
+        }
+    }
+
+    /// Resets the radio (`FR`), entering command mode first unless the radio is already in it.
+    ///
+    /// The radio answers before resetting, and comes back in transparent mode, so command mode
+    /// is not left with `CN`. If `FR` is refused, command mode is left if it was entered for it.
+    pub fn soft_reset(&mut self) -> Result<(), CommandError<E>> {
+        let entered = !self.in_command_mode;
+        if entered {
+            self.enter_command_mode()?;
+        }
+        let err = match self.send_command([b'F', b'R'], None) {
+            Ok(ref response) if response.as_slice() == b"OK" => {
+                self.in_command_mode = false;
+                return Ok(());
+            }
+            Ok(_) => CommandError::InvalidResponse,
+            Err(err) => err,
+        };
+        if entered {
+            self.exit_command_mode()?;
+        }
+        Err(err)
+    }
+
+    /// Resets the network layer (`NR`), entering and leaving command mode around it unless the
+    /// radio is already in command mode.
+    pub fn network_reset(&mut self) -> Result<(), CommandError<E>> {
+        self.with_command_mode(
+            |xbee| match xbee.send_command([b'N', b'R'], None)?.as_slice() {
+                b"OK" => Ok(()),
+                _ => Err(CommandError::InvalidResponse),
+            },
+        )
+    }
+
+    /// Runs `f` in command mode, only entering and leaving it if the radio is not in command
+    /// mode already. Like `command_session`, command mode is left even if `f` fails.
+    fn with_command_mode<F, R>(&mut self, f: F) -> Result<R, CommandError<E>>
+    where
+        F: FnOnce(&mut Self) -> Result<R, CommandError<E>>,
+    {
+        if self.in_command_mode {
+            return f(self);
+        }
+        self.enter_command_mode()?;
+        let ret = f(self);
+        self.exit_command_mode()?;
+        ret
+    }
+
+    fn command_set(&mut self, at_cmd: [u8; 2], value: u8) -> Result<(), CommandError<E>> {
+        self.command_set_bytes(at_cmd, &[value])
+    }
+
+    fn command_set_param<P: AtParam>(&mut self, param: P) -> Result<(), CommandError<E>> {
+        self.command_set_bytes(P::AT_CMD, param.to_params().as_ref())
+    }
+
+    fn command_set_bytes(&mut self, at_cmd: [u8; 2], param: &[u8]) -> Result<(), CommandError<E>> {
+        match self.send_command(at_cmd, Some(param))?.as_slice() {
+            b"OK" => Ok(()),
+            _ => Err(CommandError::InvalidResponse),
+        }
+    }
+
+    fn command_query(&mut self, at_cmd: [u8; 2]) -> Result<u8, CommandError<E>> {
+        let value = self.command_query_u32(at_cmd)?;
+        u8::try_from(value).map_err(|_| CommandError::InvalidResponse)
+    }
+
+    fn command_query_u32(&mut self, at_cmd: [u8; 2]) -> Result<u32, CommandError<E>> {
+        let response = self.send_command(at_cmd, None)?;
+        if response.is_empty() {
+            return Err(CommandError::InvalidResponse);
+        }
+        // Values are answered in hex without leading zeros
+        let mut value: u32 = 0;
+        for &digit in response.iter() {
+            let nibble = (digit as char)
+                .to_digit(16)
+                .ok_or(CommandError::InvalidResponse)?;
+            value = value.checked_mul(16).ok_or(CommandError::InvalidResponse)? | nibble;
+        }
+        Ok(value)
+    }
+
+    /// Sends `at_cmd`, with `param` written out in hex if there is one, and returns the line the
+    /// radio answers with, such as `OK` or the value asked for. The radio must be in command
+    /// mode.
+    ///
+    /// An `ERROR` answer is returned as `CommandError::Error`.
+    pub fn send_command(
+        &mut self,
+        at_cmd: [u8; 2],
+        param: Option<&[u8]>,
+    ) -> Result<CommandResponse, CommandError<E>> {
+        const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+        let serial = &mut self.serial;
+        let mut write = |bytes: &[u8]| serial.bwrite_all(bytes).map_err(CommandError::Serial);
+        write(b"AT")?;
+        write(&at_cmd)?;
+        for &byte in param.unwrap_or(&[]) {
+            write(&[
+                HEX_DIGITS[(byte >> 4) as usize],
+                HEX_DIGITS[(byte & 0x0F) as usize],
+            ])?;
+        }
+        write(b"\r")?;
+
+        let mut response = ArrayVec::new();
+        let mut overflowed = false;
+        let mut elapsed = 0;
+        loop {
+            match self.serial.read() {
+                Ok(b'\r') => break,
+                Ok(byte) => overflowed |= response.try_push(byte).is_err(),
+                Err(nb::Error::WouldBlock) if elapsed < self.response_timeout_ms => {
+                    self.timer.delay_ms(1);
+                    elapsed += 1;
+                }
+                Err(nb::Error::WouldBlock) => return Err(CommandError::Timeout),
+                Err(nb::Error::Other(err)) => return Err(CommandError::Serial(err)),
+            }
+        }
+        if overflowed {
+            return Err(CommandError::InvalidResponse);
+        }
+        if response.as_slice() == b"ERROR" {
+            return Err(CommandError::Error);
+        }
+        Ok(response)
+    }
+}
+
+/// An answer to a command sent in command mode, without the ending CR.
+pub type CommandResponse = ArrayVec<[u8; MAX_COMMAND_RESPONSE]>;
+
+/// Returned by `XBeeTransparent::change_baud` once the radio has switched to `baud`, to tell the
+/// host UART has to be switched to it too before the radio is talked to again.
+#[must_use]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PendingBaudChange {
+    baud: BaudRate,
+}
+
+impl PendingBaudChange {
+    /// Rate the host UART has to be switched to.
+    pub fn baud(&self) -> BaudRate {
+        self.baud
+    }
+}
+
+/// Access to an `XBeeTransparent` in command mode, for the length of
+/// `XBeeTransparent::command_session`, such as to call `send_command`.
+pub struct CommandSession<'s, U, D> {
+    xbee: &'s mut XBeeTransparent<U, D>,
+}
+
+impl<'s, U, D> Deref for CommandSession<'s, U, D> {
+    type Target = XBeeTransparent<U, D>;
+
+    fn deref(&self) -> &XBeeTransparent<U, D> {
+        self.xbee
+    }
+}
+
+impl<'s, U, D> DerefMut for CommandSession<'s, U, D> {
+    fn deref_mut(&mut self) -> &mut XBeeTransparent<U, D> {
+        self.xbee
+    }
+}
+
+impl<U, D> Read<u8> for XBeeTransparent<U, D>
+where
+    U: Read<u8>,
+{
+    type Error = U::Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.serial.read()
+    }
+}
+
+impl<U, D> Write<u8> for XBeeTransparent<U, D>
+where
+    U: Write<u8>,
+{
+    type Error = U::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.serial.write(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.serial.flush()
+    }
+}
+
+impl<E, U, D> XBeeTransparent<U, D>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+{
+    /// Hands the UART over to an API mode driver, such as once `switch_to_api_mode` has
+    /// succeeded, giving back the delay provider.
+    pub fn into_api(self) -> (XBeeApiUart<U>, D) {
+        (XBeeApiUart::take(self.serial), self.timer)
+    }
+}
+
+impl<U, D> BlockingWrite<u8> for XBeeTransparent<U, D>
+where
+    U: BlockingWrite<u8>,
+{
+    type Error = U::Error;
+
+    fn bwrite_all(&mut self, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.serial.bwrite_all(buffer)
+    }
+
+    fn bflush(&mut self) -> Result<(), Self::Error> {
+        self.serial.bflush()
+    }
+}
+
+/// Time `ro` characters take at `baud` with 8N1 framing, in whole milliseconds rounded up.
+fn packetization_time_ms(ro: u8, baud: BaudRate) -> u16 {
+    let bits = ro as u32 * 10;
+    (bits * 1000).div_ceil(baud.bps()) as u16
+}
+
+impl<T> XBeeApi<T>
+where
+    T: XBeeTransport,
+{
+    pub fn with_transport(transport: T) -> XBeeApi<T> {
+        XBeeApi::with_tx_queue(transport, ArrayDeque::new())
+    }
+}
+
+impl<T, Q> XBeeApi<T, Q>
+where
+    T: XBeeTransport,
+    Q: TxQueue,
+{
+    /// Like `with_transport`, but queues frames to send in `tx_queue`, such as a
+    /// `heapless::Deque`. Anything already in it is sent as queued frames.
+    pub fn with_tx_queue(transport: T, tx_queue: Q) -> XBeeApi<T, Q> {
+        XBeeApi {
+            transport,
+            tx_queue,
+            rx_queue: RxQueue::new(),
+            rx_frame: RxFramer::new(FrameFormat::Unescaped),
+            read_any: false,
+            rx_refused: false,
+            asleep: false,
+            frame_id: 0,
+            tx_retry: TxRetry::default(),
+            stats: XBeeStats::default(),
+            tx_counters: TxCounters::default(),
+            rx_counters: RxCounters::default(),
+        }
+    }
+
+    pub fn tx_queue_empty(&self) -> bool {
+        self.tx_queue.is_empty()
+    }
+
+    pub fn tx_queue_full(&self) -> bool {
+        self.tx_queue.is_full()
+    }
+
+    pub fn rx_queue_empty(&self) -> bool {
+        self.rx_queue.is_empty()
+    }
+
+    pub fn rx_queue_full(&self) -> bool {
+        self.rx_queue.is_full()
+    }
+
+    /// Sets the frame format the radio has been configured for with `AP`, which defaults to
+    /// `FrameFormat::Unescaped`.
+    ///
+    /// Should be set before any frames are exchanged, as a frame partway through being received
+    /// is forgotten.
+    pub fn set_frame_format(&mut self, format: FrameFormat) {
+        self.rx_frame.format = format;
+        self.rx_frame.reset();
+    }
+
+    /// Queues an `AP` command switching the radio to `format`, then packs and unpacks every
+    /// later frame in it, so the driver never falls out of step with the radio.
+    ///
+    /// Frames already queued are sent in the old format, which the radio still expects until it
+    /// has handled `AP`. Like `set_frame_format`, a frame partway through being received is
+    /// forgotten.
+    pub fn set_api_mode(&mut self, frame_id: u8, format: FrameFormat) -> Result<(), SendError> {
+        {
+            let (mut sender, _) = self.get_sender_receiver();
+            sender.at_command(frame_id, [b'A', b'P'], &[format.ap_value()])?;
+        }
+        self.set_frame_format(format);
+        Ok(())
+    }
+
+    /// Sets what happens to received data that does not fit in the RX queue, which defaults to
+    /// `RxOverflowPolicy::Fail`.
+    pub fn set_rx_overflow_policy(&mut self, policy: RxOverflowPolicy) {
+        self.rx_frame.overflow = policy;
+    }
+
+    /// Resends TX requests up to `count` times when their TX status is
+    /// `TxStatus::CcaFailure` or `TxStatus::NoAck`. 0, the default, turns resending off.
+    ///
+    /// Requests sent with a frame ID other than 0 through `XBeeApiSender::send_data` and the like
+    /// are kept until their TX status arrives, for up to `TX_RETRY_SLOTS` requests at a time.
+    /// The TX status of a request that is resent is dropped, so only the status of its last
+    /// attempt is received.
+    pub fn set_tx_retry(&mut self, count: u8) {
+        self.tx_retry.set_retries(count);
+    }
+
+    /// Marks the radio as asleep, such as after `sleep::XBeeSleepControl::request_sleep`, or as
+    /// awake once `wait_awake` returns.
+    ///
+    /// Queued frames are held back while the radio is asleep. Bytes it sent before sleeping can
+    /// still be read.
+    pub fn set_asleep(&mut self, asleep: bool) {
+        self.asleep = asleep;
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    pub fn stats(&self) -> XBeeStats {
+        XBeeStats {
+            frames_parsed: self.rx_counters.frames_parsed.get(),
+            checksum_failures: self.rx_counters.checksum_failures.get(),
+            resync_bytes: self.rx_counters.resync_bytes.get(),
+            tx_frames_queued: self.tx_counters.frames_queued,
+            tx_queue_full: self.tx_counters.queue_full,
+            ..self.stats
+        }
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats = XBeeStats::default();
+        self.tx_counters = TxCounters::default();
+        self.rx_counters = RxCounters::default();
+    }
+
+    /// Drops every whole frame in the RX queue. A frame still being received is kept.
+    pub fn clear_rx_queue(&mut self) {
+        // The receiver may have dropped part of the frame in progress when resyncing
+        let len = self.rx_queue.len().saturating_sub(self.rx_frame.partial);
+        self.rx_queue.remove_exact(len);
+    }
+
+    /// Sends the TX queue and reads whatever the radio has, blocking until both are done.
+    ///
+    /// Returns whether any bytes were received, or `FlushError::Asleep` without exchanging
+    /// anything if frames are queued while the radio is asleep. Only `Io`, `Rx` and `Asleep`
+    /// errors are returned. Over SPI, which direction failed is told apart by `SpiIoError`.
+    pub fn transmit_and_receive(&mut self) -> Result<bool, FlushError<T::Error>> {
+        if self.asleep && !self.tx_queue.is_empty() {
+            return Err(FlushError::Asleep);
+        }
+        block!(self.poll())
+    }
+
+    /// Like `transmit_and_receive`, but gives up with `FlushError::Timeout` if the exchange has
+    /// not finished within about `timeout_ms` milliseconds, such as when a stuck ATTN line makes
+    /// the radio look like it never runs out of bytes to send.
+    ///
+    /// Between delays at most `POLL_STEPS_PER_MS` exchanges are made with the transport. Bytes
+    /// already received are kept, and calling again resumes the exchange.
+    pub fn transmit_and_receive_timeout<D>(
+        &mut self,
+        timer: &mut D,
+        timeout_ms: u16,
+    ) -> Result<bool, FlushError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        if self.asleep && !self.tx_queue.is_empty() {
+            return Err(FlushError::Asleep);
+        }
+        for elapsed in 0..=timeout_ms {
+            match self.poll_steps(POLL_STEPS_PER_MS) {
+                Ok(read_any) => return Ok(read_any),
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+            if elapsed < timeout_ms {
+                timer.delay_ms(1);
+            }
+        }
+        self.finish_nb().map_err(FlushError::Io)?;
+        Err(FlushError::Timeout)
+    }
+
+    /// Calls `transmit_and_receive` until the TX queue is empty, at most `max_iterations` times.
+    ///
+    /// Bytes received meanwhile are kept in the RX queue as usual. An exchange only stops before
+    /// the TX queue is empty if the RX queue fills up, so a `Timeout` or `Rx` error means
+    /// received frames need to be taken out before the rest can be sent.
+    pub fn flush_tx(&mut self, max_iterations: usize) -> Result<(), FlushError<T::Error>> {
+        for _ in 0..max_iterations {
+            if self.tx_queue.is_empty() {
+                return Ok(());
+            }
+            self.transmit_and_receive()?;
+        }
+
+        if self.tx_queue.is_empty() {
+            Ok(())
+        } else {
+            Err(FlushError::Timeout)
+        }
+    }
+
+    /// Writes the whole TX queue in one burst, without the reads `transmit_and_receive` makes
+    /// while the radio has data, so frames queued together go out with CS asserted once.
+    ///
+    /// Returns the number of bytes written, or `FlushError::Asleep` without writing anything if
+    /// frames are queued while the radio is asleep. Bytes clocked in while writing are moved to
+    /// the RX queue as the overflow policy allows, and the rest are left for the next read. With
+    /// `RxOverflowPolicy::Fail`, writing stops with `FlushError::Rx` once neither the RX queue
+    /// nor the transport has room for them, leaving the rest of the TX queue to send later.
+    pub fn flush_tx_burst(&mut self) -> Result<usize, FlushError<T::Error>> {
+        if self.asleep && !self.tx_queue.is_empty() {
+            return Err(FlushError::Asleep);
+        }
+
+        let mut written = 0;
+        while !self.tx_queue.is_empty() {
+            match self.write_some() {
+                // The transport has no room left for what it clocks in
+                Ok(0) => self.drain_stalled()?,
+                Ok(len) => written += len,
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(FlushError::Io(err)),
+            }
+        }
+        block!(self.transport.finish()).map_err(FlushError::Io)?;
+        Ok(written)
+    }
+
+    /// Packs and sends the data portion of a frame, such as a `TxRequestIter`, straight to the
+    /// radio without copying it into the TX queue first.
+    ///
+    /// Bytes received while sending are kept in the RX queue as the overflow policy allows. The
+    /// frame cannot be paused partway through, so with `RxOverflowPolicy::Fail` sending stops
+    /// with `FlushError::Rx` once neither the RX queue nor the transport has room for them, and
+    /// the cut short frame has to be sent again.
+    ///
+    /// If the TX queue is not empty, the frame is queued behind it instead. `FlushError::Timeout`
+    /// is never returned, and `FlushError::Asleep` is returned while the radio is asleep.
+    pub fn transmit_frame_now<I>(&mut self, data: I) -> Result<(), FlushError<T::Error>>
+    where
+        I: ExactSizeIterator<Item = u8>,
+    {
+        if !self.tx_queue.is_empty() {
+            let (mut sender, _) = self.get_sender_receiver();
+            return sender.queue_frame(data).map_err(FlushError::Send);
+        }
+        if self.asleep {
+            return Err(FlushError::Asleep);
+        }
+
+        let mut frame = FramePacker::new(data, self.rx_frame.format.is_escaped(), false)
+            .map_err(|err| FlushError::Send(SendError::Pack(err)))?;
+        let mut buf = [0; READ_CHUNK_SIZE];
+        loop {
+            let mut len = 0;
+            for (dst, src) in buf.iter_mut().zip(frame.by_ref()) {
+                *dst = src;
+                len += 1;
+            }
+            if len == 0 {
+                break;
+            }
+
+            let mut sent = 0;
+            while sent < len {
+                match self.transport.write(&buf[sent..len]) {
+                    Ok(0) => self.drain_stalled()?,
+                    Ok(written) => {
+                        sent += written;
+                        self.drain_buffered().map_err(FlushError::Io)?;
+                    }
+                    Err(nb::Error::WouldBlock) => {
+                        self.drain_buffered().map_err(FlushError::Io)?;
+                    }
+                    Err(nb::Error::Other(err)) => return Err(FlushError::Io(err)),
+                }
+            }
+        }
+
+        // Read whatever else the radio has and finish with the transport
+        self.transmit_and_receive()?;
+        Ok(())
+    }
+
+    /// Queues a TX request and sends it straight away, along with any frames queued before it.
+    ///
+    /// The frame is sent with a frame ID of 0, so the radio does not answer with a TX status.
+    /// Returns `FlushError::Timeout` if an exchange could not send anything, such as when the RX
+    /// queue is full, leaving the frame queued.
+    pub fn send_frame_blocking(
+        &mut self,
+        addr: Addr,
+        data: &[u8],
+    ) -> Result<(), FlushError<T::Error>> {
+        {
+            let (mut sender, _) = self.get_sender_receiver();
+            sender
+                .send_data(0x00, addr, data)
+                .map_err(FlushError::Send)?;
+        }
+        // The frame is last in the TX queue, so it has been sent once the queue is empty
+        while !self.tx_queue.is_empty() {
+            let queued = self.tx_queue.len();
+            self.transmit_and_receive()?;
+            if self.tx_queue.len() == queued {
+                return Err(FlushError::Timeout);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a local AT command and waits for the answer, checking every millisecond for up to
+    /// `timeout_ms` milliseconds.
+    ///
+    /// Frame IDs are counted up from 1 by the driver, skipping 0 which the radio never answers,
+    /// so the answer can be told apart from ones to commands sent with the same ID through an
+    /// `XBeeApiSender`. Other frames received meanwhile are left in the RX queue. Returns
+    /// `FlushError::Timeout` if there was no answer in time.
+    pub fn at_query<C, D>(
+        &mut self,
+        at_cmd: C,
+        params: &[u8],
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<AtResponse, FlushError<T::Error>>
+    where
+        C: Into<[u8; 2]>,
+        D: DelayMs<u16>,
+    {
+        let at_cmd = at_cmd.into();
+        let frame_id = self.queue_at_query(at_cmd, params, false)?;
+        self.wait_at_response(frame_id, at_cmd, delay, timeout_ms)
+            .map(|(response, _)| response)
+    }
+
+    /// Queues an AT command with the next frame ID, returning the ID.
+    ///
+    /// With `queue_param` the command is sent as an AT queue parameter frame, so the new value
+    /// is only applied by a later `AC` or other AT command frame.
+    fn queue_at_query(
+        &mut self,
+        at_cmd: [u8; 2],
+        params: &[u8],
+        queue_param: bool,
+    ) -> Result<u8, FlushError<T::Error>> {
+        let frame_id = self.next_frame_id();
+        let (mut sender, _) = self.get_sender_receiver();
+        if queue_param {
+            sender.at_queue_param(frame_id, at_cmd, params)
+        } else {
+            sender.at_command(frame_id, at_cmd, params)
+        }
+        .map_err(FlushError::Send)?;
+        Ok(frame_id)
+    }
+
+    /// Waits for the answer to an AT command, also returning how many milliseconds were waited.
+    fn wait_at_response<D>(
+        &mut self,
+        frame_id: u8,
+        at_cmd: [u8; 2],
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(AtResponse, u16), FlushError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        for elapsed in 0..=timeout_ms {
+            self.transmit_and_receive()?;
+            if let Some(response) = self.take_at_response(frame_id, at_cmd) {
+                return Ok((response, elapsed));
+            }
+            if elapsed < timeout_ms {
+                delay.delay_ms(1);
+            }
+        }
+        Err(FlushError::Timeout)
+    }
+
+    /// Polls `AI` every `poll_interval_ms` milliseconds until the radio has joined a PAN, for up
+    /// to `timeout_ms` milliseconds in all.
+    ///
+    /// Only `AssociationStatus::Scanning` is waited out. Any other failure is returned straight
+    /// away as `AssociationError::Failed`.
+    pub fn wait_for_association<D>(
+        &mut self,
+        delay: &mut D,
+        poll_interval_ms: u16,
+        timeout_ms: u16,
+    ) -> Result<(), AssociationError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let mut elapsed = 0;
+        loop {
+            let frame_id = self
+                .queue_at_query([b'A', b'I'], &[], false)
+                .map_err(AtError::Flush)?;
+            let (response, waited) =
+                match self.wait_at_response(frame_id, [b'A', b'I'], delay, timeout_ms - elapsed) {
+                    Ok(answer) => answer,
+                    Err(FlushError::Timeout) => return Err(AssociationError::Timeout),
+                    Err(err) => return Err(AtError::Flush(err).into()),
+                };
+            // An answer already waiting takes no time, so count at least 1 ms per poll or a zero
+            // poll interval would never time out
+            elapsed += waited.max(1);
+
+            if response.status != AtCommandStatus::Ok {
+                return Err(AtError::Status(response.status).into());
+            }
+            let val = AtResponseData(response.data())
+                .as_u8()
+                .ok_or(AtError::InvalidResponse)?;
+            match AssociationStatus::from(val) {
+                AssociationStatus::Success => return Ok(()),
+                AssociationStatus::Scanning => {}
+                status => return Err(AssociationError::Failed(status)),
+            }
+
+            if elapsed >= timeout_ms {
+                return Err(AssociationError::Timeout);
+            }
+            let wait = poll_interval_ms.min(timeout_ms - elapsed);
+            delay.delay_ms(wait);
+            elapsed += wait;
+        }
+    }
+
+    /// Discovers the nodes in range with `ND`, handing each one found to `f`, and returns how
+    /// many were found.
+    ///
+    /// Nodes answer at random within the discovery time `NT`, which is read first. Answers are
+    /// waited for until the empty answer that ends the discovery, or until `NT` has passed plus
+    /// `per_node_timeout_ms` milliseconds for the last answer to arrive. `per_node_timeout_ms`
+    /// is also how long the answer to `NT` is waited for.
+    pub fn discover_nodes<D, F>(
+        &mut self,
+        delay: &mut D,
+        per_node_timeout_ms: u16,
+        mut f: F,
+    ) -> Result<usize, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+        F: FnMut(NodeDiscoveryResult<'_>),
+    {
+        let nt = self.at_exchange([b'N', b'T'], &[], false, delay, per_node_timeout_ms)?;
+        let nt = AtResponseData(nt.data())
+            .as_u8()
+            .ok_or(AtError::InvalidResponse)?;
+        // NT is in units of 100 ms
+        let timeout_ms = nt as u32 * 100 + per_node_timeout_ms as u32;
+
+        let frame_id = self.queue_at_query([b'N', b'D'], &[], false)?;
+        let mut found = 0;
+        for elapsed in 0..=timeout_ms {
+            self.transmit_and_receive()?;
+            // Each answer gives whether it was a node rather than the end of the discovery
+            while let Some((answer, _)) = self.take_frame(|data| match *data {
+                ApiData::AtCommandResponse {
+                    frame_id: id,
+                    at_cmd: [b'N', b'D'],
+                    status,
+                    data,
+                } if id == frame_id => Some(if status != AtCommandStatus::Ok {
+                    Err(AtError::Status(status))
+                } else if data.is_empty() {
+                    Ok(false)
+                } else {
+                    match NodeDiscoveryResult::parse(data) {
+                        Some(node) => {
+                            f(node);
+                            Ok(true)
+                        }
+                        None => Err(AtError::InvalidResponse),
+                    }
+                }),
+                _ => None,
+            }) {
+                if !answer? {
+                    return Ok(found);
+                }
+                found += 1;
+            }
+            if elapsed < timeout_ms {
+                delay.delay_ms(1);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Reads the firmware version (`VR`), waiting up to `timeout_ms` milliseconds for the answer.
+    pub fn firmware_version<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.at_query_u16([b'V', b'R'], delay, timeout_ms)
+    }
+
+    /// Reads the hardware version (`HV`), waiting up to `timeout_ms` milliseconds for the answer.
+    /// See `api_frame::is_s2c_hardware`.
+    pub fn hardware_version<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.at_query_u16([b'H', b'V'], delay, timeout_ms)
+    }
+
+    /// Checks that the radio runs the 802.15.4 firmware, waiting up to `timeout_ms` milliseconds
+    /// for the answer. Worth calling once at startup, as other firmware answers with frames that
+    /// fail to parse.
+    pub fn verify_firmware<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), FirmwareMismatch<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let firmware_version = self.firmware_version(delay, timeout_ms)?;
+        if api_frame::is_802_15_4_firmware(firmware_version) {
+            Ok(())
+        } else {
+            Err(FirmwareMismatch::NotIeee802154 { firmware_version })
+        }
+    }
+
+    /// Reads the radio's 64-bit serial number, the address other nodes reach it at with
+    /// `Addr::Long`.
+    ///
+    /// `SH` and `SL` are queried in turn, each waiting for up to `timeout_ms` milliseconds.
+    pub fn read_serial_number<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u64, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let high = self.at_query_u32([b'S', b'H'], delay, timeout_ms)?;
+        let low = self.at_query_u32([b'S', b'L'], delay, timeout_ms)?;
+        Ok((high as u64) << 32 | low as u64)
+    }
+
+    /// Sets the AES encryption key (`KY`) and turns encryption (`EE`) on, or turns it off when
+    /// `key` is `None`, waiting up to `timeout_ms` milliseconds for each answer.
+    ///
+    /// The key is sent before encryption is turned on, so the radio never encrypts with an old
+    /// key, and is left as it is when turning encryption off. The radio never gives the key back,
+    /// so there is no way to read it.
+    pub fn set_encryption<D>(
+        &mut self,
+        key: Option<&[u8; 16]>,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        if let Some(key) = key {
+            self.at_exchange([b'K', b'Y'], key, false, delay, timeout_ms)?;
+        }
+        let enable = api_frame::at_param_u8(key.is_some() as u8);
+        self.at_exchange([b'E', b'E'], &enable, false, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Sets the transmit power level (`PL`), waiting up to `timeout_ms` milliseconds for the
+    /// answer.
+    pub fn set_power_level<D>(
+        &mut self,
+        level: PowerLevel,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let pl = api_frame::at_param_u8(level as u8);
+        self.at_exchange([b'P', b'L'], &pl, false, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Reads the transmit power level (`PL`), waiting up to `timeout_ms` milliseconds for the
+    /// answer.
+    pub fn power_level<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<PowerLevel, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange([b'P', b'L'], &[], false, delay, timeout_ms)?;
+        AtResponseData(response.data())
+            .as_u8()
+            .and_then(|pl| PowerLevel::try_from(pl).ok())
+            .ok_or(AtError::InvalidResponse)
+    }
+
+    /// Turns boost mode (`PM`) on or off, waiting up to `timeout_ms` milliseconds for the answer.
+    /// See `PowerLevel::dbm`.
+    pub fn set_boost_mode<D>(
+        &mut self,
+        boost: bool,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let pm = api_frame::at_param_u8(boost as u8);
+        self.at_exchange([b'P', b'M'], &pm, false, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Reads whether boost mode (`PM`) is on, waiting up to `timeout_ms` milliseconds for the
+    /// answer.
+    pub fn boost_mode<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<bool, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange([b'P', b'M'], &[], false, delay, timeout_ms)?;
+        match AtResponseData(response.data()).as_u8() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(AtError::InvalidResponse),
+        }
+    }
+
+    /// Asks the radio at `addr` to sample its IO lines straight away (`IS`), waiting up to
+    /// `timeout_ms` milliseconds for the sample.
+    pub fn force_sample<D>(
+        &mut self,
+        addr: Addr,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<IoSample, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.remote_at_exchange(addr, [b'I', b'S'], &[], delay, timeout_ms)?;
+        IoSample::parse(response.data()).ok_or(AtError::InvalidResponse)
+    }
+
+    /// Signal strength of the last packet received (`DB`) in dBm, waiting up to `timeout_ms`
+    /// milliseconds for the answer.
+    pub fn last_rssi<D>(&mut self, delay: &mut D, timeout_ms: u16) -> Result<i16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange([b'D', b'B'], &[], false, delay, timeout_ms)?;
+        parse_rssi(&response)
+    }
+
+    /// Like `last_rssi`, but asks the radio at `addr`, giving the signal strength of the last
+    /// packet it received.
+    pub fn remote_last_rssi<D>(
+        &mut self,
+        addr: Addr,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<i16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.remote_at_exchange(addr, [b'D', b'B'], &[], delay, timeout_ms)?;
+        parse_rssi(&response)
+    }
+
+    /// Reads the energy on each of the scan channels (`SC`) with an energy scan (`ED`), waiting
+    /// up to `timeout_ms` milliseconds for each answer. The scan itself takes as long as the
+    /// scan duration (`SD`) sets.
+    pub fn energy_scan<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<EnergyScan, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let channel_mask = self.at_exchange([b'S', b'C'], &[], false, delay, timeout_ms)?;
+        let channel_mask = AtResponseData(channel_mask.data())
+            .as_u16()
+            .ok_or(AtError::InvalidResponse)?;
+        let response = self.at_exchange([b'E', b'D'], &[], false, delay, timeout_ms)?;
+        EnergyScan::parse(channel_mask, response.data()).ok_or(AtError::InvalidResponse)
+    }
+
+    /// Supply voltage of the radio (`%V`) in millivolts, waiting up to `timeout_ms`
+    /// milliseconds for the answer.
+    pub fn supply_voltage_mv<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange([b'%', b'V'], &[], false, delay, timeout_ms)?;
+        parse_supply_voltage(&response)
+    }
+
+    /// Like `supply_voltage_mv`, but asks the radio at `addr`.
+    pub fn remote_supply_voltage_mv<D>(
+        &mut self,
+        addr: Addr,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.remote_at_exchange(addr, [b'%', b'V'], &[], delay, timeout_ms)?;
+        parse_supply_voltage(&response)
+    }
+
+    /// Drives an IO line of the radio at `addr` low or high, waiting up to `timeout_ms`
+    /// milliseconds for the answer.
+    pub fn set_remote_pin<D>(
+        &mut self,
+        addr: Addr,
+        pin: DioPin,
+        level: PinLevel,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.configure_remote_pin(addr, pin, level.into(), delay, timeout_ms)
+    }
+
+    /// Sets what an IO line of the radio at `addr` is used for, waiting up to `timeout_ms`
+    /// milliseconds for the answer. The change is applied straight away.
+    pub fn configure_remote_pin<D>(
+        &mut self,
+        addr: Addr,
+        pin: DioPin,
+        function: PinFunction,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let param = api_frame::at_param_u8(function as u8);
+        self.remote_at_exchange(addr, pin.at_command(), &param, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Sets up periodic IO sampling on this radio, or on the radio at `addr`, waiting up to
+    /// `timeout_ms` milliseconds for each answer.
+    ///
+    /// Samples are sent every `rate_ms` milliseconds (`IR`), 0 turning periodic sampling off, and
+    /// whenever a digital line in `change_mask` changes (`IC`). `samples_per_packet` sets are
+    /// gathered before each IO packet is sent (`IT`). `IT` and `IC` are set before `IR`, so
+    /// sampling only starts once everything else is in place.
+    ///
+    /// Returns `AtError::OutOfRange` without sending anything if `samples_per_packet` is 0 or
+    /// over `MAX_IO_SAMPLES_PER_PACKET`, or if `change_mask` has analog lines or D8 in it.
+    pub fn configure_io_sampling<D>(
+        &mut self,
+        addr: Option<Addr>,
+        rate_ms: u16,
+        change_mask: ChannelIndicator,
+        samples_per_packet: u8,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        if samples_per_packet == 0 || samples_per_packet > MAX_IO_SAMPLES_PER_PACKET {
+            return Err(AtError::OutOfRange);
+        }
+        // Only DIO0 to DIO7 can be watched for changes
+        if change_mask.bits() > 0xFF {
+            return Err(AtError::OutOfRange);
+        }
+
+        let mut set = |xbee: &mut Self, at_cmd: [u8; 2], params: &[u8]| match addr {
+            Some(addr) => xbee.remote_at_exchange(addr, at_cmd, params, delay, timeout_ms),
+            None => xbee.at_exchange(at_cmd, params, false, delay, timeout_ms),
+        };
+        let it = api_frame::at_param_u8(samples_per_packet);
+        set(self, [b'I', b'T'], &it)?;
+        let ic = api_frame::at_param_u8(change_mask.bits() as u8);
+        set(self, [b'I', b'C'], &ic)?;
+        set(self, [b'I', b'R'], &api_frame::at_param_u16(rate_ms))?;
+        Ok(())
+    }
+
+    /// Writes the current settings to flash (`WR`) so they survive a reset, waiting up to
+    /// `timeout_ms` milliseconds for the answer.
+    pub fn commit_to_flash<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.at_exchange([b'W', b'R'], &[], false, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Resets the network layer (`NR`), waiting up to `timeout_ms` milliseconds for the answer.
+    pub fn network_reset<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.at_exchange([b'N', b'R'], &[], false, delay, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Resets the radio (`FR`) and waits for it to come back, returning the modem status it
+    /// reports once it has, such as `ModemStatus::HardwareReset`.
+    ///
+    /// Both queues are cleared first, dropping frames waiting to be sent, and frames received
+    /// before the answer to `FR` are dropped as well, so an old modem status is never taken for
+    /// the one sent after the reset. Waits up to `timeout_ms` milliseconds in all.
+    pub fn soft_reset<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<ModemStatus, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        self.tx_queue.clear();
+        self.clear_rx_queue();
+        let frame_id = self.queue_at_query([b'F', b'R'], &[], false)?;
+
+        let mut answered = false;
+        for elapsed in 0..=timeout_ms {
+            self.transmit_and_receive()?;
+            if !answered {
+                let response = self.take_frame(|data| match *data {
+                    ApiData::AtCommandResponse {
+                        frame_id: id,
+                        at_cmd: [b'F', b'R'],
+                        status,
+                        ..
+                    } if id == frame_id => Some(status),
+                    _ => None,
+                });
+                if let Some((status, offset)) = response {
+                    if status != AtCommandStatus::Ok {
+                        return Err(AtError::Status(status));
+                    }
+                    // Everything before the answer was received before the reset
+                    self.rx_queue.remove(0, offset);
+                    answered = true;
+                }
+            }
+            if answered {
+                let status = self.take_frame(|data| match *data {
+                    ApiData::ModemStatus { status } => Some(status),
+                    _ => None,
+                });
+                if let Some((status, _)) = status {
+                    return Ok(status);
+                }
+            }
+            if elapsed < timeout_ms {
+                delay.delay_ms(1);
+            }
+        }
+        Err(FlushError::Timeout.into())
+    }
+
+    fn at_query_u16<D>(
+        &mut self,
+        at_cmd: [u8; 2],
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u16, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange(at_cmd, &[], false, delay, timeout_ms)?;
+        AtResponseData(response.data())
+            .as_u16()
+            .ok_or(AtError::InvalidResponse)
+    }
+
+    fn at_query_u32<D>(
+        &mut self,
+        at_cmd: [u8; 2],
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<u32, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let response = self.at_exchange(at_cmd, &[], false, delay, timeout_ms)?;
+        AtResponseData(response.data())
+            .as_u32()
+            .ok_or(AtError::InvalidResponse)
+    }
+
+    /// Like `at_query`, but any status other than `AtCommandStatus::Ok` is returned as an error.
+    ///
+    /// With `queue_param` the command is sent as an AT queue parameter frame.
+    pub(crate) fn at_exchange<D>(
+        &mut self,
+        at_cmd: [u8; 2],
+        params: &[u8],
+        queue_param: bool,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<AtResponse, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let frame_id = self.queue_at_query(at_cmd, params, queue_param)?;
+        let (response, _) = self.wait_at_response(frame_id, at_cmd, delay, timeout_ms)?;
+        if response.status != AtCommandStatus::Ok {
+            return Err(AtError::Status(response.status));
+        }
+        Ok(response)
+    }
+
+    /// Sends an AT command to the radio at `addr`, applying any change straight away, and waits
+    /// for its answer. Any status other than `AtCommandStatus::Ok` is returned as an error.
+    fn remote_at_exchange<D>(
+        &mut self,
+        addr: Addr,
+        at_cmd: [u8; 2],
+        params: &[u8],
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<AtResponse, AtError<T::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        let frame_id = self.next_frame_id();
+        let (mut sender, _) = self.get_sender_receiver();
+        sender
+            .remote_at_command(frame_id, addr, RemoteAtOptions::default(), at_cmd, params)
+            .map_err(FlushError::Send)?;
+        let (response, _) = self.wait_at_response(frame_id, at_cmd, delay, timeout_ms)?;
+        if response.status != AtCommandStatus::Ok {
+            return Err(AtError::Status(response.status));
+        }
+        Ok(response)
+    }
+
+    /// Counts frame IDs up from 1, skipping 0 when wrapping around since the radio never answers
+    /// a request with a frame ID of 0.
+    fn next_frame_id(&mut self) -> u8 {
+        self.frame_id = self.frame_id.checked_add(1).unwrap_or(1);
+        self.frame_id
+    }
+
+    /// Removes the answer to a local or remote AT command from wherever it is in the RX queue.
+    fn take_at_response(&mut self, frame_id: u8, at_cmd: [u8; 2]) -> Option<AtResponse> {
+        self.take_frame(|data| match *data {
+            ApiData::AtCommandResponse {
+                frame_id: id,
+                at_cmd: cmd,
+                status,
+                data,
+            }
+            | ApiData::RemoteAtCommandResponse {
+                frame_id: id,
+                at_cmd: cmd,
+                status,
+                data,
+                ..
+            } if id == frame_id && cmd == at_cmd => {
+                let data = data.iter().cloned().take(MAX_AT_RESPONSE).collect();
+                Some(AtResponse { status, data })
+            }
+            _ => None,
+        })
+        .map(|(response, _)| response)
+    }
+
+    fn take_frame<F, R>(&mut self, f: F) -> Option<(R, usize)>
+    where
+        F: FnMut(&ApiData<'_>) -> Option<R>,
+    {
+        take_frame(&mut self.rx_queue, &self.rx_counters, f)
+    }
+
+    /// Resends the kept TX request the frame just received at the end of the RX queue says
+    /// failed, taking that TX status out of the queue.
+    ///
+    /// Only statuses are looked at as they arrive, so one left in the queue is never matched
+    /// again with a later request that reuses its frame ID.
+    fn retry_failed(&mut self, frame_len: usize) {
+        let offset = self.rx_queue.len().saturating_sub(frame_len);
+        let status = self
+            .rx_queue
+            .slice_from(offset)
+            .and_then(|frame| api_frame::unpack_frame(frame, false, false).ok())
+            .and_then(|(data, _)| ApiData::parse(data).ok());
+        let (frame_id, tx_request) = match status {
+            Some(ApiData::TxStatus { frame_id, status }) => {
+                match self.tx_retry.on_status(frame_id, status) {
+                    Some(tx_request) => (frame_id, tx_request),
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        self.rx_queue.remove(offset, frame_len);
+        count(&self.rx_counters.frames_parsed, 1);
+
+        let (mut sender, _) = self.get_sender_receiver();
+        if sender.queue_frame(tx_request.iter().cloned()).is_err() {
+            // No room to send it again, so its status is lost
+            self.tx_retry.release(frame_id);
+        }
+    }
+
+    /// Non-blocking version of `transmit_and_receive`.
+    ///
+    /// Exchanges bytes until the TX queue is empty and the radio has nothing more to send, but
+    /// returns `WouldBlock` as soon as the transport is not ready. Calling `poll` again resumes
+    /// the exchange where it left off, so no byte is lost or sent twice.
+    ///
+    /// Bytes received between frames that are not a start delimiter are line noise or idle fill
+    /// and are discarded rather than queued.
+    ///
+    /// Returns whether any bytes were received during the exchange. Only `Io` and `Rx` errors
+    /// are returned.
+    pub fn poll(&mut self) -> nb::Result<bool, FlushError<T::Error>> {
+        self.poll_steps(usize::MAX)
+    }
+
+    /// Like `poll`, but also returns `WouldBlock` after `max_steps` exchanges with the transport.
+    fn poll_steps(&mut self, max_steps: usize) -> nb::Result<bool, FlushError<T::Error>> {
+        let ret = self.poll_internal(max_steps);
+        if let Err(nb::Error::WouldBlock) = ret {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let finish_ret = self.transport.finish();
+        // An exchange error takes precedence over failing to finish
+        ret.and(finish_ret).map_err(|err| match err {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(err) => nb::Error::Other(FlushError::Io(err)),
+        })?;
+        if self.rx_refused {
+            return Err(nb::Error::Other(FlushError::Rx(XBeeApiError::RxOverflow)));
+        }
+        Ok(core::mem::replace(&mut self.read_any, false))
+    }
+
+    /// Moves bytes the radio has already sent into the RX queue.
+    ///
+    /// Never blocks; stops once the transport has no more bytes or the RX queue is full.
+    ///
+    /// Returns the number of bytes read from the transport.
+    pub fn poll_read(&mut self) -> Result<usize, T::Error> {
+        let mut read = 0;
+        while !self.rx_blocked()? && self.transport.rx_pending()? {
+            match self.read_some() {
+                Ok(0) | Err(nb::Error::WouldBlock) => break,
+                Ok(len) => read += len,
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+        self.finish_nb()?;
+        Ok(read)
+    }
+
+    /// Writes bytes from the TX queue for as long as the transport accepts them without blocking.
+    ///
+    /// Returns the number of bytes written.
+    pub fn poll_write(&mut self) -> Result<usize, T::Error> {
+        let mut written = 0;
+        while !self.tx_queue.is_empty() {
+            match self.write_some() {
+                Ok(0) | Err(nb::Error::WouldBlock) => break,
+                Ok(len) => written += len,
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+        self.finish_nb()?;
+        Ok(written)
+    }
+
+    pub fn get_sender_receiver<'d>(&'d mut self) -> (XBeeApiSender<'d, Q>, XBeeApiReceiver<'d>) {
+        let sender = XBeeApiSender {
+            tx_queue: &mut self.tx_queue,
+            format: self.rx_frame.format,
+            counters: &mut self.tx_counters,
+            retry: &mut self.tx_retry,
+        };
+        let receiver = XBeeApiReceiver {
+            rx_queue: &mut self.rx_queue,
+            format: self.rx_frame.format,
+            partial: self.rx_frame.partial,
+            counters: &mut self.rx_counters,
+        };
+
+        (sender, receiver)
+    }
+
+    fn poll_internal(&mut self, max_steps: usize) -> nb::Result<(), T::Error> {
+        for _ in 0..max_steps {
+            self.transport
+                .rx_queue_level(self.rx_queue.len())
+                .map_err(nb::Error::Other)?;
+            if self.rx_blocked().map_err(nb::Error::Other)? {
+                return Ok(());
+            }
+
+            self.write_some()?;
+            if self.transport.rx_pending().map_err(nb::Error::Other)? {
+                self.read_some()?;
+                self.transport
+                    .rx_queue_level(self.rx_queue.len())
+                    .map_err(nb::Error::Other)?;
+            } else if self.tx_queue.is_empty() || self.asleep {
+                return Ok(());
+            }
+        }
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Whether reading has to stop until the receiver makes room in the RX queue, which counts as
+    /// an overflow if the radio has more to send.
+    fn rx_blocked(&mut self) -> Result<bool, T::Error> {
+        if !self.rx_frame.blocks_when_full() || !self.rx_queue.is_full() {
+            self.rx_refused = false;
+            return Ok(false);
+        }
+
+        if self.rx_frame.fills(&self.rx_queue) {
+            // Waiting would never make room for the rest of the frame, so drop it instead
+            self.rx_frame.drop_partial(&mut self.rx_queue);
+            let overflows = self.rx_frame.take_overflows();
+            self.stats.rx_overflows = self.stats.rx_overflows.wrapping_add(overflows);
+            return Ok(false);
+        }
+
+        // Counted once for each frame held back, however many times it is polled for
+        let pending = self.transport.rx_pending()?;
+        if pending && !self.rx_refused {
+            self.stats.rx_overflows = self.stats.rx_overflows.wrapping_add(1);
+        }
+        self.rx_refused = pending;
+        Ok(true)
+    }
+
+    /// Finishes with the transport, leaving it busy if it would block.
+    fn finish_nb(&mut self) -> Result<(), T::Error> {
+        match self.transport.finish() {
+            Ok(()) | Err(nb::Error::WouldBlock) => Ok(()),
+            Err(nb::Error::Other(err)) => Err(err),
+        }
+    }
+
+    fn write_some(&mut self) -> nb::Result<usize, T::Error> {
+        if self.asleep {
+            return Ok(0);
+        }
+        let written = match self.tx_queue.as_slices().0 {
+            [] => return Ok(0),
+            front => self.transport.write(front)?,
+        };
+        // Only remove bytes once they have actually been written
+        for _ in 0..written {
+            self.tx_queue.pop_front();
+        }
+        Ok(written)
+    }
+
+    fn read_some(&mut self) -> nb::Result<usize, T::Error> {
+        // No more than the frame in progress still needs is asked for, so a read never runs
+        // into the idle line after the frame
+        let mut len = self.rx_frame.bytes_expected().min(READ_CHUNK_SIZE);
+        if self.rx_frame.blocks_when_full() {
+            len = len.min(self.rx_queue.capacity() - self.rx_queue.len());
+        }
+        let mut buf = [0; READ_CHUNK_SIZE];
+        let read = self.transport.read(&mut buf[..len])?;
+        self.receive(&buf[..read]);
+        Ok(read)
+    }
+
+    /// Moves bytes the transport has already received into the RX queue. With
+    /// `RxOverflowPolicy::Fail` only as many are moved as fit, and the rest are left with the
+    /// transport.
+    ///
+    /// Returns the number of bytes moved.
+    fn drain_buffered(&mut self) -> Result<usize, T::Error> {
+        let mut len = READ_CHUNK_SIZE;
+        if self.rx_frame.blocks_when_full() {
+            len = len.min(self.rx_queue.capacity() - self.rx_queue.len());
+        }
+        let mut buf = [0; READ_CHUNK_SIZE];
+        let read = self.transport.read_buffered(&mut buf[..len])?;
+        self.receive(&buf[..read]);
+        Ok(read)
+    }
+
+    /// Drains the transport once it has no room left for what it clocks in while writing,
+    /// returning `XBeeApiError::RxOverflow` if the RX queue has no room for it either.
+    fn drain_stalled(&mut self) -> Result<(), FlushError<T::Error>> {
+        if self.drain_buffered().map_err(FlushError::Io)? > 0 {
+            return Ok(());
+        }
+        self.finish_nb().map_err(FlushError::Io)?;
+        Err(FlushError::Rx(XBeeApiError::RxOverflow))
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        for &rx in bytes {
+            let partial = self.rx_frame.partial;
+            if self.rx_frame.receive(&mut self.rx_queue, rx) {
+                self.read_any = true;
+                // A frame was just completed at the end of the queue
+                if self.rx_frame.state == RxFrameState::Between && !self.tx_retry.is_empty() {
+                    self.retry_failed(partial + 1);
+                }
+            }
+        }
+        let overflows = self.rx_frame.take_overflows();
+        self.stats.rx_overflows = self.stats.rx_overflows.wrapping_add(overflows);
+        self.stats.bytes_received = self.stats.bytes_received.wrapping_add(bytes.len() as u32);
+    }
+}
+
+impl<'a, 'b, 'c, E, CE, AE, S, C, A> XBeeApiSpi<Borrowed<'a, S>, Borrowed<'b, C>, Borrowed<'c, A>>
+where
+    S: FullDuplex<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    pub fn new(
+        spi: &'a mut S,
+        cs: Option<&'b mut C>,
+        attn: &'c mut A,
+    ) -> XBeeApiSpi<Borrowed<'a, S>, Borrowed<'b, C>, Borrowed<'c, A>> {
+        XBeeApiSpi::take(Borrowed(spi), cs.map(Borrowed), Borrowed(attn))
+    }
+}
+
+impl<'a, 'c, E, AE, S, A> XBeeApiSpi<Borrowed<'a, S>, NoPin, Borrowed<'c, A>>
+where
+    S: FullDuplex<u8, Error = E>,
+    A: InputPin<Error = AE>,
+{
+    /// Like `new`, for a radio with CS tied low as the only device on its SPI bus.
+    pub fn new_no_cs(
+        spi: &'a mut S,
+        attn: &'c mut A,
+    ) -> XBeeApiSpi<Borrowed<'a, S>, NoPin, Borrowed<'c, A>> {
+        XBeeApiSpi::take(Borrowed(spi), None, Borrowed(attn))
+    }
+}
+
+impl<E, CE, AE, S, C, A, Q> XBeeApi<SpiTransport<S, C, A>, Q>
+where
+    S: FullDuplex<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+    Q: TxQueue,
+{
+    /// Like `new`, but takes ownership of the peripherals.
+    pub fn take(spi: S, cs: Option<C>, attn: A) -> XBeeApi<SpiTransport<S, C, A>, Q>
+    where
+        Q: Default,
+    {
+        XBeeApi::with_tx_queue(SpiTransport::new(spi, cs, attn), Q::default())
+    }
+
+    /// Releases the SPI peripheral, CS pin and ATTN pin.
+    pub fn free(self) -> (S, Option<C>, A) {
+        self.transport.free()
+    }
+
+    /// Whether the radio is asserting ATTN, meaning it has data waiting for the host.
+    ///
+    /// A sleeping radio cannot assert ATTN, so for an end device this also signals it is awake.
+    pub fn data_waiting(&self) -> Result<bool, AE> {
+        self.transport.attn_asserted()
+    }
+
+    /// Like `data_waiting`, for polling before `transmit_and_receive`. A failure reading ATTN is
+    /// taken as data waiting, so that `transmit_and_receive` is called and reports it. Use
+    /// `data_waiting` to get the error itself.
+    pub fn data_available(&self) -> bool {
+        self.data_waiting().unwrap_or(true)
+    }
+
+    /// Sets which level of ATTN means it is asserted, for boards that invert the line. See
+    /// `AttnPolarity`.
+    pub fn set_attn_polarity(&mut self, polarity: AttnPolarity) {
+        self.transport.set_attn_polarity(polarity);
+    }
+}
+
+impl<'a, 'b, 'c, E, CE, AE, S, C, A>
+    XBeeApiSpiTransfer<Borrowed<'a, S>, Borrowed<'b, C>, Borrowed<'c, A>>
+where
+    S: Transfer<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    pub fn new(
+        spi: &'a mut S,
+        cs: Option<&'b mut C>,
+        attn: &'c mut A,
+    ) -> XBeeApiSpiTransfer<Borrowed<'a, S>, Borrowed<'b, C>, Borrowed<'c, A>> {
+        XBeeApiSpiTransfer::take(Borrowed(spi), cs.map(Borrowed), Borrowed(attn))
+    }
+}
+
+impl<'a, 'c, E, AE, S, A> XBeeApiSpiTransfer<Borrowed<'a, S>, NoPin, Borrowed<'c, A>>
+where
+    S: Transfer<u8, Error = E>,
+    A: InputPin<Error = AE>,
+{
+    /// Like `new`, for a radio with CS tied low as the only device on its SPI bus.
+    pub fn new_no_cs(
+        spi: &'a mut S,
+        attn: &'c mut A,
+    ) -> XBeeApiSpiTransfer<Borrowed<'a, S>, NoPin, Borrowed<'c, A>> {
+        XBeeApiSpiTransfer::take(Borrowed(spi), None, Borrowed(attn))
+    }
+}
+
+impl<E, CE, AE, S, C, A> XBeeApiSpiTransfer<S, C, A>
+where
+    S: Transfer<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    /// Like `new`, but takes ownership of the peripherals.
+    pub fn take(spi: S, cs: Option<C>, attn: A) -> XBeeApiSpiTransfer<S, C, A> {
+        XBeeApi::with_transport(SpiTransferTransport::new(spi, cs, attn))
+    }
+
+    /// Releases the SPI peripheral, CS pin and ATTN pin.
+    pub fn free(self) -> (S, Option<C>, A) {
+        self.transport.free()
+    }
+
+    /// Whether the radio is asserting ATTN, meaning it has data waiting for the host.
+    pub fn data_waiting(&self) -> Result<bool, AE> {
+        self.transport.attn_asserted()
+    }
+
+    /// Like `data_waiting`, for polling before `transmit_and_receive`. A failure reading ATTN is
+    /// taken as data waiting, so that `transmit_and_receive` is called and reports it. Use
+    /// `data_waiting` to get the error itself.
+    pub fn data_available(&self) -> bool {
+        self.data_waiting().unwrap_or(true)
+    }
+
+    /// Sets which level of ATTN means it is asserted, for boards that invert the line. See
+    /// `AttnPolarity`.
+    pub fn set_attn_polarity(&mut self, polarity: AttnPolarity) {
+        self.transport.set_attn_polarity(polarity);
+    }
+
+    /// Sets the most bytes exchanged in one SPI transfer. See
+    /// `SpiTransferTransport::set_chunk_size`.
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.transport.set_chunk_size(size);
+    }
+}
+
+impl<'a, E, U> XBeeApiUart<Borrowed<'a, U>>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+{
+    pub fn new(uart: &'a mut U) -> XBeeApiUart<Borrowed<'a, U>> {
+        XBeeApiUart::take(Borrowed(uart))
+    }
+}
+
+impl<E, U> XBeeApiUart<U>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+{
+    /// Like `new`, but takes ownership of the UART.
+    pub fn take(uart: U) -> XBeeApiUart<U> {
+        XBeeApi::with_transport(SerialTransport::new(uart))
+    }
+
+    /// Releases the UART.
+    pub fn free(self) -> U {
+        self.transport.free()
+    }
+}
+
+impl<E, CE, RE, U, CTS, RTS> XBeeApiUartFlowControl<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    /// Takes ownership of the UART and whichever of the CTS and RTS lines are wired up.
+    pub fn with_flow_control(
+        uart: U,
+        cts: Option<CTS>,
+        rts: Option<RTS>,
+    ) -> XBeeApiUartFlowControl<U, CTS, RTS> {
+        XBeeApi::with_transport(FlowControlTransport::new(uart, cts, rts))
+    }
+
+    /// Releases the UART and the CTS and RTS pins.
+    pub fn free(self) -> (U, Option<CTS>, Option<RTS>) {
+        self.transport.free()
+    }
+
+    /// Sets the RX queue levels RTS is asserted again at or below and deasserted at or above.
+    /// See `FlowControl::set_rts_thresholds`.
+    pub fn set_rts_thresholds(&mut self, low_water: usize, high_water: usize) {
+        self.transport.set_rts_thresholds(low_water, high_water);
+    }
+
+    /// Whether RTS is asserted, letting the radio send.
+    pub fn rts_asserted(&self) -> bool {
+        self.transport.rts_asserted()
+    }
+}
+
+/// Queues frames for `XBeeApi` to send.
+///
+/// A `frame_id` of 0 tells the radio not to answer the request with a TX status or AT command
+/// response, so nothing sent with it can be waited on.
+#[derive(Debug)]
+pub struct XBeeApiSender<'a, Q = ArrayDeque<[u8; 512]>> {
+    tx_queue: &'a mut Q,
+    format: FrameFormat,
+    counters: &'a mut TxCounters,
+    retry: &'a mut TxRetry,
+}
+
+impl<'a, Q> XBeeApiSender<'a, Q>
+where
+    Q: TxQueue,
+{
+    pub fn queue_empty(&self) -> bool {
+        self.tx_queue.is_empty()
+    }
+
+    pub fn queue_full(&self) -> bool {
+        self.tx_queue.is_full()
+    }
+
+    pub fn send_data_raw(&mut self, data: &[u8]) -> Result<(), SendError> {
+        if self.tx_queue.capacity() - self.tx_queue.len() < data.len() {
+            self.counters.queue_full = self.counters.queue_full.wrapping_add(1);
+            return Err(SendError::QueueFull);
+        }
+        for &byte in data {
+            // Cannot fail, the room was checked above
+            let _ = self.tx_queue.push_back(byte);
+        }
+        Ok(())
+    }
+
+    pub fn send_data(&mut self, frame_id: u8, addr: Addr, data: &[u8]) -> Result<(), SendError> {
+        self.send_data_iter(frame_id, addr, TxOptions::empty(), data.iter().cloned())
+    }
+
+    pub fn send_data_no_ack(
+        &mut self,
+        frame_id: u8,
+        addr: Addr,
+        data: &[u8],
+    ) -> Result<(), SendError> {
+        self.send_data_iter(frame_id, addr, TxOptions::DISABLE_ACK, data.iter().cloned())
+    }
+
+    /// Queues a TX request with the payload taken from an iterator, so it does not need to be
+    /// collected into a buffer first.
+    pub fn send_data_iter<I>(
+        &mut self,
+        frame_id: u8,
+        addr: Addr,
+        options: TxOptions,
+        data: I,
+    ) -> Result<(), SendError>
+    where
+        I: ExactSizeIterator<Item = u8>,
+    {
+        if data.len() > MAX_TX_PAYLOAD {
+            return Err(SendError::PayloadTooLarge);
+        }
+
+        let tx_request = TxRequestIter::new(frame_id, addr, options, data);
+        if !self.retry.wants(frame_id) {
+            return self.queue_frame(tx_request);
+        }
+
+        // Kept in case it has to be sent again
+        let tx_request: ArrayVec<[u8; 128]> = tx_request.collect();
+        self.queue_frame(tx_request.iter().cloned())?;
+        self.retry.retain(frame_id, &tx_request);
+        Ok(())
+    }
+
+    /// Broadcasts data to every node in range whatever its PAN ID.
+    ///
+    /// Sending to `Addr::Broadcast` without this only reaches nodes on the same PAN ID, while
+    /// this also sets `TxOptions::PAN_BROADCAST` so the packet is sent to the broadcast PAN ID
+    /// (0xFFFF). Broadcasts are never acknowledged.
+    pub fn broadcast(&mut self, frame_id: u8, data: &[u8]) -> Result<(), SendError> {
+        self.send_data_iter(
+            frame_id,
+            Addr::Broadcast,
+            TxOptions::PAN_BROADCAST,
+            data.iter().cloned(),
+        )
+    }
+
+    pub fn at_command<C>(&mut self, frame_id: u8, at_cmd: C, params: &[u8]) -> Result<(), SendError>
+    where
+        C: Into<[u8; 2]>,
+    {
+        let at_command = AtCommandIter::new(frame_id, at_cmd.into(), params.iter().cloned());
+        self.queue_frame(at_command)
+    }
+
+    pub fn at_queue_param<C>(
+        &mut self,
+        frame_id: u8,
+        at_cmd: C,
+        params: &[u8],
+    ) -> Result<(), SendError>
+    where
+        C: Into<[u8; 2]>,
+    {
+        let at_command =
+            AtCommandIter::queue_param(frame_id, at_cmd.into(), params.iter().cloned());
+        self.queue_frame(at_command)
+    }
+
+    /// Sets the I/O sample rate (`IR`) in milliseconds. A rate of 0 disables periodic sampling.
+    pub fn set_io_sample_rate(&mut self, frame_id: u8, ms: u16) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'I', b'R'], &api_frame::at_param_u16(ms))
+    }
+
+    /// Sets the digital lines monitored for change detection (`IC`).
+    ///
+    /// Only D0 through D7 support change detection; other channels in the mask are ignored.
+    pub fn set_change_detect(
+        &mut self,
+        frame_id: u8,
+        mask: ChannelIndicator,
+    ) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'I', b'C'], &[mask.bits() as u8])
+    }
+
+    /// Sets a typed parameter with its command, such as `params::Channel` with `CH`.
+    pub fn set_param<P: AtParam>(&mut self, frame_id: u8, param: P) -> Result<(), SendError> {
+        self.at_command(frame_id, P::AT_CMD, param.to_params().as_ref())
+    }
+
+    /// Sets the PAN ID (`ID`).
+    pub fn set_pan_id(&mut self, frame_id: u8, pan_id: u16) -> Result<(), SendError> {
+        self.set_param(frame_id, params::PanId(pan_id))
+    }
+
+    /// Sets the operating channel (`CH`).
+    pub fn set_channel(&mut self, frame_id: u8, channel: u8) -> Result<(), SendError> {
+        self.set_param(frame_id, params::Channel(channel))
+    }
+
+    /// Sets the 16-bit source address (`MY`). 0xFFFE disables 16-bit addressing.
+    pub fn set_source_address(&mut self, frame_id: u8, addr: u16) -> Result<(), SendError> {
+        self.set_param(frame_id, params::SourceAddr(addr))
+    }
+
+    /// Saves the configuration to non-volatile memory (`WR`) so it survives a reset.
+    pub fn write_config(&mut self, frame_id: u8) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'W', b'R'], &[])
+    }
+
+    /// Applies queued configuration changes (`AC`).
+    pub fn apply_changes(&mut self, frame_id: u8) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'A', b'C'], &[])
+    }
+
+    /// Saves the configuration (`WR`) and then applies it (`AC`), both with `frame_id`.
+    ///
+    /// The radio answers with a `WR` response followed by an `AC` response, which can be told
+    /// apart with `ApiData::at_response_status`. If queueing `AC` fails, `WR` is still queued.
+    pub fn commit_config(&mut self, frame_id: u8) -> Result<(), SendError> {
+        self.write_config(frame_id)?;
+        self.apply_changes(frame_id)
+    }
+
+    /// Sets the sleep mode (`SM`).
+    pub fn set_sleep_mode(&mut self, frame_id: u8, mode: SleepMode) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'S', b'M'], &api_frame::at_param_u8(mode as u8))
+    }
+
+    /// Sets the node identifier (`NI`), which may be at most `MAX_NODE_IDENTIFIER` bytes long.
+    pub fn set_node_identifier(&mut self, frame_id: u8, name: &str) -> Result<(), SendError> {
+        if name.len() > MAX_NODE_IDENTIFIER {
+            return Err(SendError::ParamTooLong);
+        }
+        self.at_command(frame_id, [b'N', b'I'], name.as_bytes())
+    }
+
+    /// Sets how long a cyclic sleeper sleeps (`SP`), in units of 10 ms.
+    pub fn set_sleep_period(&mut self, frame_id: u8, periods: u16) -> Result<(), SendError> {
+        self.at_command(frame_id, [b'S', b'P'], &api_frame::at_param_u16(periods))
+    }
+
+    /// Queues an AT command for another node. `RemoteAtOptions::default()` applies the change
+    /// straight away.
+    pub fn remote_at_command<C>(
+        &mut self,
+        frame_id: u8,
+        addr: Addr,
+        options: RemoteAtOptions,
+        at_cmd: C,
+        params: &[u8],
+    ) -> Result<(), SendError>
+    where
+        C: Into<[u8; 2]>,
+    {
+        let command = RemoteAtCommandIter::new(
+            frame_id,
+            addr,
+            options,
+            at_cmd.into(),
+            params.iter().cloned(),
+        );
+        self.queue_frame(command)
+    }
+
+    /// Queues a remote AT command driving an IO line of the radio at `addr` low or high, applied
+    /// straight away. See `XBeeApi::set_remote_pin` to wait for the answer.
+    pub fn set_digital_output(
+        &mut self,
+        frame_id: u8,
+        addr: Addr,
+        pin: DioPin,
+        level: PinLevel,
+    ) -> Result<(), SendError> {
+        let function = PinFunction::from(level) as u8;
+        self.remote_at_command(
+            frame_id,
+            addr,
+            RemoteAtOptions::default(),
+            pin.at_command(),
+            &api_frame::at_param_u8(function),
+        )
+    }
+
+    /// Reserves room at the back of the queue for a frame of at least `len` bytes, which is then
+    /// written into the returned slot, such as a frame packed by hand.
+    pub fn reserve(&mut self, len: usize) -> Result<FrameSlot<'_, Q>, SendError> {
+        if self.tx_queue.capacity() - self.tx_queue.len() < len {
+            self.counters.queue_full = self.counters.queue_full.wrapping_add(1);
+            return Err(SendError::QueueFull);
+        }
+
+        Ok(FrameSlot {
+            start: self.tx_queue.len(),
+            tx_queue: self.tx_queue,
+            counters: self.counters,
+            committed: false,
+        })
+    }
+
+    /// Packs a frame into the queue. Nothing is queued if the whole frame does not fit.
+    pub(crate) fn queue_frame<I>(&mut self, data: I) -> Result<(), SendError>
+    where
+        I: ExactSizeIterator<Item = u8>,
+    {
+        // Escaping can only make the frame longer
+        let len = 3 + data.len() + 1;
+        let frame =
+            FramePacker::new(data, self.format.is_escaped(), false).map_err(SendError::Pack)?;
+
+        let mut slot = self.reserve(len)?;
+        slot.extend(frame)?;
+        slot.commit();
+        Ok(())
+    }
+}
+
+/// Frame being written into the TX queue, returned by `XBeeApiSender::reserve`.
+///
+/// The bytes only become part of the queue once `commit` is called, and dropping the slot
+/// without committing removes them again, so the queue only ever holds whole frames and the
+/// driver never starts sending a frame it does not have all of.
+pub struct FrameSlot<'s, Q = ArrayDeque<[u8; 512]>>
+where
+    Q: TxQueue,
+{
+    tx_queue: &'s mut Q,
+    counters: &'s mut TxCounters,
+    /// Length of the queue before the slot.
+    start: usize,
+    committed: bool,
+}
+
+impl<'s, Q> FrameSlot<'s, Q>
+where
+    Q: TxQueue,
+{
+    pub fn push(&mut self, byte: u8) -> Result<(), SendError> {
+        if self.tx_queue.push_back(byte).is_err() {
+            self.counters.queue_full = self.counters.queue_full.wrapping_add(1);
+            return Err(SendError::QueueFull);
+        }
+        Ok(())
+    }
+
+    pub fn extend<I>(&mut self, bytes: I) -> Result<(), SendError>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        for byte in bytes {
+            self.push(byte)?;
+        }
+        Ok(())
+    }
+
+    /// Number of bytes written to the slot.
+    pub fn len(&self) -> usize {
+        self.tx_queue.len() - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keeps the frame in the queue to be sent.
+    pub fn commit(mut self) {
+        self.committed = true;
+        self.counters.frames_queued = self.counters.frames_queued.wrapping_add(1);
+    }
+}
+
+impl<'s, Q> Drop for FrameSlot<'s, Q>
+where
+    Q: TxQueue,
+{
+    fn drop(&mut self) {
+        if !self.committed {
+            while self.tx_queue.len() > self.start {
+                self.tx_queue.pop_back();
+            }
+        }
+    }
+}
+
+/// Outcome of discarding bytes to resynchronize on a frame boundary.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Resync {
+    /// The queue was already empty and nothing was discarded.
+    Empty,
+    /// The queue now starts with a start delimiter.
+    Found { discarded: usize },
+    /// No start delimiter was found, so every byte was discarded and the queue is now empty.
+    NotFound { discarded: usize },
+}
+
+/// A frame parsed by `XBeeApiReceiver::parse_frame`.
+#[derive(Debug)]
+pub struct ReceivedFrame<'d> {
+    data: ApiData<'d>,
+    len: usize,
+}
+
+impl<'d> ReceivedFrame<'d> {
+    pub fn data(&self) -> &ApiData<'d> {
+        &self.data
+    }
+
+    pub fn into_data(self) -> ApiData<'d> {
+        self.data
+    }
+
+    /// Number of bytes the frame takes up in the RX queue, delimiter and checksum included.
+    pub fn frame_len(&self) -> usize {
+        self.len
+    }
+}
+
+pub struct XBeeApiReceiver<'a> {
+    // TODO: make generic
+    rx_queue: &'a mut RxQueue,
+    format: FrameFormat,
+    /// Number of bytes at the end of the queue belonging to the frame still being received.
+    partial: usize,
+    counters: &'a mut RxCounters,
+}
+
+impl<'a> XBeeApiReceiver<'a> {
+    pub fn queue_empty(&self) -> bool {
+        self.rx_queue.is_empty()
+    }
+
+    pub fn queue_full(&self) -> bool {
+        self.rx_queue.is_full()
+    }
+
+    pub fn unpack_and_parse_buffer<'d>(&'d self) -> Result<ApiData<'d>, XBeeApiError> {
+        self.parse_frame().map(|frame| frame.data)
+    }
+
+    /// Like `unpack_and_parse_buffer`, but also gives how many bytes of the queue the frame takes
+    /// up.
+    pub fn parse_frame<'d>(&'d self) -> Result<ReceivedFrame<'d>, XBeeApiError> {
+        self.parse_frame_at(0)
+    }
+
+    /// Like `unpack_and_parse_buffer`, but parses the frame starting `offset` bytes into the
+    /// queue, such as one found with `next_frame_offset`. Nothing is removed from the queue.
+    pub fn unpack_and_parse_at<'d>(&'d self, offset: usize) -> Result<ApiData<'d>, XBeeApiError> {
+        self.parse_frame_at(offset).map(|frame| frame.data)
+    }
+
+    /// Offset of whatever follows the whole frame starting `offset` bytes into the queue, or
+    /// `None` if no whole frame starts there.
+    ///
+    /// Starting from 0, this walks the frames in the queue without removing any.
+    pub fn next_frame_offset(&self, offset: usize) -> Option<usize> {
+        let queued = self.rx_queue.slice_from(offset)?;
+        let len = api_frame::frame_len_from_header(queued)?;
+        if len <= queued.len() {
+            Some(offset + len)
+        } else {
+            None
+        }
+    }
+
+    /// The bytes queued after the frame at the front of the queue, or `None` if the queue does
+    /// not start with a whole frame.
+    ///
+    /// Any bytes left over from a partially received frame are only given up to where the
+    /// queue's storage wraps around.
+    pub fn remaining_after_frame(&self) -> Option<&[u8]> {
+        let offset = self.next_frame_offset(0)?;
+        Some(self.rx_queue.slice_from(offset).unwrap_or(&[]))
+    }
+
+    fn parse_frame_at<'d>(&'d self, offset: usize) -> Result<ReceivedFrame<'d>, XBeeApiError> {
+        // Whole frames never wrap around the end of the queue's storage
+        let queued = self.rx_queue.slice_from(offset).unwrap_or(&[]);
+        // The drivers unescape frames as they are received
+        let ret = match api_frame::unpack_frame(queued, false, false) {
+            Ok((frame, rem)) => ApiData::parse(frame)
+                .map(|data| ReceivedFrame {
+                    data,
+                    len: queued.len() - rem.len(),
+                })
+                .map_err(XBeeApiError::Parse),
+            Err(err) => Err(XBeeApiError::Unpack(err)),
+        };
+
+        match ret {
+            Ok(_) => count(&self.counters.frames_parsed, 1),
+            Err(XBeeApiError::Unpack(ApiUnpackError::BadChecksum(_))) => {
+                count(&self.counters.checksum_failures, 1)
+            }
+            Err(_) => {}
+        }
+        ret
+    }
+
+    /// How far along the frame being received is, as the bytes of it queued so far and the
+    /// length of the whole frame, or `None` if the queue does not end in a partial frame whose
+    /// header has been received.
+    ///
+    /// Whole frames queued ahead of it are skipped over.
+    pub fn partial_frame_progress(&self) -> Option<(usize, usize)> {
+        let mut offset = 0;
+        while let Some(next) = self.next_frame_offset(offset) {
+            offset = next;
+        }
+        // The header of a partial frame can wrap around the end of the queue's storage
+        let header = [
+            self.rx_queue.get(offset)?,
+            self.rx_queue.get(offset + 1)?,
+            self.rx_queue.get(offset + 2)?,
+        ];
+        let need = api_frame::frame_len_from_header(&header)?;
+        Some((self.rx_queue.len() - offset, need))
+    }
+
+    /// Parses the frame at the front of the queue, hands it to `f`, and then removes it, leaving
+    /// the queue starting at the next frame.
+    ///
+    /// Nothing is removed if the frame cannot be parsed.
+    pub fn consume_frame<F, R>(&mut self, f: F) -> Result<R, XBeeApiError>
+    where
+        F: FnOnce(ApiData<'_>) -> R,
+    {
+        let (ret, len) = {
+            let frame = self.parse_frame()?;
+            let len = frame.frame_len();
+            (f(frame.data), len)
+        };
+        self.rx_queue.remove_exact(len);
+        Ok(ret)
+    }
+
+    pub fn remove_until_packet(&mut self) -> usize {
+        let discarded = self.rx_queue.remove_until_start();
+        count(&self.counters.resync_bytes, discarded);
+        discarded
+    }
+
+    /// Drops every whole frame in the queue, such as to start over after a frame fails to parse.
+    /// A frame still being received is kept, so it is completed as the rest of it arrives.
+    pub fn clear(&mut self) {
+        // Resyncing may have already dropped part of the frame in progress
+        let len = self.rx_queue.len().saturating_sub(self.partial);
+        self.rx_queue.remove_exact(len);
+    }
+
+    /// Drops the frame at the front of the queue by discarding bytes up to the next start
+    /// delimiter.
+    ///
+    /// In escaped mode the queue only ever holds frames back to back, and a start delimiter
+    /// value inside a frame is data rather than the start of the next frame, so the frame is
+    /// skipped using its length field instead.
+    pub fn remove_until_next_packet(&mut self) -> Resync {
+        if self.rx_queue.is_empty() {
+            return Resync::Empty;
+        }
+
+        let discarded = if self.format.is_escaped() {
+            self.rx_queue.remove_frame()
+        } else {
+            let frame_len = match api_frame::unpack_frame(self.rx_queue.as_slice(), false, false) {
+                Ok((_, rem)) => self.rx_queue.len() - rem.len(),
+                Err(_) => 0,
+            };
+            let _ = self.rx_queue.pop_front();
+            let discarded = self.rx_queue.remove_until_start() + 1;
+            // Only bytes that were not part of a good frame count as resynchronizing
+            count(
+                &self.counters.resync_bytes,
+                discarded.saturating_sub(frame_len),
+            );
+            discarded
+        };
+        if self.rx_queue.is_empty() {
+            Resync::NotFound { discarded }
+        } else {
+            Resync::Found { discarded }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.rx_queue.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use core::cell::RefCell;
+
+    use crate::api_frame::AtCmd;
+    use crate::mock::{
+        MockAttn, MockCs, MockError, MockLine, MockLinePin, MockRadio, MockSerial, MockSleep,
+    };
+    use crate::sleep::XBeeSleepControl;
+    use crate::transport::FlowControl;
+
+    #[test]
+    fn poll_resumes_after_would_block_test() {
+        let radio = MockRadio::new();
+        radio.stall_alternately(true);
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut xbee = XBeeApiSpi::new(&mut spi, None::<&mut MockCs>, &mut attn);
+        xbee.tx_queue.extend([0x01, 0x02, 0x03].iter().cloned());
+
+        let mut would_blocks = 0;
+        let read = loop {
+            match xbee.poll() {
+                Ok(read) => break read,
+                Err(nb::Error::WouldBlock) => would_blocks += 1,
+                Err(nb::Error::Other(_)) => panic!("unexpected error"),
+            }
+        };
+
+        assert!(read);
+        assert!(would_blocks > 0);
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(
+            xbee.rx_queue.as_slice(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]
+        );
+        assert_eq!(&radio.sent()[..3], &[0x01, 0x02, 0x03]);
+        assert_eq!(radio.sent().len(), 6);
+    }
+
+    #[test]
+    fn trailing_fill_discarded_test() {
+        let radio = MockRadio::new();
+        // ATTN is released one byte late, so an idle 0xFF is clocked after the frame
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75, 0xFF]);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut xbee = XBeeApiSpi::new(&mut spi, None::<&mut MockCs>, &mut attn);
+
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(
+            xbee.rx_queue.as_slice(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]
+        );
+
+        // A following frame is still picked up, even if it contains 0xFF
+        radio.respond(&[0xFF, 0x7E, 0x00, 0x03, 0x89, 0x01, 0xFF, 0x76]);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(
+            &xbee.rx_queue.as_slice()[6..],
+            &[0x7E, 0x00, 0x03, 0x89, 0x01, 0xFF, 0x76]
+        );
+    }
+
+    #[test]
+    fn io_sampling_config_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender.set_io_sample_rate(0x01, 1000).unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frame = [
+            0x7E,
+            0x00, 0x06,
+            0x08,
+            0x01,
+            b'I', b'R',
+            0x03, 0xE8,
+            0x70,
+        ];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender
+            .set_change_detect(0x01, ChannelIndicator::D0 | ChannelIndicator::D1)
+            .unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frame = [
+            0x7E,
+            0x00, 0x05,
+            0x08,
+            0x01,
+            b'I', b'C',
+            0x03,
+            0x67,
+        ];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+    }
+
+    #[test]
+    fn network_config_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender.set_pan_id(0x01, 0x3332).unwrap();
+        let frame = [0x7E, 0x00, 0x06, 0x08, 0x01, b'I', b'D', 0x33, 0x32, 0x04];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.set_channel(0x01, 0x0C).unwrap();
+        let frame = [0x7E, 0x00, 0x05, 0x08, 0x01, b'C', b'H', 0x0C, 0x5F];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.set_source_address(0x01, 0x0001).unwrap();
+        let frame = [0x7E, 0x00, 0x06, 0x08, 0x01, b'M', b'Y', 0x00, 0x01, 0x4F];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.write_config(0x01).unwrap();
+        let frame = [0x7E, 0x00, 0x04, 0x08, 0x01, b'W', b'R', 0x4D];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.apply_changes(0x01).unwrap();
+        let frame = [0x7E, 0x00, 0x04, 0x08, 0x01, b'A', b'C', 0x72];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.commit_config(0x01).unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frames = [
+            0x7E, 0x00, 0x04, 0x08, 0x01, b'W', b'R', 0x4D,
+            0x7E, 0x00, 0x04, 0x08, 0x01, b'A', b'C', 0x72,
+        ];
+        assert!(sender.tx_queue.iter().eq(frames.iter()));
+    }
+
+    #[test]
+    fn frame_slot_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+        sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+        let frame = [0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'H', 0x60];
+
+        // Packing fails partway through the frame
+        {
+            let mut slot = sender.reserve(8).unwrap();
+            slot.extend([0x7E, 0x00, 0x04, 0x08].iter().cloned())
+                .unwrap();
+            assert_eq!(slot.len(), 4);
+        }
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        let mut slot = sender.reserve(8).unwrap();
+        slot.extend(frame.iter().cloned()).unwrap();
+        slot.commit();
+        assert_eq!(sender.tx_queue.len(), 16);
+        assert!(sender.tx_queue.iter().skip(8).eq(frame.iter()));
+
+        match sender.reserve(512) {
+            Err(SendError::QueueFull) => {}
+            _ => panic!("expected the queue to be full"),
+        }
+        assert_eq!(sender.counters.frames_queued, 2);
+        assert_eq!(sender.counters.queue_full, 1);
+    }
+
+    #[test]
+    fn broadcast_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender.broadcast(0x01, b"hi").unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frame = [
+            0x7E, 0x00, 0x07,
+            0x01, 0x01,
+            // broadcast address
+            0xFF, 0xFF,
+            // PAN broadcast option
+            0x04,
+            b'h', b'i',
+            0x2A,
+        ];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+    }
+
+    #[test]
+    fn sleep_config_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender.set_sleep_mode(0x01, SleepMode::CyclicSleep).unwrap();
+        let frame = [0x7E, 0x00, 0x05, 0x08, 0x01, b'S', b'M', 0x04, 0x52];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        sender.set_sleep_period(0x01, 0x0190).unwrap();
+        let frame = [0x7E, 0x00, 0x06, 0x08, 0x01, b'S', b'P', 0x01, 0x90, 0xC2];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+    }
+
+    #[test]
+    fn encode_tx_frame_test() {
+        // The frame from api_frame's packing_test
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let test_frame = [
+            0x7E,
+            0x00, 0x12,
+            0x00,
+            0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x00,
+            0x54, 0x65, 0x73, 0x74, 0x69, 0x6E, 0x67,
+            0xF5,
+        ];
+        let mut out = [0; 32];
+        let len = encode_tx_frame(
+            0x01,
+            Addr::Long(0x0013_A200_415D_1DBB),
+            b"Testing",
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(&out[..len], &test_frame[..]);
+
+        match encode_tx_frame(0x01, Addr::Short(0x1234), b"Testing", &mut out[..10]) {
+            Err(ApiPackError::BufferTooSmall) => {}
+            _ => panic!("expected the buffer to be too small"),
+        }
+        let mut big = [0; 128];
+        match encode_tx_frame(
+            0x01,
+            Addr::Short(0x1234),
+            &[0; MAX_TX_PAYLOAD + 1],
+            &mut big,
+        ) {
+            Err(ApiPackError::TooLong) => {}
+            _ => panic!("expected the payload to be too long"),
+        }
+    }
+
+    #[test]
+    fn set_digital_output_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender
+            .set_digital_output(
+                0x01,
+                Addr::Long(0x0013_A200_415D_1DBB),
+                DioPin::D3,
+                PinLevel::High,
+            )
+            .unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frame = [
+            0x7E, 0x00, 0x10, 0x17, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0xFF, 0xFE,
+            // Changes applied, output high
+            0x02, b'D', b'3', 0x05,
+            0x41,
+        ];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+    }
+
+    #[test]
+    fn set_node_identifier_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        sender.set_node_identifier(0x01, "Node 1").unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let frame = [
+            0x7E, 0x00, 0x0A, 0x08, 0x01, b'N', b'I',
+            b'N', b'o', b'd', b'e', b' ', b'1',
+            0x88,
+        ];
+        assert!(sender.tx_queue.iter().eq(frame.iter()));
+
+        sender.tx_queue.clear();
+        match sender.set_node_identifier(0x01, "A name far too long!!") {
+            Err(SendError::ParamTooLong) => {}
+            _ => panic!("expected the name to be refused"),
+        }
+        assert!(sender.tx_queue.is_empty());
+    }
+
+    #[test]
+    fn transmit_and_receive_timeout_test() {
+        let radio = MockRadio::new();
+        let serial = MockSerial::new();
+        let mut timer = serial.delay();
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // A radio that stops sending finishes well within the timeout
+        assert!(xbee.transmit_and_receive_timeout(&mut timer, 5).unwrap());
+        assert_eq!(serial.delayed_ms(), 0);
+        assert_eq!(xbee.rx_queue.len(), 6);
+
+        // ATTN stuck asserted, so the radio never seems to run out of bytes
+        radio.stick_attn(true);
+        match xbee.transmit_and_receive_timeout(&mut timer, 5) {
+            Err(FlushError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 5);
+        assert!(!radio.cs_held());
+        // Only idle fill was read, and the frame before it is kept
+        assert_eq!(xbee.rx_queue.len(), 6);
+    }
+
+    #[test]
+    fn attn_error_propagates_test() {
+        let radio = MockRadio::new();
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        radio.fail_attn(true);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Io(SpiIoError::Attn(()))) => {}
+            _ => panic!("expected a pin error"),
+        }
+        assert!(xbee.rx_queue_empty());
+        assert!(!radio.cs_held());
+
+        radio.fail_attn(false);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.len(), 6);
+    }
+
+    #[test]
+    fn attn_polarity_test() {
+        for &polarity in &[AttnPolarity::ActiveLow, AttnPolarity::ActiveHigh] {
+            let radio = MockRadio::new();
+            radio.invert_attn(polarity == AttnPolarity::ActiveHigh);
+
+            let mut spi = radio.spi();
+            let mut attn = radio.attn();
+            let mut cs = radio.cs();
+            let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+            xbee.set_attn_polarity(polarity);
+            assert!(!xbee.data_waiting().unwrap());
+
+            radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+            assert!(xbee.data_waiting().unwrap());
+            xbee.transmit_and_receive().unwrap();
+            assert!(!xbee.data_waiting().unwrap());
+            let (_, receiver) = xbee.get_sender_receiver();
+            match receiver.unpack_and_parse_buffer() {
+                Ok(ApiData::ModemStatus { .. }) => {}
+                other => panic!("expected a modem status, got {:?}", other),
+            }
+        }
+
+        // Read the wrong way round, the radio seems to have data when it has none
+        let radio = MockRadio::new();
+        radio.invert_attn(true);
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut xbee = XBeeApiSpiTransfer::new(&mut spi, None::<&mut MockCs>, &mut attn);
+        assert!(xbee.data_waiting().unwrap());
+        xbee.set_attn_polarity(AttnPolarity::ActiveHigh);
+        assert!(!xbee.data_waiting().unwrap());
+    }
+
+    #[test]
+    fn data_available_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut xbee = XBeeApiSpi::new(&mut spi, None::<&mut MockCs>, &mut attn);
+
+        // ATTN high
+        assert!(!xbee.data_available());
+        // ATTN low
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        assert!(xbee.data_available());
+        xbee.transmit_and_receive().unwrap();
+        assert!(!xbee.data_available());
+
+        // Inverted, so ATTN high means data
+        radio.invert_attn(true);
+        xbee.set_attn_polarity(AttnPolarity::ActiveHigh);
+        assert!(!xbee.data_available());
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        assert!(xbee.data_available());
+
+        radio.fail_attn(true);
+        assert!(xbee.data_available());
+        assert!(xbee.transmit_and_receive().is_err());
+    }
+
+    #[test]
+    fn no_cs_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        {
+            let mut xbee = XBeeApiSpi::new_no_cs(&mut spi, &mut attn);
+            radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+            xbee.transmit_and_receive().unwrap();
+            let (_, receiver) = xbee.get_sender_receiver();
+            match receiver.unpack_and_parse_buffer() {
+                Ok(ApiData::ModemStatus { .. }) => {}
+                _ => panic!("expected a modem status"),
+            }
+        }
+        {
+            let mut xbee = XBeeApiSpiTransfer::new_no_cs(&mut spi, &mut attn);
+            radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+            xbee.transmit_and_receive().unwrap();
+            assert!(!xbee.rx_queue_empty());
+        }
+        assert_eq!(radio.cs_asserts(), 0);
+
+        // Owned peripherals without naming a pin type
+        let xbee = XBeeApiSpi::take(radio.spi(), None::<NoPin>, radio.attn());
+        let (_, cs, _) = xbee.free();
+        assert!(cs.is_none());
+    }
+
+    #[test]
+    fn spi_read_error_test() {
+        let radio = MockRadio::new();
+        radio.fail_spi_read(true);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // Sending the byte worked, reading back what was clocked in did not
+        xbee.tx_queue.extend([0x01, 0x02].iter().cloned());
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Io(SpiIoError::Read(()))) => {}
+            _ => panic!("expected a read error"),
+        }
+        assert_eq!(radio.sent().as_slice(), &[0x01]);
+
+        radio.fail_spi_read(false);
+        xbee.transmit_and_receive().unwrap();
+        assert_eq!(radio.sent().as_slice(), &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn asleep_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        let sleep = MockSleep::new(5);
+        let mut delay = sleep.delay();
+        let mut control = XBeeSleepControl::new(sleep.sleep_rq(), sleep.on_sleep());
+        control.request_sleep().unwrap();
+        xbee.set_asleep(true);
+
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+        }
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Asleep) => {}
+            _ => panic!("expected the radio to be asleep"),
+        }
+        assert!(!block!(xbee.poll()).unwrap());
+        assert_eq!(xbee.poll_write().unwrap(), 0);
+        assert!(radio.sent().is_empty());
+
+        // Only sent once the radio has signalled it is awake
+        control.request_wake().unwrap();
+        control.wait_awake(&mut delay, 10).unwrap();
+        xbee.set_asleep(false);
+        assert!(!xbee.transmit_and_receive().unwrap());
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(
+            radio.sent().as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'H', 0x60]
+        );
+    }
+
+    #[test]
+    fn at_query_test() {
+        let radio = MockRadio::new();
+        let serial = MockSerial::new();
+        let mut timer = serial.delay();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        let modem_status = [0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75];
+        // Same frame ID, but for another command
+        let other_response = [0x7E, 0x00, 0x06, 0x88, 0x01, b'C', b'H', 0x00, 0x0C, 0xDF];
+        radio.respond(&modem_status);
+        radio.respond(&other_response);
+        radio.respond(&[
+            0x7E, 0x00, 0x07, 0x88, 0x01, b'N', b'I', 0x00, b'a', b'b', 0x1C,
+        ]);
+        let response = xbee.at_query([b'N', b'I'], &[], &mut timer, 10).unwrap();
+        assert_eq!(response.status, AtCommandStatus::Ok);
+        assert_eq!(response.data(), b"ab");
+        assert_eq!(
+            &radio.sent()[..8],
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'I', 0x5F]
+        );
+        // Other frames are left where they were
+        assert_eq!(&xbee.rx_queue.as_slice()[..6], &modem_status[..]);
+        assert_eq!(&xbee.rx_queue.as_slice()[6..], &other_response[..]);
+
+        radio.respond(&[0x7E, 0x00, 0x05, 0x88, 0x02, b'C', b'H', 0x01, 0xE9]);
+        let response = xbee.at_query(AtCmd::Ch, &[], &mut timer, 10).unwrap();
+        assert_eq!(response.status, AtCommandStatus::Error);
+        assert!(response.data().is_empty());
+        assert_eq!(xbee.rx_queue.len(), 16);
+        assert_eq!(serial.delayed_ms(), 0);
+
+        match xbee.at_query([b'C', b'H'], &[], &mut timer, 5) {
+            Err(FlushError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 5);
+    }
+
+    #[test]
+    fn wait_for_association_test() {
+        let radio = MockRadio::new();
+        let serial = MockSerial::new();
+        let mut timer = serial.delay();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // Scanning, then joined
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x01, b'A', b'I', 0x00, 0xFF, 0xED]);
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x02, b'A', b'I', 0x00, 0x00, 0xEB]);
+        xbee.wait_for_association(&mut timer, 20, 100).unwrap();
+        assert_eq!(serial.delayed_ms(), 20);
+
+        // No PANs found is given up on without waiting for the timeout
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x03, b'A', b'I', 0x00, 0x02, 0xE8]);
+        match xbee.wait_for_association(&mut timer, 20, 100) {
+            Err(AssociationError::Failed(AssociationStatus::ActiveScanNoPans)) => {}
+            _ => panic!("expected a failure"),
+        }
+        assert_eq!(serial.delayed_ms(), 20);
+
+        match xbee.wait_for_association(&mut timer, 20, 30) {
+            Err(AssociationError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 50);
+
+        // Answers already waiting still count towards the timeout without a poll interval
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x05, b'A', b'I', 0x00, 0xFF, 0xE9]);
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x06, b'A', b'I', 0x00, 0xFF, 0xE8]);
+        radio.respond(&[0x7E, 0x00, 0x06, 0x88, 0x07, b'A', b'I', 0x00, 0xFF, 0xE7]);
+        match xbee.wait_for_association(&mut timer, 0, 3) {
+            Err(AssociationError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 50);
+    }
+
+    #[test]
+    fn set_encryption_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        let key = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ];
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x01, b'K', b'Y', 0x00, 0xD2]);
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x02, b'E', b'E', 0x00, 0xEB]);
+        xbee.set_encryption(Some(&key), &mut timer, 10).unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected = [
+            0x7E, 0x00, 0x14, 0x08, 0x01, b'K', b'Y',
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+            0xDA,
+            // KY before EE
+            0x7E, 0x00, 0x05, 0x08, 0x02, b'E', b'E', 0x01, 0x6A,
+        ];
+        assert_eq!(serial.written().as_slice(), &expected[..]);
+
+        // Turning encryption off leaves the key alone
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x03, b'E', b'E', 0x00, 0xEA]);
+        xbee.set_encryption(None, &mut timer, 10).unwrap();
+        assert_eq!(
+            &serial.written()[expected.len()..],
+            &[0x7E, 0x00, 0x05, 0x08, 0x03, b'E', b'E', 0x00, 0x6A]
+        );
+    }
+
+    #[test]
+    fn force_sample_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+        let addr = 0x0013_A200_415D_1DBB;
+
+        // A0 and A1 analog, D2 to D4 digital with D2 and D4 high
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x18, 0x97, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'I', b'S', 0x00,
+            0x01, 0x06, 0x1C, 0x00, 0x14, 0x01, 0x23, 0x03, 0xFF,
+            0xFD,
+        ]);
+        let sample = xbee.force_sample(Addr::Long(addr), &mut timer, 10).unwrap();
+        assert_eq!(
+            sample,
+            IoSample {
+                samples: 1,
+                channel_indicator: ChannelIndicator::A0
+                    | ChannelIndicator::A1
+                    | ChannelIndicator::D2
+                    | ChannelIndicator::D3
+                    | ChannelIndicator::D4,
+                digital_samples: Some(0x0014),
+                analog_samples: [Some(0x0123), Some(0x03FF), None, None],
+            }
+        );
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected = [
+            0x7E, 0x00, 0x0F, 0x17, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0xFF, 0xFE,
+            0x02, b'I', b'S', 0x21,
+        ];
+        assert_eq!(serial.written().as_slice(), &expected[..]);
+
+        // A1 missing from the sample
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x16, 0x97, 0x02,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'I', b'S', 0x00,
+            0x01, 0x06, 0x1C, 0x00, 0x14, 0x01, 0x23,
+            0xFE,
+        ]);
+        match xbee.force_sample(Addr::Long(addr), &mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x0F, 0x97, 0x03,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'I', b'S', 0x04,
+            0x54,
+        ]);
+        match xbee.force_sample(Addr::Long(addr), &mut timer, 10) {
+            Err(AtError::Status(AtCommandStatus::NoResponse)) => {}
+            _ => panic!("expected no response from the remote radio"),
+        }
+    }
+
+    #[test]
+    fn last_rssi_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond(&[0x7E, 0x00, 0x06, 0x88, 0x01, b'D', b'B', 0x00, 0x28, 0xC8]);
+        assert_eq!(xbee.last_rssi(&mut timer, 10).unwrap(), -40);
+        let query = [0x7E, 0x00, 0x04, 0x08, 0x01, b'D', b'B', 0x70];
+        assert_eq!(serial.written().as_slice(), &query[..]);
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x10, 0x97, 0x02,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'D', b'B', 0x00, 0x4F,
+            0x20,
+        ]);
+        let addr = Addr::Long(0x0013_A200_415D_1DBB);
+        assert_eq!(xbee.remote_last_rssi(addr, &mut timer, 10).unwrap(), -79);
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let remote_query = [
+            0x7E, 0x00, 0x0F, 0x17, 0x02,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0xFF, 0xFE,
+            0x02, b'D', b'B',
+            0x36,
+        ];
+        assert_eq!(&serial.written()[query.len()..], &remote_query[..]);
+
+        // Nothing received yet
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x03, b'D', b'B', 0x00, 0xEE]);
+        match xbee.last_rssi(&mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+    }
+
+    #[test]
+    fn configure_io_sampling_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"IT", 0x00, &[]);
+        serial.respond_at(2, *b"IC", 0x00, &[]);
+        serial.respond_at(3, *b"IR", 0x00, &[]);
+        let mask = ChannelIndicator::D0 | ChannelIndicator::D7;
+        xbee.configure_io_sampling(None, 1000, mask, 4, &mut timer, 10)
+            .unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected = [
+            0x7E, 0x00, 0x05, 0x08, 0x01, b'I', b'T', 0x04, 0x55,
+            0x7E, 0x00, 0x05, 0x08, 0x02, b'I', b'C', 0x81, 0xE8,
+            // 1000 ms, big-endian
+            0x7E, 0x00, 0x06, 0x08, 0x03, b'I', b'R', 0x03, 0xE8, 0x6E,
+        ];
+        assert_eq!(serial.written().as_slice(), &expected[..]);
+
+        // A rate of 0 turns periodic sampling off on the remote radio
+        let addr = Addr::Long(0x0013_A200_415D_1DBB);
+        for (id, at_cmd) in [(4, *b"IT"), (5, *b"IC"), (6, *b"IR")].iter() {
+            let mut frame: ArrayVec<[u8; 32]> = ArrayVec::new();
+            frame.extend(
+                [0x97, *id, 0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D]
+                    .iter()
+                    .cloned(),
+            );
+            frame.extend(
+                [0x1D, 0xBB, 0xFF, 0xFE, at_cmd[0], at_cmd[1], 0x00]
+                    .iter()
+                    .cloned(),
+            );
+            let packed: ArrayVec<[u8; 32]> = FramePacker::new(frame.into_iter(), false, false)
+                .unwrap()
+                .collect();
+            serial.respond(&packed);
+        }
+        xbee.configure_io_sampling(Some(addr), 0, ChannelIndicator::empty(), 1, &mut timer, 10)
+            .unwrap();
+        let written = serial.written();
+        let mut rest = &written[expected.len()..];
+        let mut frames = 0;
+        let mut ir = &[][..];
+        while !rest.is_empty() {
+            let (frame, rem) = api_frame::unpack_frame(rest, false, false).unwrap();
+            ir = frame;
+            rest = rem;
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+        assert_eq!(ir[0], 0x17);
+        assert_eq!(&ir[12..], &[0x02, b'I', b'R', 0x00, 0x00]);
+
+        // Refused before anything is sent
+        let refused = [
+            (0, ChannelIndicator::D0),
+            (MAX_IO_SAMPLES_PER_PACKET + 1, ChannelIndicator::D0),
+            (1, ChannelIndicator::A0),
+            (1, ChannelIndicator::D8),
+        ];
+        for &(samples, mask) in refused.iter() {
+            match xbee.configure_io_sampling(None, 1000, mask, samples, &mut timer, 10) {
+                Err(AtError::OutOfRange) => {}
+                _ => panic!("expected {} samples of {:?} to be refused", samples, mask),
+            }
+        }
+        assert_eq!(serial.written().len(), written.len());
+    }
+
+    #[test]
+    fn power_level_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"PL", 0x00, &[]);
+        xbee.set_power_level(PowerLevel::Highest, &mut timer, 10)
+            .unwrap();
+        assert_eq!(
+            serial.written().as_slice(),
+            &[0x7E, 0x00, 0x05, 0x08, 0x01, b'P', b'L', 0x04, 0x56]
+        );
+
+        serial.respond_at(2, *b"PL", 0x00, &[0x04]);
+        assert_eq!(
+            xbee.power_level(&mut timer, 10).unwrap(),
+            PowerLevel::Highest
+        );
+        assert_eq!(
+            &serial.written()[9..],
+            &[0x7E, 0x00, 0x04, 0x08, 0x02, b'P', b'L', 0x59]
+        );
+
+        // Not a power level
+        serial.respond_at(3, *b"PL", 0x00, &[0x05]);
+        match xbee.power_level(&mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+
+        serial.respond_at(4, *b"PM", 0x00, &[]);
+        xbee.set_boost_mode(false, &mut timer, 10).unwrap();
+        assert!(serial
+            .written()
+            .ends_with(&[0x7E, 0x00, 0x05, 0x08, 0x04, b'P', b'M', 0x00, 0x56]));
+        serial.respond_at(5, *b"PM", 0x00, &[0x00]);
+        assert!(!xbee.boost_mode(&mut timer, 10).unwrap());
+    }
+
+    #[test]
+    fn discover_nodes_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let node1 = [
+            0x12, 0x34,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB,
+            0x28,
+            b'N', b'o', b'd', b'e', b' ', b'1', 0x00,
+        ];
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let node2 = [
+            0xFF, 0xFE,
+            0x00, 0x13, 0xA2, 0x00, 0x40, 0x52, 0x2B, 0xAA,
+            0x4F,
+            0x00,
+        ];
+
+        // NT of 200 ms, then two nodes and the empty answer ending the discovery
+        serial.respond_at(1, *b"NT", 0x00, &[0x02]);
+        serial.respond_at(2, *b"ND", 0x00, &node1);
+        serial.respond_at(2, *b"ND", 0x00, &node2);
+        serial.respond_at(2, *b"ND", 0x00, &[]);
+        let mut found: ArrayVec<[(u16, u64, u8, bool); 4]> = ArrayVec::new();
+        let count = xbee
+            .discover_nodes(&mut timer, 10, |node| {
+                found.push((node.my, node.serial, node.rssi, node.identifier == "Node 1"))
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            found.as_slice(),
+            &[
+                (0x1234, 0x0013_A200_415D_1DBB, 0x28, true),
+                (0xFFFE, 0x0013_A200_4052_2BAA, 0x4F, false),
+            ]
+        );
+        // Ended by the empty answer rather than by waiting out NT
+        assert_eq!(serial.delayed_ms(), 0);
+
+        // Nothing in range
+        serial.respond_at(3, *b"NT", 0x00, &[0x02]);
+        serial.respond_at(4, *b"ND", 0x00, &[]);
+        let count = xbee.discover_nodes(&mut timer, 10, |_| panic!()).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(serial.delayed_ms(), 0);
+
+        // No empty answer, so the discovery ends after NT and the per node timeout
+        serial.respond_at(5, *b"NT", 0x00, &[0x02]);
+        serial.respond_at(6, *b"ND", 0x00, &node1);
+        let count = xbee.discover_nodes(&mut timer, 10, |_| {}).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(serial.delayed_ms(), 210);
+    }
+
+    #[test]
+    fn energy_scan_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"SC", 0x00, &[0x04, 0x21]);
+        serial.respond_at(2, *b"ED", 0x00, &[0x4A, 0x50, 0x3C]);
+        let scan = xbee.energy_scan(&mut timer, 10).unwrap();
+        assert_eq!(scan.reading(0x15), Some(0x3C));
+        assert_eq!(scan.quietest_channel(), Some(0x10));
+
+        // More readings than scan channels
+        serial.respond_at(3, *b"SC", 0x00, &[0x04, 0x21]);
+        serial.respond_at(4, *b"ED", 0x00, &[0x4A, 0x50, 0x3C, 0x58]);
+        match xbee.energy_scan(&mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+    }
+
+    #[test]
+    fn supply_voltage_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        // 0x300 * 1200 / 1024
+        serial.respond_at(1, *b"%V", 0x00, &[0x03, 0x00]);
+        assert_eq!(xbee.supply_voltage_mv(&mut timer, 10).unwrap(), 900);
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x11, 0x97, 0x02,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'%', b'V', 0x00, 0x02, 0xAA,
+            0xCE,
+        ]);
+        let voltage = xbee
+            .remote_supply_voltage_mv(Addr::Short(0x1234), &mut timer, 10)
+            .unwrap();
+        assert_eq!(voltage, 799);
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let remote_query = [
+            0x7E, 0x00, 0x0F, 0x17, 0x02,
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x12, 0x34,
+            0x02, b'%', b'V',
+            0x2B,
+        ];
+        assert_eq!(&serial.written()[8..], &remote_query[..]);
+
+        // Wider than the ADC
+        serial.respond_at(3, *b"%V", 0x00, &[0x04, 0x00]);
+        match xbee.supply_voltage_mv(&mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+    }
+
+    #[test]
+    fn verify_firmware_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        serial.respond_at(1, *b"VR", 0x00, &[0x20, 0x03]);
+        xbee.verify_firmware(&mut timer, 10).unwrap();
+        serial.respond_at(2, *b"HV", 0x00, &[0x22, 0x41]);
+        assert_eq!(xbee.hardware_version(&mut timer, 10).unwrap(), 0x2241);
+
+        // Zigbee firmware
+        serial.respond_at(3, *b"VR", 0x00, &[0x40, 0x5F]);
+        match xbee.verify_firmware(&mut timer, 10) {
+            Err(FirmwareMismatch::NotIeee802154 {
+                firmware_version: 0x405F,
+            }) => {}
+            _ => panic!("expected a firmware mismatch"),
+        }
+
+        match xbee.verify_firmware(&mut timer, 2) {
+            Err(FirmwareMismatch::At(AtError::Flush(FlushError::Timeout))) => {}
+            _ => panic!("expected a timeout"),
+        }
+    }
+
+    #[test]
+    fn remote_pin_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        // A node without a 16-bit address answers with 0xFFFE
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x0F, 0x97, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0xFF, 0xFE,
+            b'D', b'4', 0x00,
+            0xC7,
+        ]);
+        xbee.set_remote_pin(
+            Addr::Long(0x0013_A200_415D_1DBB),
+            DioPin::D4,
+            PinLevel::High,
+            &mut timer,
+            10,
+        )
+        .unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected = [
+            0x7E, 0x00, 0x10, 0x17, 0x01,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0xFF, 0xFE,
+            // Changes applied, output high
+            0x02, b'D', b'4', 0x05,
+            0x40,
+        ];
+        assert_eq!(serial.written().as_slice(), &expected[..]);
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x0F, 0x97, 0x02,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'P', b'1', 0x00,
+            0x74,
+        ]);
+        xbee.configure_remote_pin(
+            Addr::Short(0x1234),
+            DioPin::P1,
+            PinFunction::Adc,
+            &mut timer,
+            10,
+        )
+        .unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected_p1 = [
+            0x7E, 0x00, 0x10, 0x17, 0x02,
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x12, 0x34,
+            0x02, b'P', b'1', 0x02,
+            0x23,
+        ];
+        assert_eq!(&serial.written()[expected.len()..], &expected_p1[..]);
+
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            0x7E, 0x00, 0x0F, 0x97, 0x03,
+            0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x12, 0x34,
+            b'P', b'1', 0x03,
+            0x70,
+        ]);
+        match xbee.configure_remote_pin(
+            Addr::Short(0x1234),
+            DioPin::P1,
+            PinFunction::Adc,
+            &mut timer,
+            10,
+        ) {
+            Err(AtError::Status(AtCommandStatus::InvalidParam)) => {}
+            _ => panic!("expected the parameter to be refused"),
+        }
+    }
+
+    #[test]
+    fn soft_reset_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+
+        // Stale modem statuses, one already received and one about to be, and a frame that was
+        // never sent
+        let stale = [0x7E, 0x00, 0x02, 0x8A, 0x02, 0x73];
+        xbee.rx_queue.extend(stale.iter().cloned());
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data_raw(&[0x01, 0x02]).unwrap();
+        }
+        serial.respond(&stale);
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x01, b'F', b'R', 0x00, 0xDE]);
+        serial.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        assert_eq!(
+            xbee.soft_reset(&mut timer, 10).unwrap(),
+            ModemStatus::HardwareReset
+        );
+        // The frame waiting to be sent is dropped rather than sent before the reset
+        assert_eq!(
+            serial.written().as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'F', b'R', 0x5E]
+        );
+        assert!(xbee.rx_queue_empty());
+        assert!(xbee.tx_queue_empty());
+
+        // Only the answer to FR, and no modem status in time
+        serial.respond(&stale);
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x02, b'F', b'R', 0x00, 0xDD]);
+        match xbee.soft_reset(&mut timer, 10) {
+            Err(AtError::Flush(FlushError::Timeout)) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 10);
+
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x03, b'W', b'R', 0x00, 0xCB]);
+        xbee.commit_to_flash(&mut timer, 10).unwrap();
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x04, b'N', b'R', 0x00, 0xD3]);
+        xbee.network_reset(&mut timer, 10).unwrap();
+    }
+
+    #[test]
+    fn tx_retry_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+        xbee.set_tx_retry(1);
+
+        let frame = [
+            0x7E, 0x00, 0x07, 0x01, 0x01, 0x12, 0x34, 0x00, b'h', b'i', 0xE6,
+        ];
+        let cca_failure = [0x7E, 0x00, 0x03, 0x89, 0x01, 0x02, 0x73];
+        let success = [0x7E, 0x00, 0x03, 0x89, 0x01, 0x00, 0x75];
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x01, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        serial.respond(&cca_failure);
+        serial.respond(&success);
+        while xbee.transmit_and_receive().unwrap() {}
+        // Sent again, and only the status of the last attempt is left
+        assert_eq!(&serial.written()[..frame.len()], &frame[..]);
+        assert_eq!(&serial.written()[frame.len()..], &frame[..]);
+        assert_eq!(xbee.rx_queue.as_slice(), &success[..]);
+        xbee.clear_rx_queue();
+
+        // Out of retries
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x01, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        serial.respond(&cca_failure);
+        serial.respond(&cca_failure);
+        while xbee.transmit_and_receive().unwrap() {}
+        assert_eq!(serial.written().len(), 4 * frame.len());
+        assert_eq!(xbee.rx_queue.as_slice(), &cca_failure[..]);
+        xbee.clear_rx_queue();
+
+        // Nothing is kept for frame ID 0, as no status comes for it
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x00, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        assert!(xbee.tx_retry.is_empty());
+    }
+
+    #[test]
+    fn tx_retry_stale_status_test() {
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+        xbee.set_tx_retry(1);
+
+        let frame = [
+            0x7E, 0x00, 0x07, 0x01, 0x01, 0x12, 0x34, 0x00, b'h', b'i', 0xE6,
+        ];
+        let cca_failure = [0x7E, 0x00, 0x03, 0x89, 0x01, 0x02, 0x73];
+        let success = [0x7E, 0x00, 0x03, 0x89, 0x01, 0x00, 0x75];
+        // The final status of a request is left in the queue for the receiver
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x01, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        serial.respond(&cca_failure);
+        serial.respond(&cca_failure);
+        while xbee.transmit_and_receive().unwrap() {}
+        assert_eq!(serial.written().len(), 2 * frame.len());
+        assert_eq!(xbee.rx_queue.as_slice(), &cca_failure[..]);
+
+        // Frame IDs wrap around from 0xFF to 1, so a later request reuses the ID of the status
+        // still waiting to be read, which must not be taken as its own
+        xbee.frame_id = 0xFF;
+        let frame_id = xbee.next_frame_id();
+        assert_eq!(frame_id, 0x01);
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(frame_id, Addr::Short(0x1234), b"hi")
+                .unwrap();
+        }
+        while xbee.transmit_and_receive().unwrap() {}
+        assert_eq!(serial.written().len(), 3 * frame.len());
+        assert_eq!(xbee.rx_queue.as_slice(), &cca_failure[..]);
+        assert!(!xbee.tx_retry.is_empty());
+
+        // Its own status still lets it go
+        serial.respond(&success);
+        while xbee.transmit_and_receive().unwrap() {}
+        assert_eq!(serial.written().len(), 3 * frame.len());
+        assert!(xbee.tx_retry.is_empty());
+        let mut expected = ArrayVec::<[u8; 16]>::new();
+        expected.extend(cca_failure.iter().cloned());
+        expected.extend(success.iter().cloned());
+        assert_eq!(xbee.rx_queue.as_slice(), &expected[..]);
+    }
+
+    #[test]
+    fn frame_id_skips_zero_test() {
+        let radio = MockRadio::new();
+        let serial = MockSerial::new();
+        let mut timer = serial.delay();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        xbee.frame_id = 0xFE;
+        assert_eq!(xbee.next_frame_id(), 0xFF);
+        // Wraps around to 1, as no answer would ever come for 0
+        radio.respond(&[0x7E, 0x00, 0x05, 0x88, 0x01, b'C', b'H', 0x00, 0xEB]);
+        let response = xbee.at_query([b'C', b'H'], &[], &mut timer, 10).unwrap();
+        assert_eq!(response.status, AtCommandStatus::Ok);
+        assert_eq!(radio.sent()[4], 0x01);
+
+        // Nothing is kept to wait on for a TX request sent with frame ID 0
+        xbee.set_tx_retry(1);
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x00, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        xbee.transmit_and_receive().unwrap();
+        assert!(xbee.tx_queue_empty());
+        assert!(xbee.tx_retry.is_empty());
+        // Unlike one with any other frame ID
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x01, Addr::Short(0x1234), b"hi").unwrap();
+        }
+        assert!(!xbee.tx_retry.is_empty());
+    }
+
+    #[test]
+    fn read_serial_number_test() {
+        let radio = MockRadio::new();
+        let serial = MockSerial::new();
+        let mut timer = serial.delay();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // SL arrives first, but SH is still the high half
+        radio.respond(&[
+            0x7E, 0x00, 0x09, 0x88, 0x02, b'S', b'L', 0x00, 0x41, 0x5D, 0x1D, 0xBB, 0x60,
+        ]);
+        radio.respond(&[
+            0x7E, 0x00, 0x09, 0x88, 0x01, b'S', b'H', 0x00, 0x00, 0x13, 0xA2, 0x00, 0x26,
+        ]);
+        let serial_number = xbee.read_serial_number(&mut timer, 10).unwrap();
+        assert_eq!(serial_number, 0x0013_A200_415D_1DBB);
+        assert!(xbee.rx_queue_empty());
+
+        // Sent to, the serial number goes out most significant byte first
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(0x01, Addr::Long(serial_number), &[0x01])
+                .unwrap();
+        }
+        let sent = radio.sent().len();
+        xbee.transmit_and_receive().unwrap();
+        assert_eq!(
+            &radio.sent()[sent + 5..sent + 13],
+            &[0x00, 0x13, 0xA2, 0x00, 0x41, 0x5D, 0x1D, 0xBB]
+        );
+
+        radio.respond(&[0x7E, 0x00, 0x05, 0x88, 0x03, b'S', b'H', 0x01, 0xD8]);
+        match xbee.read_serial_number(&mut timer, 10) {
+            Err(AtError::Status(AtCommandStatus::Error)) => {}
+            _ => panic!("expected an error status"),
+        }
+
+        radio.respond(&[
+            0x7E, 0x00, 0x07, 0x88, 0x04, b'S', b'H', 0x00, 0x13, 0xA2, 0x23,
+        ]);
+        match xbee.read_serial_number(&mut timer, 10) {
+            Err(AtError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+
+        match xbee.read_serial_number(&mut timer, 2) {
+            Err(AtError::Flush(FlushError::Timeout)) => {}
+            _ => panic!("expected a timeout"),
+        }
+    }
+
+    #[test]
+    fn data_waiting_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+        assert_eq!(xbee.data_waiting(), Ok(false));
+
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        assert_eq!(xbee.data_waiting(), Ok(true));
+
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.data_waiting(), Ok(false));
+    }
+
+    #[test]
+    fn halves_are_send_test() {
+        fn assert_send<T: Send>() {}
+
+        assert_send::<XBeeApiSender<'static>>();
+        assert_send::<XBeeApiReceiver<'static>>();
+    }
+
+    #[test]
+    fn send_error_test() {
+        let mut tx_queue = ArrayDeque::new();
+        let mut sender: XBeeApiSender = XBeeApiSender {
+            tx_queue: &mut tx_queue,
+            format: FrameFormat::Unescaped,
+            counters: &mut TxCounters::default(),
+            retry: &mut TxRetry::default(),
+        };
+
+        let payload = [0x55; MAX_TX_PAYLOAD + 1];
+        match sender.send_data(0x01, Addr::Short(0x0001), &payload) {
+            Err(SendError::PayloadTooLarge) => {}
+            _ => panic!("expected the payload to be rejected"),
+        }
+        assert!(sender.queue_empty());
+
+        // Leave room for less than a full frame
+        let filler = [0x00; 500];
+        sender.send_data_raw(&filler).unwrap();
+        match sender.send_data(0x01, Addr::Short(0x0001), &payload[..10]) {
+            Err(SendError::QueueFull) => {}
+            _ => panic!("expected the queue to be full"),
+        }
+        assert_eq!(sender.tx_queue.len(), filler.len());
+
+        match sender.send_data_raw(&payload[..13]) {
+            Err(SendError::QueueFull) => {}
+            _ => panic!("expected the queue to be full"),
+        }
+        sender.send_data_raw(&payload[..12]).unwrap();
+        assert!(sender.queue_full());
+    }
+
+    fn receiver_with<'a>(
+        rx_queue: &'a mut RxQueue,
+        counters: &'a mut RxCounters,
+        data: &[u8],
+    ) -> XBeeApiReceiver<'a> {
+        rx_queue.extend(data.iter().cloned());
+        XBeeApiReceiver {
+            rx_queue,
+            format: FrameFormat::Unescaped,
+            partial: 0,
+            counters,
+        }
+    }
+
+    #[test]
+    fn parse_at_offset_test() {
+        let mut rx_queue = RxQueue::new();
+        let mut counters = RxCounters::default();
+        let modem_status = [0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75];
+        let at_response = [0x7E, 0x00, 0x06, 0x88, 0x01, b'C', b'H', 0x00, 0x0C, 0xDF];
+        let mut data = [0; 16];
+        data[..6].copy_from_slice(&modem_status);
+        data[6..].copy_from_slice(&at_response);
+        let receiver = receiver_with(&mut rx_queue, &mut counters, &data);
+
+        assert_eq!(
+            receiver.unpack_and_parse_at(0).unwrap(),
+            ApiData::ModemStatus {
+                status: ModemStatus::HardwareReset
+            }
+        );
+        let offset = receiver.next_frame_offset(0).unwrap();
+        assert_eq!(offset, modem_status.len());
+        assert_eq!(
+            receiver.unpack_and_parse_at(offset).unwrap(),
+            ApiData::AtCommandResponse {
+                frame_id: 0x01,
+                at_cmd: [b'C', b'H'],
+                status: AtCommandStatus::Ok,
+                data: &[0x0C],
+            }
+        );
+        assert_eq!(receiver.next_frame_offset(offset), Some(data.len()));
+        assert_eq!(receiver.next_frame_offset(data.len()), None);
+        assert!(receiver.unpack_and_parse_at(data.len()).is_err());
+        // Nothing was removed
+        assert_eq!(receiver.as_slice(), &data[..]);
+    }
+
+    #[test]
+    fn remaining_after_frame_test() {
+        let mut rx_queue = RxQueue::new();
+        let mut counters = RxCounters::default();
+        let data = [
+            0x7E, 0x00, 0x0A, 0x01, 0x01, 0x50, 0x01, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0xB8,
+            // extra data
+            0x7E, 0x01, 0x02,
+        ];
+        let receiver = receiver_with(&mut rx_queue, &mut counters, &data);
+        assert_eq!(
+            receiver.remaining_after_frame(),
+            Some(&[0x7E, 0x01, 0x02][..])
+        );
+
+        // A lone frame leaves nothing behind
+        let mut rx_queue = RxQueue::new();
+        let receiver = receiver_with(&mut rx_queue, &mut counters, &data[..14]);
+        assert_eq!(receiver.remaining_after_frame(), Some(&[][..]));
+
+        // No whole frame to skip
+        let mut rx_queue = RxQueue::new();
+        let receiver = receiver_with(&mut rx_queue, &mut counters, &data[..10]);
+        assert_eq!(receiver.remaining_after_frame(), None);
+    }
+
+    #[test]
+    fn consume_frame_test() {
+        let mut rx_queue = RxQueue::new();
+        let mut counters = RxCounters::default();
+        let modem_status = [0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75];
+        let at_response = [0x7E, 0x00, 0x06, 0x88, 0x01, b'C', b'H', 0x00, 0x0C, 0xDF];
+        let mut data = [0; 16];
+        data[..6].copy_from_slice(&modem_status);
+        data[6..].copy_from_slice(&at_response);
+        let mut receiver = receiver_with(&mut rx_queue, &mut counters, &data);
+
+        assert_eq!(
+            receiver.parse_frame().unwrap().frame_len(),
+            modem_status.len()
+        );
+
+        let status = receiver
+            .consume_frame(|data| match data {
+                ApiData::ModemStatus { status } => Some(status),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(status, Some(ModemStatus::HardwareReset));
+        // The next frame is left whole
+        assert_eq!(receiver.as_slice(), &at_response[..]);
+
+        let channel = receiver
+            .consume_frame(|data| data.at_response_data().and_then(|data| data.as_u8()))
+            .unwrap();
+        assert_eq!(channel, Some(0x0C));
+        assert!(receiver.queue_empty());
+
+        // Nothing is removed from an incomplete frame
+        receiver.rx_queue.extend(at_response[..4].iter().cloned());
+        assert!(receiver.consume_frame(|_| ()).is_err());
+        assert_eq!(receiver.as_slice(), &at_response[..4]);
+        assert_eq!(counters.frames_parsed.get(), 3);
+    }
+
+    #[test]
+    fn partial_frame_progress_test() {
+        let mut rx_queue = RxQueue::new();
+        let mut counters = RxCounters::default();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let receiver = receiver_with(
+            &mut rx_queue,
+            &mut counters,
+            &[
+                0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75,
+                // Header and half of a TX status
+                0x7E, 0x00, 0x04, 0x89, 0x01,
+            ],
+        );
+        assert_eq!(receiver.partial_frame_progress(), Some((5, 8)));
+
+        // Only whole frames
+        let mut rx_queue = RxQueue::new();
+        let receiver = receiver_with(
+            &mut rx_queue,
+            &mut counters,
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75],
+        );
+        assert_eq!(receiver.partial_frame_progress(), None);
+
+        // The length is not known yet
+        let mut rx_queue = RxQueue::new();
+        let receiver = receiver_with(&mut rx_queue, &mut counters, &[0x7E, 0x00]);
+        assert_eq!(receiver.partial_frame_progress(), None);
+    }
+
+    #[test]
+    fn receiver_clear_test() {
+        let mut rx_queue = RxQueue::new();
+        let mut counters = RxCounters::default();
+        let mut receiver = receiver_with(
+            &mut rx_queue,
+            &mut counters,
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75],
+        );
+        assert!(!receiver.queue_empty());
+        receiver.clear();
+        assert!(receiver.queue_empty());
+
+        // Clearing partway through receiving a frame in escaped mode
+        let serial = MockSerial::new();
+        let mut uart = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut uart);
+        xbee.set_frame_format(FrameFormat::Escaped);
+        serial.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75, 0x7E, 0x00, 0x02]);
+        xbee.transmit_and_receive().unwrap();
+        {
+            let (_, mut receiver) = xbee.get_sender_receiver();
+            receiver.clear();
+            assert_eq!(receiver.as_slice(), &[0x7E, 0x00, 0x02]);
+        }
+        xbee.clear_rx_queue();
+        // The rest of the frame, with its status escaped
+        serial.respond(&[0x8A, 0x7D, 0x31, 0x64]);
+        xbee.transmit_and_receive().unwrap();
+        let (_, mut receiver) = xbee.get_sender_receiver();
+        let status = receiver.consume_frame(|data| match data {
+            ApiData::ModemStatus { status } => Some(status),
+            _ => None,
+        });
+        assert_eq!(status.unwrap(), Some(ModemStatus::Unknown));
+        assert!(receiver.queue_empty());
+    }
+
+    #[test]
+    fn remove_until_next_packet_test() {
+        let mut counters = RxCounters::default();
+
+        // Starting mid-frame
+        let mut rx_queue = RxQueue::new();
+        let mut receiver = receiver_with(
+            &mut rx_queue,
+            &mut counters,
+            &[0x00, 0x75, 0x7E, 0x00, 0x02],
+        );
+        assert_eq!(
+            receiver.remove_until_next_packet(),
+            Resync::Found { discarded: 2 }
+        );
+        assert_eq!(receiver.as_slice(), &[0x7E, 0x00, 0x02]);
+
+        // Back-to-back frames
+        let mut rx_queue = RxQueue::new();
+        let mut receiver = receiver_with(
+            &mut rx_queue,
+            &mut counters,
+            &[
+                0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75, 0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74,
+            ],
+        );
+        assert_eq!(
+            receiver.remove_until_next_packet(),
+            Resync::Found { discarded: 6 }
+        );
+        assert_eq!(receiver.as_slice(), &[0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74]);
+        assert_eq!(
+            receiver.remove_until_next_packet(),
+            Resync::NotFound { discarded: 6 }
+        );
+        assert!(receiver.queue_empty());
+
+        // Empty buffer
+        assert_eq!(receiver.remove_until_next_packet(), Resync::Empty);
+    }
+
+    #[test]
+    fn cs_error_propagates_test() {
+        let radio = MockRadio::new();
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        radio.fail_cs(true);
+
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // CS and ATTN errors are different types, and reported apart
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Io(SpiIoError::Cs(MockError))) => {}
+            _ => panic!("expected a CS error"),
+        }
+
+        radio.fail_cs(false);
+        radio.fail_attn(true);
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Io(SpiIoError::Attn(()))) => {}
+            _ => panic!("expected an ATTN error"),
+        }
+
+        radio.fail_attn(false);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.len(), 6);
+    }
+
+    #[test]
+    fn flush_tx_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // A modem status arrives while the frame is being sent
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        xbee.send_frame_blocking(Addr::Short(0x1234), b"hi")
+            .unwrap();
+        assert!(xbee.tx_queue_empty());
+        let frame = [
+            0x7E, 0x00, 0x07, 0x01, 0x00, 0x12, 0x34, 0x00, b'h', b'i', 0xE7,
+        ];
+        assert_eq!(radio.sent().as_slice(), &frame[..]);
+        assert_eq!(
+            xbee.rx_queue.as_slice(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]
+        );
+
+        // Nothing can be sent while the RX queue is full and the radio has more to send
+        xbee.rx_queue.extend([0x00; 506].iter().cloned());
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        xbee.tx_queue.extend(frame.iter().cloned());
+        match xbee.flush_tx(3) {
+            Err(FlushError::Rx(XBeeApiError::RxOverflow)) => {}
+            _ => panic!("expected an RX overflow"),
+        }
+        assert_eq!(xbee.tx_queue.len(), frame.len());
+
+        // Once there is room, the frame left over goes out first
+        xbee.rx_queue.clear();
+        xbee.send_frame_blocking(Addr::Short(0x1234), b"hi")
+            .unwrap();
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(radio.sent().len(), 3 * frame.len());
+
+        // Not sent while the RX queue is full, even with nothing more from the radio
+        xbee.rx_queue.extend([0x00; 506].iter().cloned());
+        match xbee.send_frame_blocking(Addr::Short(0x1234), b"hi") {
+            Err(FlushError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(xbee.tx_queue.len(), frame.len());
+    }
+
+    #[test]
+    fn flush_tx_burst_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x00, Addr::Short(0x1234), b"hi").unwrap();
+            sender.send_data(0x00, Addr::Short(0x1234), b"yo").unwrap();
+        }
+        let frames = [
+            0x7E, 0x00, 0x07, 0x01, 0x00, 0x12, 0x34, 0x00, b'h', b'i', 0xE7, 0x7E, 0x00, 0x07,
+            0x01, 0x00, 0x12, 0x34, 0x00, b'y', b'o', 0xD0,
+        ];
+
+        // A modem status waiting to be read does not get the radio clocked any further
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        assert_eq!(xbee.flush_tx_burst().unwrap(), frames.len());
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(radio.cs_asserts(), 1);
+        assert_eq!(radio.sent().as_slice(), &frames[..]);
+
+        // Nothing queued, nothing sent
+        assert_eq!(xbee.flush_tx_burst().unwrap(), 0);
+        assert_eq!(radio.cs_asserts(), 1);
+    }
+
+    #[test]
+    fn flush_tx_burst_full_rx_queue_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(0x00, Addr::Short(0x1234), &[0x55; 60])
+                .unwrap();
+        }
+        let frame: ArrayVec<[u8; 128]> = xbee.tx_queue.iter().cloned().collect();
+        let frame_len = frame.len();
+
+        // Only 12 bytes of room, while the radio sends a whole frame
+        xbee.rx_queue.extend([0x00; 500].iter().cloned());
+        radio.respond(&rx_frame(1));
+        match xbee.flush_tx_burst() {
+            Err(FlushError::Rx(XBeeApiError::RxOverflow)) => {}
+            _ => panic!("expected an RX overflow"),
+        }
+        // Stopped once the transport could hold no more either, with nothing dropped
+        assert!(xbee.rx_queue_full());
+        assert_eq!(&xbee.rx_queue.as_slice()[500..], &rx_frame(1)[..12]);
+        assert_eq!(xbee.stats().rx_overflows, 0);
+        assert_eq!(radio.sent().len(), 12 + READ_CHUNK_SIZE);
+        assert_eq!(xbee.tx_queue.len(), frame_len - radio.sent().len());
+        assert!(!radio.cs_held());
+
+        // Picks up where it stopped once there is room
+        xbee.rx_queue.remove(0, 500);
+        xbee.flush_tx_burst().unwrap();
+        while xbee.transmit_and_receive().unwrap() {}
+        assert_eq!(&radio.sent()[..frame_len], &frame[..]);
+        assert_eq!(xbee.rx_queue.as_slice(), &rx_frame(1)[..]);
+    }
+
+    #[test]
+    fn transmit_frame_now_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+
+        // More is received while sending than the transport can hold on to
+        radio.respond(&rx_frame(1));
+        let payload = [0x55; 50];
+        let tx_request = TxRequestIter::new(
+            0x01,
+            Addr::Short(0x1234),
+            TxOptions::empty(),
+            payload.iter().cloned(),
+        );
+        let frame: ArrayVec<[u8; 64]> = FramePacker::new(tx_request, false, false)
+            .unwrap()
+            .collect();
+
+        let tx_request = TxRequestIter::new(
+            0x01,
+            Addr::Short(0x1234),
+            TxOptions::empty(),
+            payload.iter().cloned(),
+        );
+        xbee.transmit_frame_now(tx_request).unwrap();
+        assert!(xbee.tx_queue_empty());
+        // The frame is sent in one piece, followed by idle fill to read the rest
+        let sent = radio.sent();
+        assert_eq!(&sent[..59], &frame[..]);
+        assert!(sent[59..].iter().all(|&b| b == 0xFF));
+        assert_eq!(xbee.rx_queue.as_slice(), &rx_frame(1)[..]);
+
+        // Queued behind a transmission in progress
+        xbee.tx_queue.push_back(0x00).unwrap();
+        let tx_request = TxRequestIter::new(
+            0x01,
+            Addr::Short(0x1234),
+            TxOptions::empty(),
+            payload.iter().cloned(),
+        );
+        xbee.transmit_frame_now(tx_request).unwrap();
+        assert_eq!(xbee.tx_queue.len(), 60);
+        assert_eq!(radio.sent().len(), sent.len());
+    }
+
+    #[test]
+    fn poll_holds_cs_until_done_test() {
+        let radio = MockRadio::new();
+        radio.stall_alternately(true);
+
+        let mut spi = radio.spi();
+        let mut attn: MockAttn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+        xbee.tx_queue.extend([0x01, 0x02].iter().cloned());
+
+        assert!(!block!(xbee.poll()).ok().unwrap());
+        assert_eq!(radio.cs_asserts(), 1);
+        assert_eq!(radio.sent().as_slice(), &[0x01, 0x02]);
+    }
+
+    /// Sends a frame while two are received, returning what was sent and received.
+    fn exchange_frames<T>(xbee: &mut XBeeApi<T>) -> ArrayVec<[u8; 512]>
+    where
+        T: XBeeTransport,
+        T::Error: core::fmt::Debug,
+    {
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(0x01, Addr::Short(0x1234), &[0x55; 60])
+                .unwrap();
+        }
+        while xbee.transmit_and_receive().unwrap() {}
+        assert!(xbee.tx_queue_empty());
+        xbee.rx_queue.iter().cloned().collect()
+    }
+
+    #[test]
+    fn spi_transfer_test() {
+        let radio = MockRadio::new();
+        radio.respond(&rx_frame(1));
+        radio.respond(&rx_frame(2));
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpi::new(&mut spi, Some(&mut cs), &mut attn);
+        let received = exchange_frames(&mut xbee);
+        let sent = radio.sent();
+        assert_eq!(&received[..100], &rx_frame(1)[..]);
+        assert_eq!(&received[100..], &rx_frame(2)[..]);
+
+        // Smaller chunks interleave reads with writes differently, but the same bytes make it
+        // across whatever the chunk size
+        let frames = |sent: &[u8]| -> ArrayVec<[u8; 512]> {
+            sent.iter().cloned().filter(|&b| b != 0xFF).collect()
+        };
+        for &size in &[READ_CHUNK_SIZE, 5, 1] {
+            let radio = MockRadio::new();
+            radio.respond(&rx_frame(1));
+            radio.respond(&rx_frame(2));
+            let mut spi = radio.spi();
+            let mut attn = radio.attn();
+            let mut cs = radio.cs();
+            let mut xbee = XBeeApiSpiTransfer::new(&mut spi, Some(&mut cs), &mut attn);
+            xbee.set_chunk_size(size);
+            assert_eq!(exchange_frames(&mut xbee), received);
+            if size == READ_CHUNK_SIZE {
+                assert_eq!(radio.sent(), sent);
+            }
+            assert_eq!(frames(&radio.sent()), frames(&sent));
+            assert!(!radio.cs_held());
+        }
+    }
+
+    #[test]
+    fn uart_partial_frame_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+
+        // Noise before the frame, then only part of the header
+        serial.respond(&[0x00, 0x7E, 0x00]);
+        assert_eq!(xbee.poll_read(), Ok(3));
+        assert_eq!(xbee.poll_read(), Ok(0));
+        {
+            let (_, receiver) = xbee.get_sender_receiver();
+            assert!(receiver.unpack_and_parse_buffer().is_err());
+        }
+
+        serial.respond(&[0x02, 0x8A]);
+        assert_eq!(xbee.poll_read(), Ok(2));
+        serial.respond(&[0x00, 0x75]);
+        assert_eq!(xbee.poll_read(), Ok(2));
+
+        let (_, receiver) = xbee.get_sender_receiver();
+        match receiver.unpack_and_parse_buffer() {
+            Ok(ApiData::ModemStatus {
+                status: ModemStatus::HardwareReset,
+            }) => {}
+            _ => panic!("expected a modem status frame"),
+        }
+        assert_eq!(receiver.as_slice(), &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+    }
+
+    #[test]
+    fn stats_test() {
+        let serial = MockSerial::new();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond(&[
+            // noise
+            0x00,
+            0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75,
+            // bad checksum
+            0x7E, 0x00, 0x02, 0x8A, 0x00, 0x74,
+            0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74,
+        ]);
+
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        assert!(xbee.transmit_and_receive().unwrap());
+        {
+            let (mut sender, mut receiver) = xbee.get_sender_receiver();
+            while !receiver.queue_empty() {
+                let _ = receiver.unpack_and_parse_buffer();
+                receiver.remove_until_next_packet();
+            }
+
+            sender.send_data(0x01, Addr::Short(0x0001), b"hi").unwrap();
+            sender.send_data_raw(&[0x00; 500]).unwrap();
+            assert!(sender.send_data(0x02, Addr::Short(0x0001), b"hi").is_err());
+        }
+
+        assert_eq!(
+            xbee.stats(),
+            XBeeStats {
+                bytes_received: 19,
+                frames_parsed: 2,
+                checksum_failures: 1,
+                resync_bytes: 6,
+                rx_overflows: 0,
+                tx_frames_queued: 1,
+                tx_queue_full: 1,
+            }
+        );
+
+        xbee.clear_stats();
+        assert_eq!(xbee.stats(), XBeeStats::default());
+    }
+
+    #[test]
+    fn command_mode_flush_test() {
+        let serial = MockSerial::new();
+        serial.respond(b"OK\r");
+
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+        xbee.enter_command_mode().unwrap();
+        // Flushed once all three command characters were written
+        assert_eq!(serial.flushes(), (1, 3));
+
+        xbee.write(b'A').unwrap();
+        xbee.flush().unwrap();
+        assert_eq!(serial.flushes(), (2, 4));
+    }
+
+    #[test]
+    fn transparent_set_encryption_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        let key = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ];
+        serial.respond(b"OK\rOK\rOK\rOK\r");
+        xbee.set_encryption(Some(&key)).unwrap();
+        assert_eq!(
+            serial.written().as_slice(),
+            b"+++ATKY000102030405060708090A0B0C0D0E0F\rATEE01\rATCN\r".as_ref()
+        );
+        assert!(!xbee.in_command_mode());
+
+        // Turning encryption off leaves the key alone
+        serial.respond(b"OK\rOK\rOK\r");
+        xbee.set_encryption(None).unwrap();
+        assert!(serial.written().ends_with(b"ATCN\r+++ATEE00\rATCN\r"));
+
+        // A refused key leaves encryption as it was
+        serial.respond(b"OK\rERROR\rOK\r");
+        match xbee.set_encryption(Some(&key)) {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse KY"),
+        }
+        assert!(serial.written().ends_with(b"0E0F\rATCN\r"));
+    }
+
+    #[test]
+    fn transparent_soft_reset_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        // The radio comes back out of command mode by itself
+        serial.respond(b"OK\rOK\r");
+        xbee.soft_reset().unwrap();
+        assert_eq!(serial.written().as_slice(), b"+++ATFR\r".as_ref());
+        assert!(!xbee.in_command_mode());
+
+        // Refused, so command mode is left again
+        serial.respond(b"OK\rERROR\rOK\r");
+        match xbee.soft_reset() {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse FR"),
+        }
+        assert!(serial.written().ends_with(b"+++ATFR\rATCN\r"));
+        assert!(!xbee.in_command_mode());
+
+        serial.respond(b"OK\rOK\rOK\r");
+        xbee.network_reset().unwrap();
+        assert!(serial.written().ends_with(b"+++ATNR\rATCN\r"));
+    }
+
+    #[test]
+    fn transparent_power_level_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        serial.respond(b"OK\r");
+        xbee.set_power_level(PowerLevel::High).unwrap();
+        assert_eq!(serial.written().as_slice(), b"ATPL03\r");
+
+        serial.respond(b"3\r");
+        assert_eq!(xbee.power_level().unwrap(), PowerLevel::High);
+        assert_eq!(&serial.written()[7..], b"ATPL\r");
+
+        // Not a power level
+        serial.respond(b"7\r");
+        match xbee.power_level() {
+            Err(CommandError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+        serial.respond(b"ERROR\r");
+        match xbee.set_power_level(PowerLevel::Highest) {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse"),
+        }
+
+        serial.respond(b"OK\r");
+        xbee.set_boost_mode(false).unwrap();
+        assert!(serial.written().ends_with(b"ATPM00\r"));
+        serial.respond(b"0\r");
+        assert!(!xbee.boost_mode().unwrap());
+    }
+
+    #[test]
+    fn send_command_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        // Parameters are written out in hex
+        serial.respond(b"OK\r");
+        let response = xbee.send_command(*b"DL", Some(&[0x12, 0x3A])).unwrap();
+        assert_eq!(response.as_slice(), b"OK");
+        assert_eq!(serial.written().as_slice(), b"ATDL123A\r");
+
+        serial.respond(b"123A\r");
+        let response = xbee.send_command(*b"DL", None).unwrap();
+        assert_eq!(response.as_slice(), b"123A");
+        assert!(serial.written().ends_with(b"ATDL\r"));
+
+        serial.respond(b"ERROR\r");
+        match xbee.send_command(*b"DL", Some(&[0xFF; 5])) {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse"),
+        }
+
+        // Longer than MAX_COMMAND_RESPONSE
+        serial.respond(b"0123456789ABCDEF0\rOK\r");
+        match xbee.send_command(*b"NI", None) {
+            Err(CommandError::InvalidResponse) => {}
+            _ => panic!("expected an invalid response"),
+        }
+        // The whole line was taken, so the next answer is read as it should be
+        assert_eq!(xbee.send_command(*b"AC", None).unwrap().as_slice(), b"OK");
+    }
+
+    #[test]
+    fn command_session_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        serial.respond(b"OK\r3\rOK\r");
+        let level = xbee
+            .command_session(|session| session.power_level())
+            .unwrap()
+            .unwrap();
+        assert_eq!(level, PowerLevel::High);
+        assert_eq!(serial.written().as_slice(), b"+++ATPL\rATCN\r");
+
+        // Left even when the session gives up early
+        serial.respond(b"OK\rERROR\rOK\r");
+        let ret = xbee
+            .command_session(|session| {
+                session.send_command(*b"ZZ", Some(&[0x01]))?;
+                session.send_command(*b"PL", Some(&[0x04]))
+            })
+            .unwrap();
+        match ret {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse ZZ"),
+        }
+        assert!(serial.written().ends_with(b"+++ATZZ01\rATCN\r"));
+
+        serial.respond(b"OK\r");
+        xbee.exit_command_mode().unwrap();
+        assert!(serial.written().ends_with(b"ATCN\rATCN\r"));
+    }
+
+    #[test]
+    fn destination_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        serial.respond(b"OK\rOK\rOK\rOK\r");
+        xbee.set_destination(Addr::Long(0x0013_A200_415D_1DBB))
+            .unwrap();
+        assert_eq!(
+            serial.written().as_slice(),
+            b"+++ATDH0013A200\rATDL415D1DBB\rATCN\r".as_ref()
+        );
+        assert!(!xbee.in_command_mode());
+
+        serial.respond(b"OK\r13A200\r415D1DBB\rOK\r");
+        assert_eq!(
+            xbee.destination().unwrap(),
+            Addr::Long(0x0013_A200_415D_1DBB)
+        );
+        assert!(serial.written().ends_with(b"+++ATDH\rATDL\rATCN\r"));
+
+        // Already in command mode, so it is neither entered nor left, and can be committed
+        serial.respond(b"OK\rOK\rOK\rOK\r0\r1234\r");
+        xbee.enter_command_mode().unwrap();
+        assert!(xbee.in_command_mode());
+        xbee.set_destination(Addr::Short(0x1234)).unwrap();
+        xbee.commit_to_flash().unwrap();
+        assert_eq!(xbee.destination().unwrap(), Addr::Short(0x1234));
+        assert!(serial
+            .written()
+            .ends_with(b"+++ATDH00000000\rATDL00001234\rATWR\rATDH\rATDL\r"));
+
+        serial.respond(b"OK\rOK\r0\rFFFF\r");
+        xbee.set_destination(Addr::Broadcast).unwrap();
+        assert!(serial.written().ends_with(b"ATDL0000FFFF\r"));
+        assert_eq!(xbee.destination().unwrap(), Addr::Broadcast);
+
+        // A refused write still leaves command mode when it was entered for it
+        serial.respond(b"OK\rOK\rERROR\rOK\r");
+        xbee.exit_command_mode().unwrap();
+        match xbee.set_destination(Addr::Short(0x0001)) {
+            Err(CommandError::Error) => {}
+            _ => panic!("expected the radio to refuse DH"),
+        }
+        assert!(serial.written().ends_with(b"+++ATDH00000000\rATCN\r"));
+        assert!(!xbee.in_command_mode());
+    }
+
+    #[test]
+    fn change_baud_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+
+        for val in 0..8 {
+            let baud = BaudRate::try_from(val).unwrap();
+            serial.respond(b"OK\rOK\rOK\r");
+            let pending = xbee.change_baud(baud, false).unwrap();
+            assert_eq!(pending.baud(), baud);
+            let mut expected = *b"+++ATBD00\rATCN\r";
+            expected[8] = b'0' + val;
+            assert!(serial.written().ends_with(&expected));
+            assert!(!xbee.in_command_mode());
+        }
+
+        serial.respond(b"OK\rOK\rOK\rOK\r");
+        let pending = xbee.change_baud(BaudRate::Baud115200, true).unwrap();
+        assert_eq!(pending.baud().bps(), 115_200);
+        assert!(serial.written().ends_with(b"+++ATBD07\rATWR\rATCN\r"));
+
+        // Refused, so command mode is left with the rate unchanged
+        serial.respond(b"OK\rERROR\rOK\r");
+        match xbee.change_baud(BaudRate::Baud9600, true) {
+            Err(BaudChangeError::Command(CommandError::Error)) => {}
+            _ => panic!("expected the radio to refuse BD"),
+        }
+        assert!(serial.written().ends_with(b"+++ATBD03\rATCN\r"));
+
+        // Failing to write to flash still leaves command mode, switching the radio over
+        serial.respond(b"OK\rOK\rERROR\rOK\r");
+        match xbee.change_baud(BaudRate::Baud9600, true) {
+            Err(BaudChangeError::NotPersisted(pending, CommandError::Error)) => {
+                assert_eq!(pending.baud(), BaudRate::Baud9600)
+            }
+            _ => panic!("expected the radio to refuse WR"),
+        }
+        assert!(serial.written().ends_with(b"+++ATBD03\rATWR\rATCN\r"));
+        assert!(!xbee.in_command_mode());
+    }
+
+    #[test]
+    fn default_timing_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+        assert_eq!(xbee.cmd_char(), b'+');
+        assert_eq!(xbee.guard_time(), 1000);
+        assert_eq!(xbee.response_timeout(), DEFAULT_RESPONSE_TIMEOUT_MS);
+
+        xbee.set_cmd_char(b'-');
+        xbee.set_guard_time(50);
+        xbee.set_response_timeout(20);
+        serial.respond(b"OK\r");
+        xbee.enter_command_mode().unwrap();
+        assert_eq!(serial.written().as_slice(), b"---");
+        assert_eq!(serial.delayed_ms(), 50);
+    }
+
+    #[test]
+    fn read_timing_from_module_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+        xbee.set_guard_time(MAX_GUARD_TIME);
+
+        serial.respond(b"OK\r2D\r1F4\rOK\r");
+        xbee.read_timing_from_module().unwrap();
+        assert_eq!(serial.written().as_slice(), b"+++ATCC\rATGT\rATCN\r");
+        assert_eq!(xbee.cmd_char(), b'-');
+        assert_eq!(xbee.guard_time(), 500);
+
+        // Already in command mode, so it is left as it is
+        serial.respond(b"OK\r2B\r3E8\r");
+        xbee.enter_command_mode().unwrap();
+        xbee.read_timing_from_module().unwrap();
+        assert!(serial.written().ends_with(b"---ATCC\rATGT\r"));
+        assert_eq!(xbee.cmd_char(), b'+');
+        assert_eq!(xbee.guard_time(), 1000);
+        assert!(xbee.in_command_mode());
+
+        // A guard time too long for GT is not used
+        serial.respond(b"2B\r10000\r");
+        match xbee.read_timing_from_module() {
+            Err(CommandError::InvalidResponse) => {}
+            _ => panic!("expected the guard time to be refused"),
+        }
+        assert_eq!(xbee.guard_time(), 1000);
+    }
+
+    #[test]
+    fn write_packet_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+        // 3 characters at 9600 baud
+        assert_eq!(xbee.packetization_time(), 4);
+
+        xbee.write_packet(b"Testing").unwrap();
+        assert_eq!(serial.written().as_slice(), b"Testing");
+        assert_eq!(serial.flushes(), (1, 7));
+        assert_eq!(serial.delayed_ms(), 4);
+        assert_eq!(serial.written_at_delay(), 7);
+
+        xbee.set_packetization_timeout(0xFF, BaudRate::Baud1200);
+        assert_eq!(xbee.packetization_time(), 2125);
+        xbee.set_packetization_timeout(3, BaudRate::Baud115200);
+        assert_eq!(xbee.packetization_time(), 1);
+        xbee.write_packet(b"Again").unwrap();
+        assert_eq!(serial.delayed_ms(), 5);
+        assert_eq!(serial.written_at_delay(), 12);
+
+        // Passed through to the UART
+        xbee.bwrite_all(b"!").unwrap();
+        xbee.bflush().unwrap();
+        assert_eq!(serial.written().as_slice(), b"TestingAgain!");
+        assert_eq!(serial.flushes(), (3, 13));
+    }
+
+    #[test]
+    fn flow_control_cts_test() {
+        let serial = MockSerial::new();
+        let cts = MockLine::new(true);
+        let mut timer = serial.delay();
+        let mut xbee = XBeeApiUartFlowControl::with_flow_control(
+            serial.port(),
+            Some(cts.pin()),
+            None::<NoPin>,
+        );
+
+        // Nothing is written while the radio deasserts CTS
+        xbee.get_sender_receiver()
+            .0
+            .send_data_raw(b"Hello")
+            .unwrap();
+        assert!(matches!(
+            xbee.transmit_and_receive_timeout(&mut timer, 2),
+            Err(FlushError::Timeout)
+        ));
+        assert!(serial.written().is_empty());
+
+        cts.set_high(false);
+        xbee.transmit_and_receive_timeout(&mut timer, 2).unwrap();
+        assert_eq!(serial.written().as_slice(), b"Hello");
+
+        // The transparent mode driver waits on CTS the same way
+        let (port, cts_pin, _) = xbee.free();
+        let mut uart = FlowControl::new(port, cts_pin, None::<NoPin>);
+        cts.set_high(true);
+        assert!(matches!(uart.write(b'!'), Err(nb::Error::WouldBlock)));
+        cts.set_high(false);
+        let mut xbee = XBeeTransparent::default_timing(&mut uart, &mut timer);
+        xbee.write_packet(b" again").unwrap();
+        assert_eq!(serial.written().as_slice(), b"Hello again");
+    }
+
+    #[test]
+    fn flow_control_rts_test() {
+        let serial = MockSerial::new();
+        respond_overflow(&serial);
+        let rts = MockLine::new(true);
+        let mut xbee = XBeeApiUartFlowControl::with_flow_control(
+            serial.port(),
+            None::<MockLinePin>,
+            Some(rts.pin()),
+        );
+        xbee.set_rts_thresholds(100, 300);
+        assert!(!xbee.rts_asserted());
+
+        // Deasserted once the queue fills past the high water mark
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Rx(XBeeApiError::RxOverflow)) => {}
+            _ => panic!("expected an RX overflow"),
+        }
+        assert!(xbee.rx_queue.len() >= 300);
+        assert!(!xbee.rts_asserted());
+        assert!(rts.is_high());
+
+        // Still deasserted above the low water mark
+        {
+            let (_, mut receiver) = xbee.get_sender_receiver();
+            for _ in 0..3 {
+                receiver.remove_until_next_packet();
+            }
+        }
+        xbee.transmit_and_receive().unwrap();
+        assert!(xbee.rx_queue.len() > 100);
+        assert!(rts.is_high());
+
+        // And asserted again once the queue drains
+        xbee.get_sender_receiver().1.clear();
+        xbee.transmit_and_receive().unwrap();
+        assert!(xbee.rts_asserted());
+        assert!(!rts.is_high());
+    }
+
+    #[test]
+    fn switch_to_api_mode_test() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        const VR_FRAME: [u8; 8] = [0x7E, 0x00, 0x04, 0x08, 0x01, b'V', b'R', 0x4E];
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        const VR_RESPONSE: [u8; 11] = [
+            0x7E, 0x00, 0x07, 0x88, 0x01, b'V', b'R', 0x00, 0x20, 0x03, 0xAB,
+        ];
+
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::default_timing(&mut port, &mut delay);
+        serial.respond(b"OK\rOK\rOK\rOK\r");
+        serial.respond_after(20, &VR_RESPONSE);
+        xbee.switch_to_api_mode().unwrap();
+        let written = serial.written();
+        assert_eq!(&written[..20], b"+++ATAP01\rATWR\rATCN\r");
+        assert_eq!(&written[20..], &VR_FRAME[..]);
+
+        let (mut xbee, _) = xbee.into_api();
+        serial.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        xbee.transmit_and_receive().unwrap();
+        let (_, receiver) = xbee.get_sender_receiver();
+        match receiver.unpack_and_parse_buffer() {
+            Ok(ApiData::ModemStatus { .. }) => {}
+            _ => panic!("expected a modem status"),
+        }
+    }
+
+    #[test]
+    fn switch_to_api_mode_already_api_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 10);
+        xbee.set_response_timeout(20);
+
+        // Only the VR frame is answered
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        serial.respond_after(4, &[
+            0x7E, 0x00, 0x07, 0x88, 0x01, b'V', b'R', 0x00, 0x20, 0x03, 0xAB,
+        ]);
+        xbee.switch_to_api_mode().unwrap();
+        assert_eq!(serial.written().len(), 3 + 8);
+
+        // Another baud rate garbles whatever the radio sends
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 10);
+        xbee.set_response_timeout(20);
+        serial.respond(&[0xF8, 0x80, 0x1E]);
+        serial.respond_after(4, &[0xF8, 0x7E, 0x86, 0x00]);
+        match xbee.switch_to_api_mode() {
+            Err(ApiModeError::BaudMismatch) => {}
+            _ => panic!("expected a baud rate mismatch"),
+        }
+
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 10);
+        xbee.set_response_timeout(20);
+        match xbee.switch_to_api_mode() {
+            Err(ApiModeError::NoResponse) => {}
+            _ => panic!("expected no response"),
+        }
+    }
+
+    #[test]
+    fn command_timeout_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+        xbee.set_response_timeout(20);
+        assert_eq!(xbee.response_timeout(), 20);
+
+        // A radio that never answers
+        match xbee.exit_command_mode() {
+            Err(CommandError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 20);
+
+        // Nor finishes answering
+        serial.respond(b"4");
+        match xbee.power_level() {
+            Err(CommandError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms(), 40);
+
+        // An answer already waiting needs no time at all
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+        xbee.set_response_timeout(0);
+        serial.respond(b"OK\r");
+        xbee.exit_command_mode().unwrap();
+        assert_eq!(serial.delayed_ms(), 0);
+    }
+
+    #[test]
+    fn guard_time_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+        assert_eq!(xbee.guard_time(), 1000);
+
+        xbee.set_guard_time(50);
+        assert_eq!(xbee.guard_time(), 50);
+        serial.respond(b"OK\r");
+        xbee.enter_command_mode().unwrap();
+        assert_eq!(serial.delayed_ms(), 50);
+
+        xbee.set_guard_time(0);
+        assert_eq!(xbee.guard_time(), MIN_GUARD_TIME);
+        xbee.set_guard_time(u16::MAX);
+        assert_eq!(xbee.guard_time(), MAX_GUARD_TIME);
+    }
+
+    #[test]
+    fn enter_command_mode_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let mut xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 100);
+        xbee.set_response_timeout(50);
+
+        // Data the radio was still sending is dropped
+        serial.respond(b"data\r\nOK\r");
+        xbee.enter_command_mode().unwrap();
+        assert_eq!(serial.delayed_ms(), 100);
+
+        // A serial error partway through waiting
+        serial.respond(b"xOK\r");
+        serial.fail_read_at(12);
+        match xbee.enter_command_mode() {
+            Err(CommandModeError::Serial(MockError)) => {}
+            _ => panic!("expected a serial error"),
+        }
+
+        // Garbage that looks like the start of the answer is dropped too
+        serial.respond(b"HELLO\rOK\r");
+        xbee.enter_command_mode().unwrap();
+        serial.respond(b"OOK\r");
+        xbee.enter_command_mode().unwrap();
+
+        // A radio that never answers is waited on for the guard time and response timeout
+        serial.respond(b"\r");
+        let delayed = serial.delayed_ms();
+        match xbee.enter_command_mode() {
+            Err(CommandModeError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+        assert_eq!(serial.delayed_ms() - delayed, 100 + 150);
+    }
+
+    #[test]
+    fn transparent_release_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut delay = serial.delay();
+        let xbee = XBeeTransparent::new(&mut port, &mut delay, b'+', 1000);
+        let (uart, timer) = xbee.release();
+        uart.write(b'A').unwrap();
+        timer.delay_ms(5);
+        assert_eq!(serial.written().as_slice(), b"A");
+        assert_eq!(serial.delayed_ms(), 5);
+
+        // The borrows have ended
+        port.write(b'B').unwrap();
+        assert_eq!(serial.written().as_slice(), b"AB");
+    }
+
+    /// 100 byte RX packet frame with `id` as its source address.
+    fn rx_frame(id: u8) -> ArrayVec<[u8; 100]> {
+        let mut data: ArrayVec<[u8; 96]> = ArrayVec::new();
+        data.extend([0x81, 0x00, id, 0x28, 0x00].iter().cloned());
+        data.extend([0x55; 91].iter().cloned());
+        FramePacker::new(data.into_iter(), false, false)
+            .unwrap()
+            .collect()
+    }
+
+    /// Sends six 100 byte frames, five of which fit in the 512 byte RX queue.
+    fn respond_overflow(serial: &MockSerial) {
+        for id in 1..7 {
+            serial.respond(&rx_frame(id));
+        }
+    }
+
+    #[test]
+    fn rx_too_long_test() {
+        let serial = MockSerial::new();
+        // Claims to be longer than the whole RX queue
+        serial.respond(&[0x7E, 0x03, 0x00]);
+        serial.respond(&[0x00; 0x300 + 1]);
+        serial.respond(&rx_frame(1));
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.as_slice(), &rx_frame(1)[..]);
+        assert_eq!(xbee.stats().rx_overflows, 1);
+    }
+
+    #[test]
+    fn rx_wrap_test() {
+        let serial = MockSerial::new();
+        respond_overflow(&serial);
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        // The queue fills partway through the sixth frame
+        match xbee.transmit_and_receive() {
+            Err(FlushError::Rx(XBeeApiError::RxOverflow)) => {}
+            _ => panic!("expected an RX overflow"),
+        }
+        {
+            let (_, mut receiver) = xbee.get_sender_receiver();
+            for _ in 0..4 {
+                receiver.remove_until_next_packet();
+            }
+        }
+
+        // The sixth frame is received across the end of the queue's storage
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.as_slice().len(), 200);
+        let (_, mut receiver) = xbee.get_sender_receiver();
+        for id in 5..7 {
+            match receiver.unpack_and_parse_buffer() {
+                Ok(ApiData::RxPacket16Addr { source_addr, .. }) => {
+                    assert_eq!(source_addr, id as u16)
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(&receiver.as_slice()[..100], &rx_frame(id)[..]);
+            receiver.remove_until_next_packet();
+        }
+        assert!(receiver.queue_empty());
+    }
+
+    #[test]
+    fn rx_overflow_test() {
+        let serial = MockSerial::new();
+        respond_overflow(&serial);
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        xbee.set_rx_overflow_policy(RxOverflowPolicy::DropIncoming);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.len(), 500);
+        assert_eq!(&xbee.rx_queue.as_slice()[..100], &rx_frame(1)[..]);
+        assert_eq!(&xbee.rx_queue.as_slice()[400..], &rx_frame(5)[..]);
+        assert_eq!(xbee.stats().rx_overflows, 1);
+
+        let serial = MockSerial::new();
+        respond_overflow(&serial);
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        xbee.set_rx_overflow_policy(RxOverflowPolicy::DropOldestFrame);
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.len(), 500);
+        assert_eq!(&xbee.rx_queue.as_slice()[..100], &rx_frame(2)[..]);
+        assert_eq!(&xbee.rx_queue.as_slice()[400..], &rx_frame(6)[..]);
+        assert_eq!(xbee.stats().rx_overflows, 1);
+
+        let serial = MockSerial::new();
+        respond_overflow(&serial);
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        // Reading stops partway through the sixth frame until a frame is taken out
+        for _ in 0..2 {
+            match xbee.transmit_and_receive() {
+                Err(FlushError::Rx(XBeeApiError::RxOverflow)) => {}
+                _ => panic!("expected an RX overflow"),
+            }
+        }
+        assert!(xbee.rx_queue_full());
+        assert_eq!(&xbee.rx_queue.as_slice()[400..500], &rx_frame(5)[..]);
+        // Counted once, however many times it is polled for
+        assert_eq!(xbee.stats().rx_overflows, 1);
+        {
+            let (_, mut receiver) = xbee.get_sender_receiver();
+            receiver.remove_until_next_packet();
+        }
+        assert!(xbee.transmit_and_receive().unwrap());
+        assert_eq!(xbee.rx_queue.len(), 500);
+        assert_eq!(&xbee.rx_queue.as_slice()[..100], &rx_frame(2)[..]);
+        assert_eq!(&xbee.rx_queue.as_slice()[400..], &rx_frame(6)[..]);
+    }
+
+    #[test]
+    fn uart_write_resumes_test() {
+        let serial = MockSerial::new();
+        serial.set_write_space(3);
+
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+        }
+
+        assert_eq!(xbee.poll_write(), Ok(3));
+        assert!(!xbee.tx_queue_empty());
+        serial.set_write_space(usize::MAX);
+        assert_eq!(xbee.poll_write(), Ok(5));
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(
+            serial.written().as_slice(),
+            &[0x7E, 0x00, 0x04, 0x08, 0x01, b'N', b'H', 0x60]
+        );
+    }
+
+    #[test]
+    fn set_api_mode_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+
+        xbee.set_api_mode(0x01, FrameFormat::Escaped).unwrap();
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(0x02, Addr::Short(0x1234), &[0x7E])
+                .unwrap();
+        }
+        xbee.poll_write().unwrap();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let expected = [
+            // AP itself goes out unescaped
+            0x7E, 0x00, 0x05, 0x08, 0x01, b'A', b'P', 0x02, 0x63,
+            0x7E, 0x00, 0x06, 0x01, 0x02, 0x12, 0x34, 0x00, 0x7D, 0x5E, 0x38,
+        ];
+        assert_eq!(serial.written().as_slice(), &expected[..]);
+
+        // Answers are unpacked in the new format too
+        serial.respond(&[0x7E, 0x00, 0x05, 0x88, 0x01, b'A', b'P', 0x00, 0xE5]);
+        serial.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x7D, 0x31, 0x64]);
+        xbee.poll_read().unwrap();
+        let (_, mut receiver) = xbee.get_sender_receiver();
+        receiver.remove_until_next_packet();
+        assert_eq!(
+            receiver.unpack_and_parse_buffer().unwrap(),
+            ApiData::ModemStatus {
+                status: ModemStatus::Unknown
+            }
+        );
+    }
+
+    #[test]
+    fn escaped_round_trip_test() {
+        let serial = MockSerial::new();
+        let mut port = serial.port();
+        let mut xbee = XBeeApiUart::new(&mut port);
+        xbee.set_frame_format(FrameFormat::Escaped);
+
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender
+                .send_data(0x01, Addr::Short(0x1234), &[0x7E, 0x7D, 0x11, 0x13])
+                .unwrap();
+            sender
+                .send_data(0x02, Addr::Short(0x1234), &[0x7E])
+                .unwrap();
+        }
+        xbee.poll_write().unwrap();
+        let written = serial.written();
+        // Only the two start delimiters are sent unescaped
+        assert_eq!(
+            written.iter().filter(|&&b| b == api_frame::START).count(),
+            2
+        );
+
+        // Loop the frames back, with some noise and a frame cut short in front
+        serial.respond(&[0x13, 0x7E, 0x00, 0x05, 0x01]);
+        serial.respond(&written);
+        xbee.poll_read().unwrap();
+
+        let (_, mut receiver) = xbee.get_sender_receiver();
+        match receiver.unpack_and_parse_buffer() {
+            Ok(ApiData::TxRequest16Addr { frame_id, data, .. }) => {
+                assert_eq!(frame_id, 0x01);
+                assert_eq!(data, &[0x7E, 0x7D, 0x11, 0x13]);
+            }
+            _ => panic!("expected a TX request"),
+        }
+
+        // The 0x7E in the first frame's data is not taken for the start of the next frame
+        assert_eq!(
+            receiver.remove_until_next_packet(),
+            Resync::Found { discarded: 13 }
+        );
+        match receiver.unpack_and_parse_buffer() {
+            Ok(ApiData::TxRequest16Addr { frame_id, data, .. }) => {
+                assert_eq!(frame_id, 0x02);
+                assert_eq!(data, &[0x7E]);
+            }
+            _ => panic!("expected a TX request"),
+        }
+    }
+
+    #[test]
+    fn send_data_iter_test() {
+        let mut slice_queue = ArrayDeque::new();
+        {
+            let mut sender: XBeeApiSender = XBeeApiSender {
+                tx_queue: &mut slice_queue,
+                format: FrameFormat::Unescaped,
+                counters: &mut TxCounters::default(),
+                retry: &mut TxRetry::default(),
+            };
+            sender
+                .send_data(0x01, Addr::Short(0x1234), &[0, 1, 2, 3, 4, 5, 6, 7])
+                .unwrap();
+        }
+
+        let mut iter_queue = ArrayDeque::new();
+        {
+            let mut sender: XBeeApiSender = XBeeApiSender {
+                tx_queue: &mut iter_queue,
+                format: FrameFormat::Unescaped,
+                counters: &mut TxCounters::default(),
+                retry: &mut TxRetry::default(),
+            };
+            sender
+                .send_data_iter(0x01, Addr::Short(0x1234), TxOptions::empty(), 0..8)
+                .unwrap();
+        }
+
+        assert!(slice_queue.iter().eq(iter_queue.iter()));
+    }
+
+    /// Transport that hands every byte written straight back to the reader.
+    struct Loopback {
+        buf: ArrayVec<[u8; 64]>,
+    }
+
+    impl XBeeTransport for Loopback {
+        type Error = ();
+
+        fn write(&mut self, buf: &[u8]) -> nb::Result<usize, ()> {
+            let len = buf.len().min(self.buf.capacity() - self.buf.len());
+            self.buf.extend(buf[..len].iter().cloned());
+            Ok(len)
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, ()> {
+            let len = buf.len().min(self.buf.len());
+            buf[..len].copy_from_slice(&self.buf[..len]);
+            self.buf.drain(..len);
+            Ok(len)
+        }
+
+        fn rx_pending(&mut self) -> Result<bool, ()> {
+            Ok(!self.buf.is_empty())
+        }
+    }
+
+    #[test]
+    fn custom_transport_test() {
+        let mut xbee = XBeeApi::with_transport(Loopback {
+            buf: ArrayVec::new(),
+        });
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+        }
+
+        assert!(xbee.transmit_and_receive().unwrap());
+        let (_, receiver) = xbee.get_sender_receiver();
+        assert_eq!(
+            receiver.unpack_and_parse_buffer().unwrap(),
+            ApiData::AtCommand {
+                frame_id: 0x01,
+                at_cmd: [b'N', b'H'],
+                params: &[],
+            }
+        );
+    }
+
+    /// Stand-in for the critical section based mutexes of embedded runtimes, such as
+    /// `cortex_m::interrupt::Mutex`, which only require their contents to be `Send`.
+    struct Mutex<T>(RefCell<T>);
+
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    struct OwnedSpi;
+
+    impl FullDuplex<u8> for OwnedSpi {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            Ok(0xFF)
+        }
+
+        fn send(&mut self, _: u8) -> nb::Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct OwnedPin;
+
+    impl InputPin for OwnedPin {
+        type Error = ();
+
+        fn is_high(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn is_low(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+    }
+
+    impl OutputPin for OwnedPin {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    static XBEE: Mutex<Option<XBeeApiSpi<OwnedSpi, OwnedPin, OwnedPin>>> =
+        Mutex(RefCell::new(None));
+
+    #[test]
+    fn owned_static_test() {
+        *XBEE.0.borrow_mut() = Some(XBeeApiSpi::take(OwnedSpi, Some(OwnedPin), OwnedPin));
+        {
+            let mut xbee = XBEE.0.borrow_mut();
+            let xbee = xbee.as_mut().unwrap();
+            {
+                let (mut sender, _) = xbee.get_sender_receiver();
+                sender.at_command(0x01, [b'N', b'H'], &[]).unwrap();
+            }
+            assert!(!xbee.transmit_and_receive().unwrap());
+            assert!(xbee.tx_queue_empty());
+        }
+
+        let (_spi, cs, _attn) = XBEE.0.borrow_mut().take().unwrap().free();
+        assert!(cs.is_some());
+    }
+
+    #[test]
+    fn borrowed_blocking_write_test() {
+        // A UART with its own blocking writes, such as one using DMA
+        struct DmaUart {
+            writes: ArrayVec<[usize; 4]>,
+            flushes: u32,
+        }
+
+        impl Read<u8> for DmaUart {
+            type Error = MockError;
+
+            fn read(&mut self) -> nb::Result<u8, MockError> {
+                Err(nb::Error::WouldBlock)
+            }
+        }
+
+        impl BlockingWrite<u8> for DmaUart {
+            type Error = MockError;
+
+            fn bwrite_all(&mut self, buffer: &[u8]) -> Result<(), MockError> {
+                self.writes.push(buffer.len());
+                Ok(())
+            }
+
+            fn bflush(&mut self) -> Result<(), MockError> {
+                self.flushes += 1;
+                Ok(())
+            }
+        }
+
+        let mut uart = DmaUart {
+            writes: ArrayVec::new(),
+            flushes: 0,
+        };
+        let serial = MockSerial::new();
+        let mut delay = serial.delay();
+        XBeeTransparent::default_timing(&mut uart, &mut delay)
+            .write_packet(b"Testing")
+            .unwrap();
+        assert_eq!(uart.writes.as_slice(), &[7]);
+        assert_eq!(uart.flushes, 1);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_tx_queue_test() {
+        let radio = MockRadio::new();
+        let mut spi = radio.spi();
+        let mut attn = radio.attn();
+        let mut cs = radio.cs();
+        let mut xbee = XBeeApiSpiHeapless::<_, _, _, 16>::take(
+            Borrowed(&mut spi),
+            Some(Borrowed(&mut cs)),
+            Borrowed(&mut attn),
+        );
+
+        {
+            let (mut sender, _) = xbee.get_sender_receiver();
+            sender.send_data(0x00, Addr::Short(0x1234), b"hi").unwrap();
+            // Only room for one frame
+            match sender.send_data(0x00, Addr::Short(0x1234), b"yo") {
+                Err(SendError::QueueFull) => {}
+                _ => panic!("expected the queue to be full"),
+            }
+        }
+        radio.respond(&[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]);
+        xbee.transmit_and_receive().unwrap();
+        assert!(xbee.tx_queue_empty());
+        assert_eq!(
+            radio.sent().as_slice(),
+            &[0x7E, 0x00, 0x07, 0x01, 0x00, 0x12, 0x34, 0x00, b'h', b'i', 0xE7]
+        );
+        assert_eq!(
+            xbee.rx_queue.as_slice(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x00, 0x75]
+        );
+    }
 }
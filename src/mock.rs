@@ -0,0 +1,750 @@
+//! Scripted peripherals used by the driver tests.
+
+// Not every test configuration uses every mock
+#![allow(dead_code)]
+
+use core::cell::{Cell, RefCell};
+
+use arrayvec::ArrayVec;
+use embedded_hal::blocking;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial;
+use embedded_hal::spi::FullDuplex;
+
+use crate::api_frame::FramePacker;
+
+/// Shared state of a simulated XBee on the other end of the SPI bus.
+///
+/// The radio asserts ATTN (drives it low) for as long as it has scripted bytes left to send.
+pub struct MockRadio {
+    pending: RefCell<ArrayVec<[u8; 1024]>>,
+    pending_pos: Cell<usize>,
+    latched: Cell<Option<u8>>,
+    sent: RefCell<ArrayVec<[u8; 512]>>,
+    stall: Cell<bool>,
+    stalled: Cell<bool>,
+    cs_asserts: Cell<usize>,
+    cs_held: Cell<bool>,
+    fail_attn: Cell<bool>,
+    fail_cs: Cell<bool>,
+    fail_spi_read: Cell<bool>,
+    stuck_attn: Cell<bool>,
+    attn_active_high: Cell<bool>,
+}
+
+impl MockRadio {
+    pub fn new() -> MockRadio {
+        MockRadio {
+            pending: RefCell::new(ArrayVec::new()),
+            pending_pos: Cell::new(0),
+            latched: Cell::new(None),
+            sent: RefCell::new(ArrayVec::new()),
+            stall: Cell::new(false),
+            stalled: Cell::new(false),
+            cs_asserts: Cell::new(0),
+            cs_held: Cell::new(false),
+            fail_attn: Cell::new(false),
+            fail_cs: Cell::new(false),
+            fail_spi_read: Cell::new(false),
+            stuck_attn: Cell::new(false),
+            attn_active_high: Cell::new(false),
+        }
+    }
+
+    /// Queues bytes for the radio to send to the host.
+    pub fn respond(&self, data: &[u8]) {
+        self.pending.borrow_mut().extend(data.iter().cloned());
+    }
+
+    /// Makes every other `send` and `read` call return `WouldBlock`.
+    pub fn stall_alternately(&self, stall: bool) {
+        self.stall.set(stall);
+    }
+
+    /// Makes reading the ATTN pin fail.
+    pub fn fail_attn(&self, fail: bool) {
+        self.fail_attn.set(fail);
+    }
+
+    /// Makes driving the CS pin fail, with an error type of its own to keep it apart from ATTN.
+    pub fn fail_cs(&self, fail: bool) {
+        self.fail_cs.set(fail);
+    }
+
+    /// Makes `FullDuplex::read` fail, with the byte clocked in kept for the next read.
+    pub fn fail_spi_read(&self, fail: bool) {
+        self.fail_spi_read.set(fail);
+    }
+
+    /// Keeps ATTN asserted even once the radio has nothing left to send.
+    pub fn stick_attn(&self, stuck: bool) {
+        self.stuck_attn.set(stuck);
+    }
+
+    /// Drives ATTN high rather than low while it is asserted, as on boards that invert it.
+    pub fn invert_attn(&self, active_high: bool) {
+        self.attn_active_high.set(active_high);
+    }
+
+    pub fn attn_asserted(&self) -> bool {
+        self.stuck_attn.get() || self.pending_pos.get() < self.pending.borrow().len()
+    }
+
+    pub fn sent(&self) -> ArrayVec<[u8; 512]> {
+        self.sent.borrow().clone()
+    }
+
+    pub fn cs_asserts(&self) -> usize {
+        self.cs_asserts.get()
+    }
+
+    pub fn cs_held(&self) -> bool {
+        self.cs_held.get()
+    }
+
+    pub fn spi(&self) -> MockSpi<'_> {
+        MockSpi { radio: self }
+    }
+
+    pub fn attn(&self) -> MockAttn<'_> {
+        MockAttn { radio: self }
+    }
+
+    pub fn cs(&self) -> MockCs<'_> {
+        MockCs { radio: self }
+    }
+
+    #[cfg(feature = "eh1")]
+    pub fn spi_device(&self) -> MockSpiDevice<'_> {
+        MockSpiDevice { radio: self }
+    }
+
+    /// Clocks one byte each way.
+    fn exchange(&self, byte: u8) -> u8 {
+        // A stuck ATTN line has far more clocked out than is kept
+        let _ = self.sent.borrow_mut().try_push(byte);
+
+        let pos = self.pending_pos.get();
+        match self.pending.borrow().get(pos) {
+            Some(&reply) => {
+                self.pending_pos.set(pos + 1);
+                reply
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn should_stall(&self) -> bool {
+        if !self.stall.get() {
+            return false;
+        }
+        let stalled = !self.stalled.get();
+        self.stalled.set(stalled);
+        stalled
+    }
+}
+
+pub struct MockSpi<'a> {
+    radio: &'a MockRadio,
+}
+
+impl<'a> FullDuplex<u8> for MockSpi<'a> {
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<u8, ()> {
+        if self.radio.should_stall() {
+            return Err(nb::Error::WouldBlock);
+        }
+        if self.radio.fail_spi_read.get() {
+            return Err(nb::Error::Other(()));
+        }
+        match self.radio.latched.take() {
+            Some(byte) => Ok(byte),
+            None => panic!("read without a preceding send"),
+        }
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), ()> {
+        if self.radio.should_stall() {
+            return Err(nb::Error::WouldBlock);
+        }
+        assert!(
+            self.radio.latched.get().is_none(),
+            "send before the previous byte was read"
+        );
+        let reply = self.radio.exchange(byte);
+        self.radio.latched.set(Some(reply));
+        Ok(())
+    }
+}
+
+impl<'a> blocking::spi::transfer::Default<u8> for MockSpi<'a> {}
+
+pub struct MockAttn<'a> {
+    radio: &'a MockRadio,
+}
+
+impl<'a> InputPin for MockAttn<'a> {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        if self.radio.fail_attn.get() {
+            return Err(());
+        }
+        Ok(self.radio.attn_asserted() == self.radio.attn_active_high.get())
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+pub struct MockCs<'a> {
+    radio: &'a MockRadio,
+}
+
+impl<'a> OutputPin for MockCs<'a> {
+    type Error = MockError;
+
+    fn set_low(&mut self) -> Result<(), MockError> {
+        if self.radio.fail_cs.get() {
+            return Err(MockError);
+        }
+        self.radio.cs_asserts.set(self.radio.cs_asserts.get() + 1);
+        self.radio.cs_held.set(true);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), MockError> {
+        if self.radio.fail_cs.get() {
+            return Err(MockError);
+        }
+        self.radio.cs_held.set(false);
+        Ok(())
+    }
+}
+
+/// Sleep pins of a simulated XBee in pin sleep, which wakes `wake_ms` milliseconds after SLEEP_RQ
+/// is released.
+pub struct MockSleep {
+    wake_ms: u32,
+    /// Time passed since SLEEP_RQ was released, or `None` while it is held.
+    since_wake_request: Cell<Option<u32>>,
+}
+
+impl MockSleep {
+    /// Starts out awake.
+    pub fn new(wake_ms: u32) -> MockSleep {
+        MockSleep {
+            wake_ms,
+            since_wake_request: Cell::new(Some(wake_ms)),
+        }
+    }
+
+    pub fn sleep_requested(&self) -> bool {
+        self.since_wake_request.get().is_none()
+    }
+
+    pub fn ms_since_wake_request(&self) -> Option<u32> {
+        self.since_wake_request.get()
+    }
+
+    pub fn sleep_rq(&self) -> MockSleepRq<'_> {
+        MockSleepRq { radio: self }
+    }
+
+    pub fn on_sleep(&self) -> MockOnSleep<'_> {
+        MockOnSleep { radio: self }
+    }
+
+    pub fn delay(&self) -> MockSleepDelay<'_> {
+        MockSleepDelay { radio: self }
+    }
+}
+
+pub struct MockSleepRq<'a> {
+    radio: &'a MockSleep,
+}
+
+impl<'a> OutputPin for MockSleepRq<'a> {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        if self.radio.sleep_requested() {
+            self.radio.since_wake_request.set(Some(0));
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.radio.since_wake_request.set(None);
+        Ok(())
+    }
+}
+
+pub struct MockOnSleep<'a> {
+    radio: &'a MockSleep,
+}
+
+impl<'a> InputPin for MockOnSleep<'a> {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        Ok(match self.radio.since_wake_request.get() {
+            Some(ms) => ms >= self.radio.wake_ms,
+            None => false,
+        })
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+pub struct MockSleepDelay<'a> {
+    radio: &'a MockSleep,
+}
+
+impl<'a> DelayMs<u16> for MockSleepDelay<'a> {
+    fn delay_ms(&mut self, ms: u16) {
+        let since = self.radio.since_wake_request.get();
+        self.radio
+            .since_wake_request
+            .set(since.map(|since| since + ms as u32));
+    }
+}
+
+/// A single wire, such as a flow control line, that one side drives and the other reads.
+pub struct MockLine {
+    high: Cell<bool>,
+}
+
+impl MockLine {
+    pub fn new(high: bool) -> MockLine {
+        MockLine {
+            high: Cell::new(high),
+        }
+    }
+
+    pub fn set_high(&self, high: bool) {
+        self.high.set(high);
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.high.get()
+    }
+
+    pub fn pin(&self) -> MockLinePin<'_> {
+        MockLinePin { line: self }
+    }
+}
+
+pub struct MockLinePin<'a> {
+    line: &'a MockLine,
+}
+
+impl<'a> InputPin for MockLinePin<'a> {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        Ok(self.line.is_high())
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        Ok(!self.line.is_high())
+    }
+}
+
+impl<'a> OutputPin for MockLinePin<'a> {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.line.set_high(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.line.set_high(true);
+        Ok(())
+    }
+}
+
+/// Shared state of a simulated XBee on the other end of a UART.
+pub struct MockSerial {
+    pending: RefCell<ArrayVec<[u8; 1024]>>,
+    pending_pos: Cell<usize>,
+    written: RefCell<ArrayVec<[u8; 512]>>,
+    write_space: Cell<usize>,
+    /// Number of flushes, and how many bytes had been written at the last one.
+    flushes: Cell<(u32, usize)>,
+    delayed_ms: Cell<u32>,
+    /// How many bytes had been written at the last delay.
+    written_at_delay: Cell<usize>,
+    read_error_at: Cell<Option<usize>>,
+    write_error_at: Cell<Option<usize>>,
+    /// Bytes queued by `respond_after`, and how many bytes have to be written first.
+    held: RefCell<ArrayVec<[u8; 64]>>,
+    held_until: Cell<usize>,
+}
+
+impl MockSerial {
+    pub fn new() -> MockSerial {
+        MockSerial {
+            pending: RefCell::new(ArrayVec::new()),
+            pending_pos: Cell::new(0),
+            written: RefCell::new(ArrayVec::new()),
+            write_space: Cell::new(usize::MAX),
+            flushes: Cell::new((0, 0)),
+            delayed_ms: Cell::new(0),
+            written_at_delay: Cell::new(0),
+            read_error_at: Cell::new(None),
+            write_error_at: Cell::new(None),
+            held: RefCell::new(ArrayVec::new()),
+            held_until: Cell::new(0),
+        }
+    }
+
+    /// Queues bytes for the radio to send to the host.
+    pub fn respond(&self, data: &[u8]) {
+        self.pending.borrow_mut().extend(data.iter().cloned());
+    }
+
+    /// Queues an answer to a local AT command, with the AT command status `status`.
+    pub fn respond_at(&self, frame_id: u8, at_cmd: [u8; 2], status: u8, data: &[u8]) {
+        let mut frame: ArrayVec<[u8; 32]> = ArrayVec::new();
+        frame.extend(
+            [0x88, frame_id, at_cmd[0], at_cmd[1], status]
+                .iter()
+                .cloned(),
+        );
+        frame.extend(data.iter().cloned());
+        let packed: ArrayVec<[u8; 32]> = FramePacker::new(frame.into_iter(), false, false)
+            .unwrap()
+            .collect();
+        self.respond(&packed);
+    }
+
+    /// Like `respond`, but only once the host has written `written` bytes in all, as a radio only
+    /// answering a request would.
+    pub fn respond_after(&self, written: usize, data: &[u8]) {
+        self.held.borrow_mut().extend(data.iter().cloned());
+        self.held_until.set(written);
+    }
+
+    pub fn written(&self) -> ArrayVec<[u8; 512]> {
+        self.written.borrow().clone()
+    }
+
+    /// Fails reading once when the byte at `pos` of everything queued by `respond` is next, with
+    /// that byte read as usual afterwards.
+    pub fn fail_read_at(&self, pos: usize) {
+        self.read_error_at.set(Some(pos));
+    }
+
+    /// Fails writing once when the byte at `pos` of everything written is next, with the byte
+    /// not written.
+    pub fn fail_write_at(&self, pos: usize) {
+        self.write_error_at.set(Some(pos));
+    }
+
+    /// Limits how many more bytes can be written before writes return `WouldBlock`.
+    pub fn set_write_space(&self, space: usize) {
+        self.write_space.set(space);
+    }
+
+    /// Number of flushes and how many bytes had been written by the last one.
+    pub fn flushes(&self) -> (u32, usize) {
+        self.flushes.get()
+    }
+
+    /// Total time spent in the delay provider.
+    pub fn delayed_ms(&self) -> u32 {
+        self.delayed_ms.get()
+    }
+
+    /// How many bytes had been written by the last delay.
+    pub fn written_at_delay(&self) -> usize {
+        self.written_at_delay.get()
+    }
+
+    pub fn port(&self) -> MockSerialPort<'_> {
+        MockSerialPort { serial: self }
+    }
+
+    pub fn delay(&self) -> MockDelay<'_> {
+        MockDelay { serial: self }
+    }
+
+    fn read(&self) -> nb::Result<u8, MockError> {
+        if self.written.borrow().len() >= self.held_until.get() {
+            let mut held = self.held.borrow_mut();
+            self.respond(&held);
+            held.clear();
+        }
+        let pos = self.pending_pos.get();
+        if self.read_error_at.get() == Some(pos) {
+            self.read_error_at.set(None);
+            return Err(nb::Error::Other(MockError));
+        }
+        match self.pending.borrow().get(pos) {
+            Some(&byte) => {
+                self.pending_pos.set(pos + 1);
+                Ok(byte)
+            }
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    fn write(&self, byte: u8) -> nb::Result<(), MockError> {
+        if self.write_error_at.get() == Some(self.written.borrow().len()) {
+            self.write_error_at.set(None);
+            return Err(nb::Error::Other(MockError));
+        }
+        match self.write_space.get() {
+            0 => return Err(nb::Error::WouldBlock),
+            space => self.write_space.set(space - 1),
+        }
+        self.written.borrow_mut().push(byte);
+        Ok(())
+    }
+
+    fn flush(&self) -> nb::Result<(), MockError> {
+        let (count, _) = self.flushes.get();
+        self.flushes.set((count + 1, self.written.borrow().len()));
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MockError;
+
+pub struct MockSerialPort<'a> {
+    serial: &'a MockSerial,
+}
+
+impl<'a> serial::Read<u8> for MockSerialPort<'a> {
+    type Error = MockError;
+
+    fn read(&mut self) -> nb::Result<u8, MockError> {
+        self.serial.read()
+    }
+}
+
+impl<'a> serial::Write<u8> for MockSerialPort<'a> {
+    type Error = MockError;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), MockError> {
+        self.serial.write(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), MockError> {
+        self.serial.flush()
+    }
+}
+
+impl<'a> blocking::serial::write::Default<u8> for MockSerialPort<'a> {}
+
+pub struct MockDelay<'a> {
+    serial: &'a MockSerial,
+}
+
+impl<'a> DelayMs<u16> for MockDelay<'a> {
+    fn delay_ms(&mut self, ms: u16) {
+        self.serial
+            .delayed_ms
+            .set(self.serial.delayed_ms.get() + ms as u32);
+        self.serial
+            .written_at_delay
+            .set(self.serial.written.borrow().len());
+    }
+}
+
+#[cfg(feature = "eh1")]
+mod eh1_impls {
+    use embedded_hal_1::delay::DelayNs;
+    use embedded_hal_1::digital::{self, InputPin};
+    use embedded_hal_1::spi::{self, Operation, SpiDevice};
+    use embedded_hal_nb::serial;
+
+    use super::{MockAttn, MockDelay, MockError, MockSerialPort, MockSpiDevice};
+
+    impl digital::Error for MockError {
+        fn kind(&self) -> digital::ErrorKind {
+            digital::ErrorKind::Other
+        }
+    }
+
+    impl spi::Error for MockError {
+        fn kind(&self) -> spi::ErrorKind {
+            spi::ErrorKind::Other
+        }
+    }
+
+    impl serial::Error for MockError {
+        fn kind(&self) -> serial::ErrorKind {
+            serial::ErrorKind::Other
+        }
+    }
+
+    impl<'a> digital::ErrorType for MockAttn<'a> {
+        type Error = MockError;
+    }
+
+    impl<'a> InputPin for MockAttn<'a> {
+        fn is_high(&mut self) -> Result<bool, MockError> {
+            self.is_low().map(|low| !low)
+        }
+
+        fn is_low(&mut self) -> Result<bool, MockError> {
+            if self.radio.fail_attn.get() {
+                return Err(MockError);
+            }
+            Ok(self.radio.attn_asserted())
+        }
+    }
+
+    impl<'a> spi::ErrorType for MockSpiDevice<'a> {
+        type Error = MockError;
+    }
+
+    impl<'a> SpiDevice<u8> for MockSpiDevice<'a> {
+        fn transaction(&mut self, operations: &mut [Operation<u8>]) -> Result<(), MockError> {
+            self.radio.cs_asserts.set(self.radio.cs_asserts.get() + 1);
+            for op in operations {
+                match *op {
+                    Operation::Write(words) => {
+                        for &word in words.iter() {
+                            self.radio.exchange(word);
+                        }
+                    }
+                    Operation::Read(ref mut words) => {
+                        for word in words.iter_mut() {
+                            *word = self.radio.exchange(0xFF);
+                        }
+                    }
+                    Operation::Transfer(ref mut read, write) => {
+                        for (i, &word) in write.iter().enumerate() {
+                            let reply = self.radio.exchange(word);
+                            if let Some(dst) = read.get_mut(i) {
+                                *dst = reply;
+                            }
+                        }
+                    }
+                    Operation::TransferInPlace(ref mut words) => {
+                        for word in words.iter_mut() {
+                            *word = self.radio.exchange(*word);
+                        }
+                    }
+                    Operation::DelayNs(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<'a> serial::ErrorType for MockSerialPort<'a> {
+        type Error = MockError;
+    }
+
+    impl<'a> serial::Read<u8> for MockSerialPort<'a> {
+        fn read(&mut self) -> nb::Result<u8, MockError> {
+            self.serial.read()
+        }
+    }
+
+    impl<'a> serial::Write<u8> for MockSerialPort<'a> {
+        fn write(&mut self, byte: u8) -> nb::Result<(), MockError> {
+            self.serial.write(byte)
+        }
+
+        fn flush(&mut self) -> nb::Result<(), MockError> {
+            self.serial.flush()
+        }
+    }
+
+    impl<'a> DelayNs for MockDelay<'a> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.serial
+                .delayed_ms
+                .set(self.serial.delayed_ms.get() + ns / 1_000_000);
+        }
+
+        fn delay_ms(&mut self, ms: u32) {
+            self.serial
+                .delayed_ms
+                .set(self.serial.delayed_ms.get() + ms);
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+pub struct MockSpiDevice<'a> {
+    radio: &'a MockRadio,
+}
+
+#[cfg(feature = "async")]
+mod async_impls {
+    use embedded_hal_1::spi::Operation;
+    use embedded_hal_async::digital::Wait;
+    use embedded_hal_async::spi::SpiDevice;
+
+    use super::{MockAttn, MockError, MockSpiDevice};
+
+    impl<'a> SpiDevice<u8> for MockSpiDevice<'a> {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), MockError> {
+            embedded_hal_1::spi::SpiDevice::transaction(self, operations)
+        }
+    }
+
+    // The radio never changes ATTN on its own, so waiting for a level it is not already at
+    // would never finish and fails the test instead, while an edge never comes.
+    impl<'a> Wait for MockAttn<'a> {
+        async fn wait_for_high(&mut self) -> Result<(), MockError> {
+            assert!(!self.radio.attn_asserted(), "waiting forever for ATTN high");
+            Ok(())
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), MockError> {
+            assert!(self.radio.attn_asserted(), "waiting forever for ATTN low");
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), MockError> {
+            core::future::pending().await
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), MockError> {
+            core::future::pending().await
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), MockError> {
+            core::future::pending().await
+        }
+    }
+}
+
+/// Runs a future to completion by polling it in a loop.
+#[cfg(feature = "async")]
+pub fn block_on<F: core::future::Future>(fut: F) -> F::Output {
+    use core::pin::pin;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(core::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Typed AT command parameters.
+//!
+//! Each type knows the command it is set and read with, how to encode itself as the command's
+//! parameter, and how to decode itself from the data of a response to the command. All numeric
+//! parameters are big-endian.
+
+use crate::api_frame::{self, AtResponseData};
+
+/// A value set and read with a single AT command.
+pub trait AtParam: Sized {
+    /// The command the value is set and read with.
+    const AT_CMD: [u8; 2];
+
+    /// The encoded parameter, such as `[u8; 2]`.
+    type Params: AsRef<[u8]>;
+
+    /// Encodes the value as the parameter of `AT_CMD`.
+    fn to_params(&self) -> Self::Params;
+
+    /// Decodes the value from the data of a successful response to `AT_CMD`, or `None` if the data
+    /// is not the right length.
+    fn from_response(data: &[u8]) -> Option<Self>;
+}
+
+macro_rules! at_param {
+    (
+        $(#[$attr:meta])*
+        $name:ident($ty:ty), [$a:expr, $b:expr], $len:expr, $encode:path, $decode:ident
+    ) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        pub struct $name(pub $ty);
+
+        impl AtParam for $name {
+            const AT_CMD: [u8; 2] = [$a, $b];
+
+            type Params = [u8; $len];
+
+            fn to_params(&self) -> [u8; $len] {
+                $encode(self.0)
+            }
+
+            fn from_response(data: &[u8]) -> Option<$name> {
+                AtResponseData(data).$decode().map($name)
+            }
+        }
+    };
+}
+
+at_param!(
+    /// Operating channel (`CH`).
+    Channel(u8), [b'C', b'H'], 1, api_frame::at_param_u8, as_u8
+);
+at_param!(
+    /// PAN ID (`ID`).
+    PanId(u16), [b'I', b'D'], 2, api_frame::at_param_u16, as_u16
+);
+at_param!(
+    /// 16-bit source address (`MY`). 0xFFFE and above turn off 16-bit addressing.
+    SourceAddr(u16), [b'M', b'Y'], 2, api_frame::at_param_u16, as_u16
+);
+at_param!(
+    /// High 32 bits of the destination address (`DH`).
+    DestAddrHigh(u32), [b'D', b'H'], 4, api_frame::at_param_u32, as_u32
+);
+at_param!(
+    /// Low 32 bits of the destination address (`DL`).
+    DestAddrLow(u32), [b'D', b'L'], 4, api_frame::at_param_u32, as_u32
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<P: AtParam + core::fmt::Debug + PartialEq>(param: P, encoded: &[u8]) {
+        assert_eq!(param.to_params().as_ref(), encoded);
+        assert_eq!(P::from_response(encoded), Some(param));
+        // Too long or too short
+        assert_eq!(P::from_response(&[0; 9][..encoded.len() + 1]), None);
+        assert_eq!(P::from_response(&encoded[1..]), None);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        round_trip(Channel(0x0C), &[0x0C]);
+        round_trip(PanId(0x3332), &[0x33, 0x32]);
+        round_trip(SourceAddr(0xFFFE), &[0xFF, 0xFE]);
+        round_trip(DestAddrHigh(0x0013_A200), &[0x00, 0x13, 0xA2, 0x00]);
+        round_trip(DestAddrLow(0x415D_1DBB), &[0x41, 0x5D, 0x1D, 0xBB]);
+    }
+
+    #[test]
+    fn at_cmd_test() {
+        assert_eq!(Channel::AT_CMD, *b"CH");
+        assert_eq!(PanId::AT_CMD, *b"ID");
+        assert_eq!(SourceAddr::AT_CMD, *b"MY");
+        assert_eq!(DestAddrHigh::AT_CMD, *b"DH");
+        assert_eq!(DestAddrLow::AT_CMD, *b"DL");
+    }
+}
@@ -0,0 +1,595 @@
+//! Byte queues holding API frames.
+//!
+//! Received bytes are only useful from a start delimiter onwards, so after a corrupt or partial
+//! frame a queue is resynchronized by discarding everything before the next start delimiter.
+//! These helpers are what the drivers use for that, and can be used on buffers of a custom
+//! `XBeeTransport` as well.
+//!
+//! The drivers keep received bytes in an `RxQueue`, which removes bytes from the front without
+//! moving the rest.
+
+use core::iter::Chain;
+use core::slice::Iter;
+
+use arraydeque::ArrayDeque;
+use arrayvec::{Array, ArrayVec};
+
+use crate::api_frame;
+
+/// Ring buffer of received bytes.
+///
+/// Frames are parsed in place, so a frame pushed across the end of the storage is moved back into
+/// one piece once it is complete. When filled by a driver, `as_slice` therefore always starts
+/// with whole frames, and bytes are only ever moved when a frame wraps around.
+pub struct RxQueue {
+    // TODO: make generic
+    buf: [u8; 512],
+    head: usize,
+    len: usize,
+}
+
+impl RxQueue {
+    pub fn new() -> RxQueue {
+        RxQueue {
+            buf: [0; 512],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// The queued bytes, split where the storage wraps around.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let end = self.head + self.len;
+        if end <= self.capacity() {
+            (&self.buf[self.head..end], &[])
+        } else {
+            (&self.buf[self.head..], &self.buf[..end - self.capacity()])
+        }
+    }
+
+    /// The queued bytes up to where the storage wraps around.
+    pub fn as_slice(&self) -> &[u8] {
+        self.as_slices().0
+    }
+
+    pub fn iter(&self) -> Chain<Iter<'_, u8>, Iter<'_, u8>> {
+        let (front, back) = self.as_slices();
+        front.iter().chain(back.iter())
+    }
+
+    pub fn get(&self, index: usize) -> Option<u8> {
+        if index < self.len {
+            Some(self.buf[(self.head + index) % self.capacity()])
+        } else {
+            None
+        }
+    }
+
+    /// Adds a byte to the back, handing it back if the queue is full.
+    pub fn push(&mut self, byte: u8) -> Result<(), u8> {
+        if self.is_full() {
+            return Err(byte);
+        }
+
+        let tail = (self.head + self.len) % self.capacity();
+        self.buf[tail] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop_front(&mut self) -> Option<u8> {
+        let byte = self.get(0)?;
+        self.head = (self.head + 1) % self.capacity();
+        self.len -= 1;
+        Some(byte)
+    }
+
+    /// Removes bytes from the back so that at most `len` are left.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Whether the queued bytes are all in `as_slice`.
+    pub fn is_contiguous(&self) -> bool {
+        self.head + self.len <= self.capacity()
+    }
+
+    /// Moves the queued bytes so they are all in `as_slice`.
+    pub fn make_contiguous(&mut self) -> &[u8] {
+        if !self.is_contiguous() {
+            self.buf.rotate_left(self.head);
+            self.head = 0;
+        }
+        &self.buf[self.head..self.head + self.len]
+    }
+
+    /// The queued bytes from `offset` up to where the storage wraps around, or `None` if `offset`
+    /// is past the end.
+    pub(crate) fn slice_from(&self, offset: usize) -> Option<&[u8]> {
+        let (front, back) = self.as_slices();
+        if offset < front.len() {
+            Some(&front[offset..])
+        } else if offset < self.len {
+            Some(&back[offset - front.len()..])
+        } else {
+            None
+        }
+    }
+
+    /// Removes `len` bytes starting `start` bytes from the front, moving the bytes after them
+    /// forward.
+    pub(crate) fn remove(&mut self, start: usize, len: usize) {
+        let len = len.min(self.len.saturating_sub(start));
+        let cap = self.capacity();
+        for i in start..self.len - len {
+            self.buf[(self.head + i) % cap] = self.buf[(self.head + i + len) % cap];
+        }
+        self.len -= len;
+        // Frames moved forward may now wrap around
+        self.make_contiguous();
+    }
+
+    fn remove_front(&mut self, amount: usize) {
+        let amount = amount.min(self.len);
+        self.head = (self.head + amount) % self.capacity();
+        self.len -= amount;
+        if self.len == 0 {
+            self.head = 0;
+        }
+    }
+}
+
+impl Default for RxQueue {
+    fn default() -> RxQueue {
+        RxQueue::new()
+    }
+}
+
+impl Extend<u8> for RxQueue {
+    /// Pushes bytes until the queue is full, dropping the rest.
+    fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+        for byte in iter {
+            if self.push(byte).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Front removal and resynchronization for a queue of frame bytes.
+pub trait XBeeQueue {
+    /// Discards bytes up to, but not including, the first start delimiter, or the whole queue if
+    /// there is none. Returns the number of bytes discarded.
+    ///
+    /// A start delimiter at the front is kept, so to move past a bad frame remove its start
+    /// delimiter with `remove_exact(1)` first. In unescaped mode a start delimiter value can also
+    /// appear inside a frame, so the byte found is not guaranteed to start a frame.
+    fn remove_until_start(&mut self) -> usize;
+
+    /// Removes `amount` bytes from the front, or every byte if the queue holds fewer.
+    fn remove_exact(&mut self, amount: usize);
+
+    /// Removes the frame at the front of the queue according to its length field, or the whole
+    /// queue if the frame is not complete. Returns the number of bytes removed.
+    fn remove_frame(&mut self) -> usize;
+}
+
+/// Storage for the bytes of frames waiting to be sent, which `XBeeApi` is generic over.
+///
+/// Implemented for `ArrayDeque`, which the drivers use unless built with another queue, and for
+/// `heapless::Deque` with the `heapless` feature.
+pub trait TxQueue {
+    fn len(&self) -> usize;
+
+    fn capacity(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    fn clear(&mut self);
+
+    /// Adds a byte at the back, giving it back if the queue is full.
+    fn push_back(&mut self, byte: u8) -> Result<(), u8>;
+
+    fn pop_front(&mut self) -> Option<u8>;
+
+    fn pop_back(&mut self) -> Option<u8>;
+
+    /// The queued bytes, split where the storage wraps around.
+    fn as_slices(&self) -> (&[u8], &[u8]);
+}
+
+impl<A> TxQueue for ArrayDeque<A>
+where
+    A: arraydeque::Array<Item = u8>,
+{
+    fn len(&self) -> usize {
+        ArrayDeque::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        ArrayDeque::capacity(self)
+    }
+
+    fn clear(&mut self) {
+        ArrayDeque::clear(self)
+    }
+
+    fn push_back(&mut self, byte: u8) -> Result<(), u8> {
+        ArrayDeque::<A>::push_back(self, byte).map_err(|err| err.element)
+    }
+
+    fn pop_front(&mut self) -> Option<u8> {
+        ArrayDeque::pop_front(self)
+    }
+
+    fn pop_back(&mut self) -> Option<u8> {
+        ArrayDeque::pop_back(self)
+    }
+
+    fn as_slices(&self) -> (&[u8], &[u8]) {
+        ArrayDeque::as_slices(self)
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> TxQueue for heapless::Deque<u8, N> {
+    fn len(&self) -> usize {
+        heapless::Deque::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        heapless::Deque::capacity(self)
+    }
+
+    fn clear(&mut self) {
+        heapless::Deque::clear(self)
+    }
+
+    fn push_back(&mut self, byte: u8) -> Result<(), u8> {
+        heapless::Deque::push_back(self, byte)
+    }
+
+    fn pop_front(&mut self) -> Option<u8> {
+        heapless::Deque::pop_front(self)
+    }
+
+    fn pop_back(&mut self) -> Option<u8> {
+        heapless::Deque::pop_back(self)
+    }
+
+    fn as_slices(&self) -> (&[u8], &[u8]) {
+        heapless::Deque::as_slices(self)
+    }
+}
+
+/// Length of the frame at the front of a queue of `len` bytes, given its two length bytes.
+fn front_frame_len(len: usize, len_field: Option<(u8, u8)>) -> usize {
+    match len_field {
+        Some((msb, lsb)) => (3 + ((msb as usize) << 8 | lsb as usize) + 1).min(len),
+        None => len,
+    }
+}
+
+impl<A> XBeeQueue for ArrayVec<A>
+where
+    A: Array<Item = u8>,
+{
+    fn remove_until_start(&mut self) -> usize {
+        match self.iter().position(|c| c == &api_frame::START) {
+            Some(size) => {
+                self.remove_exact(size);
+                size
+            }
+            None => {
+                let len = self.len();
+                self.clear();
+                len
+            }
+        }
+    }
+
+    fn remove_exact(&mut self, amount: usize) {
+        let amount = amount.min(self.len());
+        self.drain(0..amount);
+    }
+
+    fn remove_frame(&mut self) -> usize {
+        let len_field = self.get(1..3).map(|len| (len[0], len[1]));
+        let len = front_frame_len(self.len(), len_field);
+        self.drain(0..len);
+        len
+    }
+}
+
+impl<A> XBeeQueue for ArrayDeque<A>
+where
+    A: arraydeque::Array<Item = u8>,
+{
+    fn remove_until_start(&mut self) -> usize {
+        match self.iter().position(|c| c == &api_frame::START) {
+            Some(size) => {
+                self.remove_exact(size);
+                size
+            }
+            None => {
+                let len = self.len();
+                self.clear();
+                len
+            }
+        }
+    }
+
+    fn remove_exact(&mut self, amount: usize) {
+        for _ in 0..amount.min(self.len()) {
+            self.pop_front();
+        }
+    }
+
+    fn remove_frame(&mut self) -> usize {
+        let len_field = match (self.get(1), self.get(2)) {
+            (Some(&msb), Some(&lsb)) => Some((msb, lsb)),
+            _ => None,
+        };
+        let len = front_frame_len(self.len(), len_field);
+        for _ in 0..len {
+            self.pop_front();
+        }
+        len
+    }
+}
+
+impl XBeeQueue for RxQueue {
+    fn remove_until_start(&mut self) -> usize {
+        let size = self
+            .iter()
+            .position(|c| c == &api_frame::START)
+            .unwrap_or(self.len);
+        self.remove_front(size);
+        size
+    }
+
+    fn remove_exact(&mut self, amount: usize) {
+        self.remove_front(amount.min(self.len));
+    }
+
+    fn remove_frame(&mut self) -> usize {
+        let len_field = match (self.get(1), self.get(2)) {
+            (Some(msb), Some(lsb)) => Some((msb, lsb)),
+            _ => None,
+        };
+        let len = front_frame_len(self.len, len_field);
+        self.remove_front(len);
+        len
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> XBeeQueue for heapless::Vec<u8, N> {
+    fn remove_until_start(&mut self) -> usize {
+        let size = self
+            .iter()
+            .position(|c| c == &api_frame::START)
+            .unwrap_or_else(|| self.len());
+        self.remove_exact(size);
+        size
+    }
+
+    fn remove_exact(&mut self, amount: usize) {
+        let amount = amount.min(self.len());
+        self.rotate_left(amount);
+        self.truncate(self.len() - amount);
+    }
+
+    fn remove_frame(&mut self) -> usize {
+        let len_field = self.get(1..3).map(|len| (len[0], len[1]));
+        let len = front_frame_len(self.len(), len_field);
+        self.remove_exact(len);
+        len
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> XBeeQueue for heapless::Deque<u8, N> {
+    fn remove_until_start(&mut self) -> usize {
+        let size = self
+            .iter()
+            .position(|c| c == &api_frame::START)
+            .unwrap_or_else(|| self.len());
+        self.remove_exact(size);
+        size
+    }
+
+    fn remove_exact(&mut self, amount: usize) {
+        for _ in 0..amount.min(self.len()) {
+            self.pop_front();
+        }
+    }
+
+    fn remove_frame(&mut self) -> usize {
+        let mut header = self.iter().skip(1);
+        let len_field = match (header.next(), header.next()) {
+            (Some(&msb), Some(&lsb)) => Some((msb, lsb)),
+            _ => None,
+        };
+        let len = front_frame_len(self.len(), len_field);
+        self.remove_exact(len);
+        len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remove_until_start_test() {
+        // No start delimiter, everything is discarded
+        let mut queue: ArrayVec<[u8; 8]> = ArrayVec::new();
+        queue.extend([0x01, 0x02, 0x03].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 3);
+        assert!(queue.is_empty());
+
+        // Start delimiter partway through
+        queue.extend([0x01, 0x02, 0x7E, 0x00, 0x7E].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 2);
+        assert_eq!(queue.as_slice(), &[0x7E, 0x00, 0x7E]);
+
+        // Already at a start delimiter
+        assert_eq!(queue.remove_until_start(), 0);
+        assert_eq!(queue.len(), 3);
+
+        let mut queue: ArrayDeque<[u8; 8]> = ArrayDeque::new();
+        queue.extend([0x01, 0x02, 0x03].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 3);
+        assert!(queue.is_empty());
+
+        queue.extend([0x01, 0x02, 0x7E, 0x00, 0x7E].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 2);
+        assert!(queue.iter().eq([0x7E, 0x00, 0x7E].iter()));
+    }
+
+    #[test]
+    fn rx_queue_wrap_test() {
+        let mut queue = RxQueue::new();
+        queue.extend([0x00; 508].iter().cloned());
+        queue.remove_exact(500);
+        // Wraps around the end of the storage
+        queue.extend([0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74].iter().cloned());
+        assert!(!queue.is_contiguous());
+        assert_eq!(queue.as_slices().1, &[0x01, 0x74]);
+        assert_eq!(queue.remove_until_start(), 8);
+        assert_eq!(queue.as_slice(), &[0x7E, 0x00, 0x02, 0x8A]);
+        assert_eq!(queue.get(5), Some(0x74));
+
+        assert_eq!(
+            queue.make_contiguous(),
+            &[0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74]
+        );
+        assert_eq!(queue.remove_frame(), 6);
+        assert!(queue.is_empty());
+
+        // Removing from the middle moves what follows forward, back into one piece
+        queue.extend([0x00; 508].iter().cloned());
+        queue.remove_exact(507);
+        queue.extend([0x01, 0x02, 0x03, 0x04, 0x05].iter().cloned());
+        assert!(!queue.is_contiguous());
+        queue.remove(1, 2);
+        assert_eq!(queue.as_slice(), &[0x00, 0x03, 0x04, 0x05]);
+        queue.clear();
+
+        queue.extend([0x00; 600].iter().cloned());
+        assert!(queue.is_full());
+        assert_eq!(queue.push(0x01), Err(0x01));
+        assert_eq!(queue.remove_until_start(), 512);
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(feature = "heapless")]
+    fn push_all<const N: usize>(queue: &mut heapless::Deque<u8, N>, data: &[u8]) {
+        for &byte in data {
+            queue.push_back(byte).unwrap();
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_remove_until_start_test() {
+        let mut queue: heapless::Vec<u8, 8> = heapless::Vec::new();
+        queue.extend([0x01, 0x02, 0x03].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 3);
+        assert!(queue.is_empty());
+
+        queue.extend([0x01, 0x02, 0x7E, 0x00, 0x7E].iter().cloned());
+        assert_eq!(queue.remove_until_start(), 2);
+        assert_eq!(queue.as_slice(), &[0x7E, 0x00, 0x7E]);
+        assert_eq!(queue.remove_until_start(), 0);
+
+        let mut queue: heapless::Deque<u8, 8> = heapless::Deque::new();
+        push_all(&mut queue, &[0x01, 0x02, 0x03]);
+        assert_eq!(queue.remove_until_start(), 3);
+        assert!(queue.is_empty());
+
+        push_all(&mut queue, &[0x01, 0x02, 0x7E, 0x00, 0x7E]);
+        assert_eq!(queue.remove_until_start(), 2);
+        assert!(queue.iter().eq([0x7E, 0x00, 0x7E].iter()));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_remove_exact_frame_test() {
+        let frame = [0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74];
+
+        let mut queue: heapless::Deque<u8, 16> = heapless::Deque::new();
+        push_all(&mut queue, &frame);
+        push_all(&mut queue, &frame[..3]);
+        assert_eq!(queue.remove_frame(), 6);
+        assert!(queue.iter().eq(frame[..3].iter()));
+        assert_eq!(queue.remove_frame(), 3);
+        assert!(queue.is_empty());
+        // Removing more than is queued empties the queue
+        push_all(&mut queue, &frame);
+        queue.remove_exact(10);
+        assert!(queue.is_empty());
+
+        let mut queue: heapless::Vec<u8, 16> = heapless::Vec::new();
+        queue.extend(frame.iter().cloned());
+        queue.extend(frame[..3].iter().cloned());
+        queue.remove_exact(1);
+        assert_eq!(&queue[..5], &frame[1..]);
+        assert_eq!(&queue[5..], &frame[..3]);
+        queue.remove_exact(10);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn remove_exact_frame_test() {
+        let frame = [0x7E, 0x00, 0x02, 0x8A, 0x01, 0x74];
+
+        let mut queue: ArrayDeque<[u8; 16]> = ArrayDeque::new();
+        queue.extend(frame.iter().cloned());
+        queue.extend(frame[..3].iter().cloned());
+        assert_eq!(queue.remove_frame(), 6);
+        assert!(queue.iter().eq(frame[..3].iter()));
+        // Incomplete frames are removed entirely
+        assert_eq!(queue.remove_frame(), 3);
+        assert!(queue.is_empty());
+        // Removing more than is queued empties the queue
+        queue.extend(frame.iter().cloned());
+        queue.remove_exact(10);
+        assert!(queue.is_empty());
+
+        let mut queue: ArrayVec<[u8; 16]> = ArrayVec::new();
+        queue.extend(frame.iter().cloned());
+        queue.remove_exact(1);
+        assert_eq!(queue.as_slice(), &frame[1..]);
+        queue.remove_exact(10);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+//! Resending TX requests the radio could not deliver.
+
+use arrayvec::ArrayVec;
+
+use crate::api_frame::TxStatus;
+use crate::TX_RETRY_SLOTS;
+
+/// Longest TX request data kept for resending, enough for a 64-bit addressed request with a
+/// `MAX_TX_PAYLOAD` byte payload.
+const RETAINED_LEN: usize = 128;
+
+#[derive(Debug)]
+struct RetainedFrame {
+    frame_id: u8,
+    retries_left: u8,
+    data: ArrayVec<[u8; RETAINED_LEN]>,
+}
+
+/// TX requests sent with a frame ID, kept until their TX status arrives.
+#[derive(Debug, Default)]
+pub(crate) struct TxRetry {
+    retries: u8,
+    frames: ArrayVec<[RetainedFrame; TX_RETRY_SLOTS]>,
+}
+
+impl TxRetry {
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+        if retries == 0 {
+            self.frames.clear();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Whether a TX request with `frame_id` would be kept. Frame ID 0 never gets a TX status.
+    pub fn wants(&self, frame_id: u8) -> bool {
+        self.retries > 0 && frame_id != 0
+    }
+
+    /// Keeps the data of a TX request, replacing any kept with the same frame ID. Nothing is
+    /// kept if every slot is taken or the data is too long.
+    pub fn retain(&mut self, frame_id: u8, data: &[u8]) {
+        self.release(frame_id);
+        if self.frames.is_full() || data.len() > RETAINED_LEN {
+            return;
+        }
+
+        self.frames.push(RetainedFrame {
+            frame_id,
+            retries_left: self.retries,
+            data: data.iter().cloned().collect(),
+        });
+    }
+
+    pub fn release(&mut self, frame_id: u8) {
+        self.frames.retain(|frame| frame.frame_id != frame_id);
+    }
+
+    /// Handles a TX status, returning the data of the request if it should be sent again.
+    ///
+    /// Requests that failed on a busy channel or went unacknowledged are resent until their
+    /// retries run out. Any other status, or running out, lets the request go.
+    pub fn on_status(
+        &mut self,
+        frame_id: u8,
+        status: TxStatus,
+    ) -> Option<ArrayVec<[u8; RETAINED_LEN]>> {
+        let idx = self
+            .frames
+            .iter()
+            .position(|frame| frame.frame_id == frame_id)?;
+        let frame = &mut self.frames[idx];
+        match status {
+            TxStatus::CcaFailure | TxStatus::NoAck if frame.retries_left > 0 => {
+                frame.retries_left -= 1;
+                Some(frame.data.clone())
+            }
+            _ => {
+                self.frames.remove(idx);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Pin sleep control.
+//!
+//! With pin sleep (`SM` set to `SleepMode::PinHibernate` or `SleepMode::PinDoze`) the radio sleeps
+//! while the host holds SLEEP_RQ high, and signals it is awake by driving ON/SLEEP high. Frames
+//! must not be sent until it is awake again, so `XBeeApi::set_asleep` should be kept in step with
+//! the requests made here.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+/// Errors from `XBeeSleepControl::wait_awake`.
+#[derive(Debug)]
+pub enum WakeError<E> {
+    /// Failure reading ON/SLEEP.
+    Pin(E),
+    /// ON/SLEEP did not go high within the timeout.
+    Timeout,
+}
+
+/// Drives SLEEP_RQ and watches ON/SLEEP of a radio in pin sleep.
+pub struct XBeeSleepControl<SRQ, ONS> {
+    sleep_rq: SRQ,
+    on_sleep: ONS,
+}
+
+impl<SRQ, ONS> XBeeSleepControl<SRQ, ONS>
+where
+    SRQ: OutputPin,
+    ONS: InputPin,
+{
+    pub fn new(sleep_rq: SRQ, on_sleep: ONS) -> XBeeSleepControl<SRQ, ONS> {
+        XBeeSleepControl { sleep_rq, on_sleep }
+    }
+
+    /// Releases the SLEEP_RQ and ON/SLEEP pins.
+    pub fn free(self) -> (SRQ, ONS) {
+        (self.sleep_rq, self.on_sleep)
+    }
+
+    /// Asks the radio to sleep once it has nothing left to do.
+    pub fn request_sleep(&mut self) -> Result<(), SRQ::Error> {
+        self.sleep_rq.set_high()
+    }
+
+    /// Asks the radio to wake. Nothing should be sent until `wait_awake` returns.
+    pub fn request_wake(&mut self) -> Result<(), SRQ::Error> {
+        self.sleep_rq.set_low()
+    }
+
+    /// Whether the radio is signalling it is awake.
+    pub fn is_awake(&self) -> Result<bool, ONS::Error> {
+        self.on_sleep.is_high()
+    }
+
+    /// Waits for the radio to signal it is awake, checking every millisecond for up to
+    /// `timeout_ms` milliseconds.
+    pub fn wait_awake<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u16,
+    ) -> Result<(), WakeError<ONS::Error>>
+    where
+        D: DelayMs<u16>,
+    {
+        for _ in 0..timeout_ms {
+            if self.is_awake().map_err(WakeError::Pin)? {
+                return Ok(());
+            }
+            delay.delay_ms(1);
+        }
+
+        if self.is_awake().map_err(WakeError::Pin)? {
+            Ok(())
+        } else {
+            Err(WakeError::Timeout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mock::MockSleep;
+
+    #[test]
+    fn wake_handshake_test() {
+        let radio = MockSleep::new(5);
+        let mut delay = radio.delay();
+        let mut control = XBeeSleepControl::new(radio.sleep_rq(), radio.on_sleep());
+
+        control.request_sleep().unwrap();
+        assert!(radio.sleep_requested());
+        assert!(!control.is_awake().unwrap());
+        // Still asleep, however long it is waited for
+        match control.wait_awake(&mut delay, 10) {
+            Err(WakeError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+
+        control.request_wake().unwrap();
+        assert!(!radio.sleep_requested());
+        control.wait_awake(&mut delay, 10).unwrap();
+        // Awake as soon as the radio has woken, not at the timeout
+        assert_eq!(radio.ms_since_wake_request(), Some(5));
+
+        control.request_sleep().unwrap();
+        control.request_wake().unwrap();
+        match control.wait_awake(&mut delay, 2) {
+            Err(WakeError::Timeout) => {}
+            _ => panic!("expected a timeout"),
+        }
+    }
+}
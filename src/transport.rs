@@ -0,0 +1,758 @@
+//! Links between the host and the radio.
+//!
+//! `XBeeApi` only needs to move bytes to and from the radio, so any link can be used by
+//! implementing `XBeeTransport` for it. SPI with an ATTN line and plain serial ports are provided,
+//! with SPI exchanged either a byte at a time or in blocking transfers of several bytes.
+
+use core::convert::Infallible;
+
+use arrayvec::{Array, ArrayVec};
+use embedded_hal::blocking::serial::write::Default as BlockingWriteDefault;
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+use embedded_hal::spi::FullDuplex;
+
+use crate::SpiIoError;
+
+/// Number of bytes `XBeeApi` asks a transport for at a time.
+pub const READ_CHUNK_SIZE: usize = 32;
+
+/// Byte level link to the radio used by `XBeeApi`.
+pub trait XBeeTransport {
+    type Error;
+
+    /// Writes the start of `buf`, returning how many bytes were written.
+    ///
+    /// Returns `WouldBlock` only if no bytes could be written.
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, Self::Error>;
+
+    /// Reads received bytes into the start of `buf`, returning how many bytes were read.
+    ///
+    /// Returns `WouldBlock` only if no bytes could be read because the link is busy.
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Self::Error>;
+
+    /// Whether the radio has bytes waiting to be read.
+    fn rx_pending(&mut self) -> Result<bool, Self::Error>;
+
+    /// Reads bytes that have already been received, such as ones clocked in while writing,
+    /// without sending anything to the radio. Used while a frame is partway through being
+    /// written, where anything sent to read would end up inside the frame.
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let _ = buf;
+        Ok(0)
+    }
+
+    /// Called once there is nothing left to write or read, such as to release a chip select.
+    ///
+    /// Returns `WouldBlock` if the link is still busy, in which case it is called again.
+    fn finish(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Told how many bytes are waiting in the RX queue before each step of an exchange, such as
+    /// to tell the radio to hold off sending with flow control.
+    fn rx_queue_level(&mut self, queued: usize) -> Result<(), Self::Error> {
+        let _ = queued;
+        Ok(())
+    }
+}
+
+/// Moves bytes from the front of `stash` to the start of `buf`, returning how many were moved.
+pub(crate) fn take_stashed<A>(stash: &mut ArrayVec<A>, buf: &mut [u8]) -> usize
+where
+    A: Array<Item = u8>,
+{
+    let read = stash.len().min(buf.len());
+    buf[..read].copy_from_slice(&stash[..read]);
+    stash.drain(..read);
+    read
+}
+
+/// Progress of an SPI exchange, kept between calls into `SpiTransport`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SpiState {
+    /// No exchange in progress and CS released.
+    Idle,
+    /// CS asserted, no byte in flight.
+    Exchanging,
+    /// A byte has been sent and the byte clocked in alongside it has not been read yet.
+    AwaitingRead { capture: bool },
+}
+
+/// Stand-in CS pin for a radio that is the only device on its SPI bus, with CS tied low, or
+/// stand-in for a flow control line that is not wired up.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoPin;
+
+/// Reads as low, so it can also stand in for a CTS line the radio is always taken to assert.
+impl InputPin for NoPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(false)
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(true)
+    }
+}
+
+impl OutputPin for NoPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Level of the ATTN line while the radio is asserting it.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum AttnPolarity {
+    /// Driven low when asserted, as the radio does itself.
+    #[default]
+    ActiveLow,
+    /// Driven high when asserted, for boards that invert the line.
+    ActiveHigh,
+}
+
+impl AttnPolarity {
+    fn asserted<P: InputPin>(self, attn: &P) -> Result<bool, P::Error> {
+        match self {
+            AttnPolarity::ActiveLow => attn.is_low(),
+            AttnPolarity::ActiveHigh => attn.is_high(),
+        }
+    }
+}
+
+/// SPI link with the radio's ATTN line signalling when it has data.
+///
+/// Bytes clocked in while writing are kept for the next `read` when ATTN was asserted, and CS
+/// stays asserted from the first byte exchanged until `finish`.
+pub struct SpiTransport<S, C, A> {
+    spi: S,
+    cs: Option<C>,
+    attn: A,
+    attn_polarity: AttnPolarity,
+
+    state: SpiState,
+    /// Bytes received but not yet read.
+    stash: ArrayVec<[u8; READ_CHUNK_SIZE]>,
+}
+
+impl<E, CE, AE, S, C, A> SpiTransport<S, C, A>
+where
+    S: FullDuplex<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    pub fn new(spi: S, cs: Option<C>, attn: A) -> SpiTransport<S, C, A> {
+        SpiTransport {
+            spi,
+            cs,
+            attn,
+            attn_polarity: AttnPolarity::default(),
+            state: SpiState::Idle,
+            stash: ArrayVec::new(),
+        }
+    }
+
+    /// Releases the SPI peripheral, CS pin and ATTN pin.
+    ///
+    /// CS is left as it is, which is asserted if an exchange was not finished.
+    pub fn free(self) -> (S, Option<C>, A) {
+        (self.spi, self.cs, self.attn)
+    }
+
+    /// Whether the radio is asserting ATTN, meaning it has data waiting.
+    pub fn attn_asserted(&self) -> Result<bool, AE> {
+        self.attn_polarity.asserted(&self.attn)
+    }
+
+    /// Sets which level of ATTN means it is asserted, which defaults to
+    /// `AttnPolarity::ActiveLow`.
+    pub fn set_attn_polarity(&mut self, polarity: AttnPolarity) {
+        self.attn_polarity = polarity;
+    }
+
+    /// Sends a byte, asserting CS first if needed. The byte clocked in is stashed by `complete`
+    /// if `capture` is set.
+    fn start(&mut self, tx: u8, capture: bool) -> nb::Result<(), SpiIoError<E, CE, AE>> {
+        if self.state == SpiState::Idle {
+            if let Some(ref mut cs) = self.cs {
+                cs.set_low()
+                    .map_err(|err| nb::Error::Other(SpiIoError::Cs(err)))?;
+            }
+            self.state = SpiState::Exchanging;
+        }
+
+        match self.spi.send(tx) {
+            Ok(()) => {}
+            Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(err)) => return Err(nb::Error::Other(SpiIoError::Send(err))),
+        }
+        self.state = SpiState::AwaitingRead { capture };
+        Ok(())
+    }
+
+    /// Reads the byte clocked in alongside the last byte sent, if it has not been read yet.
+    fn complete(&mut self) -> nb::Result<(), SpiIoError<E, CE, AE>> {
+        if let SpiState::AwaitingRead { capture } = self.state {
+            let rx = match self.spi.read() {
+                Ok(rx) => rx,
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                Err(nb::Error::Other(err)) => return Err(nb::Error::Other(SpiIoError::Read(err))),
+            };
+            self.state = SpiState::Exchanging;
+
+            if capture {
+                // Cannot fail, room is checked before a capturing exchange is started
+                let _ = self.stash.try_push(rx);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<E, CE, AE, S, C, A> XBeeTransport for SpiTransport<S, C, A>
+where
+    S: FullDuplex<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    type Error = SpiIoError<E, CE, AE>;
+
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, Self::Error> {
+        self.complete()?;
+
+        // Once a byte has been sent it has to be counted as written, so any error after that
+        // ends the write early and is returned by the next call instead
+        let mut written = 0;
+        for &tx in buf {
+            let capture = match self.attn_asserted() {
+                Ok(capture) => capture,
+                Err(_) if written > 0 => break,
+                Err(err) => return Err(nb::Error::Other(SpiIoError::Attn(err))),
+            };
+            if capture && self.stash.is_full() {
+                break;
+            }
+
+            match self.start(tx, capture) {
+                Ok(()) => {}
+                Err(_) if written > 0 => break,
+                Err(err) => return Err(err),
+            }
+            written += 1;
+
+            if self.complete().is_err() {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Self::Error> {
+        self.complete()?;
+
+        let len = buf.len().min(self.stash.capacity());
+        while self.stash.len() < len {
+            if !self
+                .attn_asserted()
+                .map_err(|err| nb::Error::Other(SpiIoError::Attn(err)))?
+            {
+                break;
+            }
+
+            match self.start(0xFF, true).and_then(|()| self.complete()) {
+                Ok(()) => {}
+                Err(nb::Error::WouldBlock) if !self.stash.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(take_stashed(&mut self.stash, &mut buf[..len]))
+    }
+
+    fn rx_pending(&mut self) -> Result<bool, Self::Error> {
+        if !self.stash.is_empty() {
+            return Ok(true);
+        }
+        if let SpiState::AwaitingRead { capture: true } = self.state {
+            return Ok(true);
+        }
+        self.attn_asserted().map_err(SpiIoError::Attn)
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // A byte still being exchanged is stashed by the next write
+        Ok(take_stashed(&mut self.stash, buf))
+    }
+
+    fn finish(&mut self) -> nb::Result<(), Self::Error> {
+        self.complete()?;
+        if self.state == SpiState::Idle {
+            return Ok(());
+        }
+
+        self.state = SpiState::Idle;
+        match self.cs {
+            Some(ref mut cs) => cs
+                .set_high()
+                .map_err(|err| nb::Error::Other(SpiIoError::Cs(err))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// SPI link exchanging bytes in blocking transfers of up to `READ_CHUNK_SIZE` bytes, with the
+/// radio's ATTN line signalling when it has data.
+///
+/// Avoids the per byte overhead of `SpiTransport` on HALs where that costs more than the bytes
+/// on the wire. ATTN is only checked between transfers, so bytes clocked in while writing are kept
+/// for the next `read` if ATTN was asserted before or after the transfer. CS stays asserted from
+/// the first transfer until `finish`.
+pub struct SpiTransferTransport<S, C, A> {
+    spi: S,
+    cs: Option<C>,
+    attn: A,
+    attn_polarity: AttnPolarity,
+
+    chunk_size: usize,
+    /// CS is asserted.
+    selected: bool,
+    /// Bytes received but not yet read.
+    stash: ArrayVec<[u8; READ_CHUNK_SIZE]>,
+}
+
+impl<E, CE, AE, S, C, A> SpiTransferTransport<S, C, A>
+where
+    S: Transfer<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    pub fn new(spi: S, cs: Option<C>, attn: A) -> SpiTransferTransport<S, C, A> {
+        SpiTransferTransport {
+            spi,
+            cs,
+            attn,
+            attn_polarity: AttnPolarity::default(),
+            chunk_size: READ_CHUNK_SIZE,
+            selected: false,
+            stash: ArrayVec::new(),
+        }
+    }
+
+    /// Releases the SPI peripheral, CS pin and ATTN pin.
+    ///
+    /// CS is left as it is, which is asserted if an exchange was not finished.
+    pub fn free(self) -> (S, Option<C>, A) {
+        (self.spi, self.cs, self.attn)
+    }
+
+    /// Whether the radio is asserting ATTN, meaning it has data waiting.
+    pub fn attn_asserted(&self) -> Result<bool, AE> {
+        self.attn_polarity.asserted(&self.attn)
+    }
+
+    /// Sets which level of ATTN means it is asserted, which defaults to
+    /// `AttnPolarity::ActiveLow`.
+    pub fn set_attn_polarity(&mut self, polarity: AttnPolarity) {
+        self.attn_polarity = polarity;
+    }
+
+    /// Sets the most bytes exchanged in one transfer, which defaults to `READ_CHUNK_SIZE` and is
+    /// limited to between 1 and `READ_CHUNK_SIZE`. Smaller transfers notice ATTN changes sooner.
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.clamp(1, READ_CHUNK_SIZE);
+    }
+
+    /// Exchanges `chunk` in place, asserting CS first if needed.
+    fn transfer(&mut self, chunk: &mut [u8]) -> Result<(), SpiIoError<E, CE, AE>> {
+        if !self.selected {
+            if let Some(ref mut cs) = self.cs {
+                cs.set_low().map_err(SpiIoError::Cs)?;
+            }
+            self.selected = true;
+        }
+
+        self.spi.transfer(chunk).map_err(SpiIoError::Serial)?;
+        Ok(())
+    }
+}
+
+impl<E, CE, AE, S, C, A> XBeeTransport for SpiTransferTransport<S, C, A>
+where
+    S: Transfer<u8, Error = E>,
+    C: OutputPin<Error = CE>,
+    A: InputPin<Error = AE>,
+{
+    type Error = SpiIoError<E, CE, AE>;
+
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, Self::Error> {
+        // Whatever is clocked in may have to be kept, so only write as much as can be
+        let len = buf
+            .len()
+            .min(self.chunk_size)
+            .min(self.stash.capacity() - self.stash.len());
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let attn_before = self.attn_asserted().map_err(SpiIoError::Attn)?;
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        chunk[..len].copy_from_slice(&buf[..len]);
+        self.transfer(&mut chunk[..len])?;
+
+        // ATTN may have been asserted partway through the transfer, in which case the bytes
+        // before the radio started sending are idle fill that framing skips over
+        if attn_before || self.attn_asserted().map_err(SpiIoError::Attn)? {
+            self.stash.extend(chunk[..len].iter().cloned());
+        }
+        Ok(len)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Self::Error> {
+        let len = buf.len().min(self.stash.capacity());
+        let wanted = len.saturating_sub(self.stash.len()).min(self.chunk_size);
+        if wanted > 0 && self.attn_asserted().map_err(SpiIoError::Attn)? {
+            let mut chunk = [0xFF; READ_CHUNK_SIZE];
+            self.transfer(&mut chunk[..wanted])?;
+            self.stash.extend(chunk[..wanted].iter().cloned());
+        }
+
+        Ok(take_stashed(&mut self.stash, &mut buf[..len]))
+    }
+
+    fn rx_pending(&mut self) -> Result<bool, Self::Error> {
+        if !self.stash.is_empty() {
+            return Ok(true);
+        }
+        self.attn_asserted().map_err(SpiIoError::Attn)
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(take_stashed(&mut self.stash, buf))
+    }
+
+    fn finish(&mut self) -> nb::Result<(), Self::Error> {
+        if !self.selected {
+            return Ok(());
+        }
+
+        self.selected = false;
+        match self.cs {
+            Some(ref mut cs) => cs
+                .set_high()
+                .map_err(|err| nb::Error::Other(SpiIoError::Cs(err))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Serial port link, such as a UART.
+pub struct SerialTransport<U> {
+    serial: U,
+    /// Byte read by `rx_pending` and not yet returned by `read`.
+    peeked: Option<u8>,
+}
+
+impl<E, U> SerialTransport<U>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+{
+    pub fn new(uart: U) -> SerialTransport<U> {
+        SerialTransport {
+            serial: uart,
+            peeked: None,
+        }
+    }
+
+    /// Releases the UART. A byte already read to check for pending data is lost.
+    pub fn free(self) -> U {
+        self.serial
+    }
+}
+
+impl<E, U> XBeeTransport for SerialTransport<U>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, E> {
+        let mut written = 0;
+        for &tx in buf {
+            match self.serial.write(tx) {
+                Ok(()) => written += 1,
+                Err(nb::Error::WouldBlock) if written > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(written)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, E> {
+        let mut read = 0;
+        if let (Some(rx), Some(dst)) = (self.peeked, buf.first_mut()) {
+            *dst = rx;
+            self.peeked = None;
+            read = 1;
+        }
+
+        while read < buf.len() {
+            match self.serial.read() {
+                Ok(rx) => {
+                    buf[read] = rx;
+                    read += 1;
+                }
+                Err(nb::Error::WouldBlock) if read > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(read)
+    }
+
+    fn rx_pending(&mut self) -> Result<bool, E> {
+        if self.peeked.is_some() {
+            return Ok(true);
+        }
+
+        // A serial port cannot be asked whether it has data without reading it
+        match self.serial.read() {
+            Ok(rx) => {
+                self.peeked = Some(rx);
+                Ok(true)
+            }
+            Err(nb::Error::WouldBlock) => Ok(false),
+            Err(nb::Error::Other(err)) => Err(err),
+        }
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, E> {
+        // Reading from a serial port never sends anything
+        match self.read(buf) {
+            Ok(read) => Ok(read),
+            Err(nb::Error::WouldBlock) => Ok(0),
+            Err(nb::Error::Other(err)) => Err(err),
+        }
+    }
+}
+
+/// Bytes in the RX queue at or above which `FlowControl` deasserts RTS by default.
+pub const DEFAULT_RTS_HIGH_WATER: usize = 384;
+/// Bytes in the RX queue at or below which `FlowControl` asserts RTS again by default.
+pub const DEFAULT_RTS_LOW_WATER: usize = 128;
+
+/// Errors from a UART with flow control lines.
+#[derive(Copy, Clone, Debug)]
+pub enum FlowControlError<E, CE, RE> {
+    Serial(E),
+    /// Failure reading CTS.
+    Cts(CE),
+    /// Failure driving RTS.
+    Rts(RE),
+}
+
+/// UART with RTS/CTS hardware flow control, both lines active low as on the radio.
+///
+/// Writes return `WouldBlock` while the radio deasserts CTS, so blocking writes wait for it. RTS
+/// is asserted on the first read, and with `FlowControlTransport` is deasserted while the RX
+/// queue is filled up to the high water mark until it drains to the low water mark. Either line
+/// can be left out.
+pub struct FlowControl<U, CTS, RTS> {
+    serial: U,
+    cts: Option<CTS>,
+    rts: Option<RTS>,
+    low_water: usize,
+    high_water: usize,
+    /// Bytes waiting in the RX queue, as last told by `set_rx_level`.
+    queued: usize,
+    rts_asserted: bool,
+}
+
+impl<E, CE, RE, U, CTS, RTS> FlowControl<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    pub fn new(uart: U, cts: Option<CTS>, rts: Option<RTS>) -> FlowControl<U, CTS, RTS> {
+        FlowControl {
+            serial: uart,
+            cts,
+            rts,
+            low_water: DEFAULT_RTS_LOW_WATER,
+            high_water: DEFAULT_RTS_HIGH_WATER,
+            queued: 0,
+            rts_asserted: false,
+        }
+    }
+
+    /// Releases the UART and the CTS and RTS pins.
+    pub fn free(self) -> (U, Option<CTS>, Option<RTS>) {
+        (self.serial, self.cts, self.rts)
+    }
+
+    /// Sets the RX queue levels RTS is asserted again at or below and deasserted at or above,
+    /// which default to `DEFAULT_RTS_LOW_WATER` and `DEFAULT_RTS_HIGH_WATER`. The high water mark
+    /// should leave room for the few bytes the radio can still send once RTS is deasserted.
+    pub fn set_rts_thresholds(&mut self, low_water: usize, high_water: usize) {
+        self.low_water = low_water.min(high_water);
+        self.high_water = high_water;
+    }
+
+    /// Whether RTS is asserted, letting the radio send.
+    pub fn rts_asserted(&self) -> bool {
+        self.rts_asserted
+    }
+
+    /// Tells how many received bytes are waiting to be handled, updating RTS.
+    pub fn set_rx_level(&mut self, queued: usize) -> Result<(), RE> {
+        self.queued = queued;
+        self.update_rts()
+    }
+
+    fn update_rts(&mut self) -> Result<(), RE> {
+        let rts = match self.rts {
+            Some(ref mut rts) => rts,
+            None => return Ok(()),
+        };
+        if self.rts_asserted && self.queued >= self.high_water {
+            rts.set_high()?;
+            self.rts_asserted = false;
+        } else if !self.rts_asserted && self.queued <= self.low_water {
+            rts.set_low()?;
+            self.rts_asserted = true;
+        }
+        Ok(())
+    }
+}
+
+impl<E, CE, RE, U, CTS, RTS> Read<u8> for FlowControl<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    type Error = FlowControlError<E, CE, RE>;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.update_rts()
+            .map_err(|err| nb::Error::Other(FlowControlError::Rts(err)))?;
+        self.serial.read().map_err(|err| match err {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(err) => nb::Error::Other(FlowControlError::Serial(err)),
+        })
+    }
+}
+
+impl<E, CE, RE, U, CTS, RTS> Write<u8> for FlowControl<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    type Error = FlowControlError<E, CE, RE>;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        if let Some(ref cts) = self.cts {
+            if cts
+                .is_high()
+                .map_err(|err| nb::Error::Other(FlowControlError::Cts(err)))?
+            {
+                return Err(nb::Error::WouldBlock);
+            }
+        }
+        self.serial.write(word).map_err(|err| match err {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(err) => nb::Error::Other(FlowControlError::Serial(err)),
+        })
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.serial.flush().map_err(|err| match err {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(err) => nb::Error::Other(FlowControlError::Serial(err)),
+        })
+    }
+}
+
+impl<E, CE, RE, U, CTS, RTS> BlockingWriteDefault<u8> for FlowControl<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+}
+
+/// Serial port link with RTS/CTS hardware flow control, driving RTS from how full the RX queue
+/// is. See `FlowControl`.
+pub struct FlowControlTransport<U, CTS, RTS> {
+    inner: SerialTransport<FlowControl<U, CTS, RTS>>,
+}
+
+impl<E, CE, RE, U, CTS, RTS> FlowControlTransport<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    pub fn new(uart: U, cts: Option<CTS>, rts: Option<RTS>) -> FlowControlTransport<U, CTS, RTS> {
+        FlowControlTransport {
+            inner: SerialTransport::new(FlowControl::new(uart, cts, rts)),
+        }
+    }
+
+    /// Releases the UART and the CTS and RTS pins. A byte already read to check for pending data
+    /// is lost.
+    pub fn free(self) -> (U, Option<CTS>, Option<RTS>) {
+        self.inner.free().free()
+    }
+
+    /// See `FlowControl::set_rts_thresholds`.
+    pub fn set_rts_thresholds(&mut self, low_water: usize, high_water: usize) {
+        self.inner.serial.set_rts_thresholds(low_water, high_water);
+    }
+
+    /// Whether RTS is asserted, letting the radio send.
+    pub fn rts_asserted(&self) -> bool {
+        self.inner.serial.rts_asserted()
+    }
+}
+
+impl<E, CE, RE, U, CTS, RTS> XBeeTransport for FlowControlTransport<U, CTS, RTS>
+where
+    U: Read<u8, Error = E> + Write<u8, Error = E>,
+    CTS: InputPin<Error = CE>,
+    RTS: OutputPin<Error = RE>,
+{
+    type Error = FlowControlError<E, CE, RE>;
+
+    fn write(&mut self, buf: &[u8]) -> nb::Result<usize, Self::Error> {
+        self.inner.write(buf)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Self::Error> {
+        self.inner.read(buf)
+    }
+
+    fn rx_pending(&mut self) -> Result<bool, Self::Error> {
+        self.inner.rx_pending()
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.inner.read_buffered(buf)
+    }
+
+    fn rx_queue_level(&mut self, queued: usize) -> Result<(), Self::Error> {
+        self.inner
+            .serial
+            .set_rx_level(queued)
+            .map_err(FlowControlError::Rts)
+    }
+}